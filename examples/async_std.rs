@@ -11,7 +11,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
     );
 
     let mut con = pool.get().await?;
-    con.set(b"key", b"value").await?;
+    con.set::<_, _, ()>(b"key", b"value").await?;
     let value: Vec<u8> = con.get(b"key").await?;
     assert_eq!(value, b"value");
     let exists: bool = con.exists(b"key").await?;
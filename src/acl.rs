@@ -0,0 +1,46 @@
+//! ACL user the connections of a pool authenticate as.
+
+/// ACL user the connections authenticate as once created, see
+/// [`RedisConnectionManager::with_acl_user`](crate::RedisConnectionManager::with_acl_user).
+///
+/// Redis has no permissions per connection: the permissions (commands, key & channel
+/// patterns, selectors) belong to the user, shared by every connection authenticated as
+/// that user, including those of other applications. A connection can only be restricted
+/// by authenticating it as a user with fewer permissions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AclUser {
+    /// Name of the user (`AUTH <username> <password>`)
+    pub username: String,
+    /// Password of the user
+    pub password: String,
+    /// Rules defining the user with `ACL SETUSER` before authenticating, e.g. `on`,
+    /// `>password`, `+get`, `~cache:*` or, with Redis 7, selectors such as
+    /// `(~audit:* +xadd)`.
+    ///
+    /// `ACL SETUSER` changes the user on the server for every client, it requires the
+    /// connection to be authenticated as an administrator (by the url of the client) and
+    /// the rules are added to those the user already has: start them with `reset` to
+    /// define the user from scratch. Leave them empty to authenticate as a user defined
+    /// on the server.
+    pub rules: Vec<String>,
+}
+
+impl AclUser {
+    /// The commands defining the user, then authenticating as it
+    pub(crate) fn commands(&self) -> Vec<redis::Cmd> {
+        let mut commands = Vec::new();
+        if !self.rules.is_empty() {
+            let mut setuser = redis::cmd("ACL");
+            setuser
+                .arg("SETUSER")
+                .arg(&self.username)
+                .arg(&self.rules[..]);
+            commands.push(setuser);
+        }
+        let mut auth = redis::cmd("AUTH");
+        auth.arg(&self.username).arg(&self.password);
+        commands.push(auth);
+        commands
+    }
+}
@@ -0,0 +1,110 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    thread::{self, ThreadId},
+};
+
+use deadpool::managed::Object;
+use redis::RedisError;
+use tokio::sync::OwnedSemaphorePermit;
+
+use crate::RedisConnection;
+
+/// A connection parked for a thread, with its slot of a resizable pool
+pub(crate) type Parked = (
+    Object<RedisConnection, RedisError>,
+    Option<OwnedSemaphorePermit>,
+);
+
+/// Connections given back by each thread, handed out to the same thread first, see
+/// [`RedisPoolBuilder::with_thread_affinity`](crate::RedisPoolBuilder::with_thread_affinity).
+pub(crate) struct Affinity {
+    /// Maximum number of connections parked per thread
+    slots: usize,
+    state: Mutex<AffinityState>,
+}
+
+#[derive(Default)]
+struct AffinityState {
+    parked: HashMap<ThreadId, Vec<Parked>>,
+    /// Number of callers acquiring a connection from the pool while it has no idle one:
+    /// connections are given back to the pool instead of being parked while there are
+    /// some, not to starve them
+    acquiring: usize,
+}
+
+impl Affinity {
+    pub(crate) fn new(slots: usize) -> Self {
+        Self {
+            slots,
+            state: Default::default(),
+        }
+    }
+
+    /// Take a connection parked by the current thread, or by any thread if the pool has
+    /// no `idle` connection.
+    ///
+    /// Without such connection, a caller which may wait is registered as acquiring a
+    /// connection from the pool until the returned guard is dropped.
+    pub(crate) fn take(&self, idle: bool) -> Result<Parked, Option<Acquiring<'_>>> {
+        let mut state = self.state.lock().unwrap();
+        let own = state
+            .parked
+            .get_mut(&thread::current().id())
+            .and_then(Vec::pop);
+        let parked = match own {
+            Some(parked) => Some(parked),
+            None if !idle => state.parked.values_mut().find_map(Vec::pop),
+            None => None,
+        };
+        match parked {
+            Some(parked) => Ok(parked),
+            None if idle => Err(None),
+            None => {
+                state.acquiring += 1;
+                Err(Some(Acquiring(self)))
+            }
+        }
+    }
+
+    /// Park `connection` for the current thread, unless its slots are all taken or callers
+    /// wait for a connection of the pool (`waiting`, or acquiring one): it is then
+    /// returned, to be given back to the pool.
+    pub(crate) fn park(&self, connection: Parked, waiting: bool) -> Option<Parked> {
+        let mut state = self.state.lock().unwrap();
+        if waiting || state.acquiring > 0 {
+            return Some(connection);
+        }
+        let slots = self.slots;
+        let parked = state.parked.entry(thread::current().id()).or_default();
+        if parked.len() >= slots {
+            return Some(connection);
+        }
+        parked.push(connection);
+        None
+    }
+
+    /// Remove the parked connections for which `remove` returns true
+    pub(crate) fn evict<F>(&self, mut remove: F) -> Vec<Parked>
+    where
+        F: FnMut(&RedisConnection) -> bool,
+    {
+        let mut state = self.state.lock().unwrap();
+        let mut evicted = Vec::new();
+        for parked in state.parked.values_mut() {
+            let (removed, kept) = parked.drain(..).partition(|(object, _)| remove(object));
+            *parked = kept;
+            evicted.extend::<Vec<_>>(removed);
+        }
+        evicted
+    }
+}
+
+/// A caller acquiring a connection from the pool
+pub(crate) struct Acquiring<'a>(&'a Affinity);
+
+impl Drop for Acquiring<'_> {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().acquiring -= 1;
+    }
+}
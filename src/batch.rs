@@ -0,0 +1,284 @@
+use std::{
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use futures_util::{
+    future::{self, Either},
+    lock::Mutex as AsyncMutex,
+};
+use redis::{aio::ConnectionLike, Cmd, Pipeline, RedisError, RedisFuture, RedisResult, Value};
+use tokio::sync::oneshot;
+
+use crate::{errors, split, PooledConnection, RedisPool};
+
+/// A connection coalescing the commands issued in rapid succession into pipelines,
+/// obtained with [`RedisPool::batched`].
+///
+/// A command issued through this connection, or any of its clones, waits up to the
+/// batching window for other commands before they are all sent at once, as a single
+/// pipeline: concurrent callers share a round-trip instead of waiting for a connection
+/// each. The commands are sent in the order they were issued. The connection of the
+/// pool is acquired on the first flush and held until the last clone is dropped.
+///
+/// Only read-only commands (`GET`, `HGETALL`, `ZRANGE`...) are batched: the other
+/// commands, like pipelines, are sent on their own right away, after the commands
+/// queued before them, so that each of them gets its own reply. Since the replies
+/// following an error reply are not read, a batch getting an error reply is sent again
+/// one command at a time on another connection, for each command to get its own reply
+/// too. Blocking commands and transactions (`WATCH`) must not be issued on a batched
+/// connection as they would hold or alter it for every caller: use `get()` for them.
+///
+/// A flush cancelled while waiting for the replies, e.g. when the command of the caller
+/// flushing times out, leaves them pending: the connection is then dropped and the next
+/// batches are sent on another connection.
+///
+/// ```rust
+/// # #[cfg(feature = "fake")]
+/// # async_std::task::block_on(async {
+/// use std::time::Duration;
+/// use redis::AsyncCommands;
+/// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+///
+/// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 5);
+/// let con = pool.batched(Duration::from_millis(10));
+/// let (mut first, mut second, mut third) = (con.clone(), con.clone(), con.clone());
+///
+/// let ((), missing, value): ((), Option<String>, String) = futures_util::try_join!(
+///     first.set("key", "value"),
+///     second.get("missing"),
+///     third.get("key"),
+/// )?;
+/// assert_eq!(missing, None);
+/// assert_eq!(value, "value");
+/// assert_eq!(con.flushes(), 1);
+///
+/// // an error reply only fails its own command
+/// let invalid = redis::cmd("GET").arg("key").arg("extra").clone();
+/// let (value, invalid, written) = futures_util::join!(
+///     first.get::<_, String>("key"),
+///     invalid.query_async::<_, String>(&mut second),
+///     third.set::<_, _, ()>("written", "value"),
+/// );
+/// assert_eq!(value?, "value");
+/// assert_eq!(invalid.unwrap_err().code(), Some("ERR"));
+/// assert!(written.is_ok());
+/// assert_eq!(con.flushes(), 2);
+///
+/// // the replies to a cancelled flush are not read by the next one
+/// first.set::<_, _, ()>("other", "other value").await?;
+/// let mut admin = pool.get().await?;
+/// redis::cmd("CLIENT")
+///     .arg("PAUSE")
+///     .arg(100)
+///     .query_async::<_, ()>(&mut *admin)
+///     .await?;
+/// drop(admin);
+/// let timeout = Duration::from_millis(30);
+/// assert!(async_std::future::timeout(timeout, first.get::<_, String>("key"))
+///     .await
+///     .is_err());
+/// let value: String = second.get("other").await?;
+/// assert_eq!(value, "other value");
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// # })
+/// # .unwrap();
+/// ```
+#[derive(Clone)]
+pub struct BatchedConnection(Arc<Batcher>);
+
+struct Batcher {
+    pool: RedisPool,
+    window: Duration,
+    /// Commands waiting for the next flush, in issue order
+    queue: Mutex<Vec<Queued>>,
+    /// Connection the batches are sent on, acquired on the first flush
+    con: AsyncMutex<Option<PooledConnection>>,
+    db: AtomicI64,
+    flushes: AtomicU64,
+}
+
+struct Queued {
+    cmd: Cmd,
+    reply: oneshot::Sender<RedisResult<Value>>,
+}
+
+impl RedisPool {
+    /// Get a connection batching the commands issued within `window` of each other into
+    /// pipelines, see [`BatchedConnection`].
+    ///
+    /// A window of a few hundred microseconds is usually enough to coalesce the commands
+    /// of concurrent callers; every command waits up to the window before being sent.
+    pub fn batched(&self, window: Duration) -> BatchedConnection {
+        BatchedConnection(Arc::new(Batcher {
+            pool: self.clone(),
+            window,
+            queue: Default::default(),
+            con: Default::default(),
+            db: AtomicI64::new(0),
+            flushes: AtomicU64::new(0),
+        }))
+    }
+}
+
+impl BatchedConnection {
+    /// Number of batches sent so far
+    pub fn flushes(&self) -> u64 {
+        self.0.flushes.load(Ordering::Relaxed)
+    }
+
+    /// Queue `cmd` for the next flush and wait for its reply, or send it on its own if it
+    /// is not read-only
+    async fn submit(&self, cmd: &Cmd) -> RedisResult<Value> {
+        if !split::is_read(cmd) {
+            let mut con = self.0.con.lock().await;
+            self.0.flush(&mut con).await;
+            return self.0.send(&mut con, cmd).await;
+        }
+        let (reply, mut receiver) = oneshot::channel();
+        self.0.queue.lock().unwrap().push(Queued {
+            cmd: cmd.clone(),
+            reply,
+        });
+        // the first caller whose window elapses flushes the whole queue
+        let window = Box::pin(self.0.pool.shared.runtime().sleep(self.0.window));
+        if let Either::Left((reply, _)) = future::select(&mut receiver, window).await {
+            return reply.unwrap_or_else(|_| Err(cancelled()));
+        }
+        let mut con = self.0.con.lock().await;
+        self.0.flush(&mut con).await;
+        drop(con);
+        receiver.await.unwrap_or_else(|_| Err(cancelled()))
+    }
+}
+
+impl Batcher {
+    /// Send the queued commands as a single pipeline
+    async fn flush(&self, con: &mut Option<PooledConnection>) {
+        let batch = std::mem::take(&mut *self.queue.lock().unwrap());
+        if batch.is_empty() {
+            return;
+        }
+        self.flushes.fetch_add(1, Ordering::Relaxed);
+        let replies = match self.connection(con).await {
+            Ok(connection) => match &batch[..] {
+                [queued] => connection
+                    .req_packed_command(&queued.cmd)
+                    .await
+                    .map(|value| vec![value]),
+                _ => {
+                    let mut pipeline = Pipeline::with_capacity(batch.len());
+                    for queued in &batch {
+                        pipeline.add_command(queued.cmd.clone());
+                    }
+                    connection
+                        .req_packed_commands(&pipeline, 0, batch.len())
+                        .await
+                }
+            },
+            Err(err) => Err(err),
+        };
+        match replies {
+            Ok(values) => {
+                for (queued, value) in batch.into_iter().zip(values) {
+                    let _ = queued.reply.send(Ok(value));
+                }
+            }
+            Err(err) if batch.len() > 1 && !err.is_io_error() => {
+                // the replies following the error reply are left unread: the commands,
+                // all read-only, are sent again for each of them to get its own reply
+                discard(con);
+                for queued in batch {
+                    let reply = self.send(con, &queued.cmd).await;
+                    let _ = queued.reply.send(reply);
+                }
+            }
+            Err(err) => {
+                if err.is_io_error() {
+                    discard(con);
+                }
+                for queued in batch {
+                    let _ = queued.reply.send(Err(errors::clone_error(&err)));
+                }
+            }
+        }
+    }
+
+    /// Send `cmd` on its own
+    async fn send(&self, con: &mut Option<PooledConnection>, cmd: &Cmd) -> RedisResult<Value> {
+        let result = self.connection(con).await?.req_packed_command(cmd).await;
+        if matches!(&result, Err(err) if err.is_io_error()) {
+            discard(con);
+        }
+        result
+    }
+
+    /// The connection of the batcher, acquired from the pool if needed
+    async fn connection<'a>(
+        &self,
+        con: &'a mut Option<PooledConnection>,
+    ) -> RedisResult<&'a mut PooledConnection> {
+        if matches!(con, Some(held) if held.broken) {
+            // a flush was cancelled while waiting for its replies, left unread
+            *con = None;
+        }
+        if con.is_none() {
+            let acquired = self.pool.get().await.map_err(errors::from_pool_error)?;
+            self.db.store(acquired.get_db(), Ordering::Relaxed);
+            *con = Some(acquired);
+        }
+        Ok(con.as_mut().unwrap())
+    }
+}
+
+/// Give the connection back to the pool to be dropped, its replies being out of sync
+fn discard(con: &mut Option<PooledConnection>) {
+    if let Some(mut con) = con.take() {
+        con.broken = true;
+    }
+}
+
+/// Error of a command whose batch was dropped before being sent
+fn cancelled() -> RedisError {
+    (
+        redis::ErrorKind::ClientError,
+        "Batch cancelled before being sent",
+    )
+        .into()
+}
+
+impl ConnectionLike for BatchedConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        Box::pin(self.submit(cmd))
+    }
+
+    /// Pipelines are sent on their own, after the commands queued before them
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        Box::pin(async move {
+            let mut con = self.0.con.lock().await;
+            self.0.flush(&mut con).await;
+            let result = self
+                .0
+                .connection(&mut con)
+                .await?
+                .req_packed_commands(cmd, offset, count)
+                .await;
+            if result.is_err() {
+                discard(&mut con);
+            }
+            result
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        self.0.db.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use redis::RedisResult;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::errors;
+
+/// Limit of the connections open at once by the managers sharing it, e.g. the pools of a
+/// service to the same server, to stay below its `maxclients`, see
+/// [`RedisConnectionManager::with_connection_budget`](crate::RedisConnectionManager::with_connection_budget).
+///
+/// Clones share the same budget. A connection takes a share of the budget from its
+/// creation until it is closed, idle or not: idle connections of a pool are not closed to
+/// make room for the connections of another pool. Creating a connection while the budget
+/// is exhausted fails right away with an error telling so ([`is_budget_exhausted`]),
+/// like a server which reached its `maxclients`; use
+/// [`CreateFailurePolicy::Retry`](crate::CreateFailurePolicy::Retry) to wait for a share.
+///
+/// [`is_budget_exhausted`]: crate::is_budget_exhausted
+///
+/// ```rust
+/// # #[cfg(feature = "fake")]
+/// # async_std::task::block_on(async {
+/// use std::time::Duration;
+/// use redis_async_pool::{
+///     deadpool::managed::PoolError, is_budget_exhausted, ConnectionBudget,
+///     FakeRedisConnectionManager, RedisPool,
+/// };
+///
+/// let budget = ConnectionBudget::new(4);
+/// let pool = || -> std::io::Result<RedisPool> {
+///     let manager =
+///         FakeRedisConnectionManager::new(true, None)?.with_connection_budget(budget.clone());
+///     Ok(RedisPool::new(manager, 3))
+/// };
+/// let (first, second) = (pool()?, pool()?);
+///
+/// let served = futures_util::future::join_all((0..20).map(|i| {
+///     let (pool, budget) = (if i % 2 == 0 { &first } else { &second }, &budget);
+///     async move {
+///         match pool.get().await {
+///             Ok(_con) => {
+///                 assert!(budget.open() <= 4);
+///                 async_std::task::sleep(Duration::from_millis(5)).await;
+///                 true
+///             }
+///             Err(PoolError::Backend(err)) if is_budget_exhausted(&err) => false,
+///             Err(err) => panic!("{}", err),
+///         }
+///     }
+/// }))
+/// .await;
+/// assert!(served.iter().any(|served| *served));
+/// assert_eq!(budget.open(), first.stats().size + second.stats().size);
+/// assert!(budget.open() <= 4);
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// # })
+/// # .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConnectionBudget {
+    /// One permit per connection which can be opened
+    semaphore: Arc<Semaphore>,
+    limit: usize,
+}
+
+impl ConnectionBudget {
+    /// Create a budget of `limit` connections open at once
+    pub fn new(limit: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(limit)),
+            limit,
+        }
+    }
+
+    /// Maximum number of connections open at once
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Number of connections currently open by the managers sharing this budget
+    pub fn open(&self) -> usize {
+        self.limit - self.semaphore.available_permits()
+    }
+
+    /// Take the share of a new connection, given back once dropped
+    pub(crate) fn acquire(&self) -> RedisResult<OwnedSemaphorePermit> {
+        self.semaphore
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| errors::budget_exhausted(self.limit))
+    }
+}
@@ -0,0 +1,549 @@
+use std::{error::Error, fmt, time::Duration};
+
+use deadpool::managed::{PoolConfig, PoolError, Timeouts};
+use redis::RedisError;
+
+use crate::{runtime::Runtime, RedisConnectionManager, RedisPool, Ttl};
+
+/// Builder of a [`RedisPool`], obtained with [`RedisPool::builder`].
+///
+/// ## Timeouts & runtime
+///
+/// Timeouts need a timer from the async runtime the pool is used on. Unless set
+/// with [`with_runtime`](Self::with_runtime), the runtime is detected each time a
+/// timer is needed: tokio when called from within a tokio runtime, async-std otherwise.
+/// When a runtime is set and the pool is used outside of it, acquiring a connection
+/// with timeouts fails with an `InvalidClientConfig` error instead of never timing out.
+///
+/// ```rust
+/// # #[cfg(all(feature = "fake", feature = "tokio-comp"))]
+/// # async_std::task::block_on(async {
+/// use std::time::Duration;
+/// use redis_async_pool::{
+///     deadpool::managed::{PoolError, TimeoutType, Timeouts},
+///     FakeRedisConnectionManager, RedisPool, Runtime,
+/// };
+///
+/// let build = |runtime| -> Result<RedisPool, Box<dyn std::error::Error>> {
+///     let pool = RedisPool::builder(FakeRedisConnectionManager::new(true, None)?)
+///         .max_size(1)
+///         .timeouts(Timeouts {
+///             wait: Some(Duration::from_millis(100)),
+///             create: Some(Duration::from_secs(1)),
+///             recycle: Some(Duration::from_millis(100)),
+///         })
+///         .with_runtime(runtime)
+///         .build()?;
+///     Ok(pool)
+/// };
+///
+/// // the timeouts fire on the selected runtime
+/// let pool = build(Runtime::AsyncStd)?;
+/// let _con = pool.get().await?;
+/// assert!(matches!(pool.get().await, Err(PoolError::Timeout(TimeoutType::Wait))));
+///
+/// // used outside of the selected runtime, the pool fails rather than never timing out
+/// let pool = build(Runtime::Tokio)?;
+/// match pool.get().await {
+///     Err(PoolError::Backend(err)) => {
+///         assert_eq!(err.kind(), redis::ErrorKind::InvalidClientConfig)
+///     }
+///     _ => panic!("the tokio runtime should be required"),
+/// }
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// # })
+/// # .unwrap();
+/// ```
+pub struct RedisPoolBuilder {
+    manager: Result<RedisConnectionManager, RedisError>,
+    config: PoolConfig,
+    runtime: Option<Runtime>,
+    max_size_limit: Option<usize>,
+    name: Option<String>,
+    thread_affinity: Option<usize>,
+    #[cfg(feature = "local-cache")]
+    local_cache: Option<usize>,
+    prefill: bool,
+    #[cfg(feature = "log")]
+    max_borrow_duration: Option<Duration>,
+}
+
+impl RedisPoolBuilder {
+    pub(crate) fn new(manager: Result<RedisConnectionManager, RedisError>) -> Self {
+        Self {
+            manager,
+            config: PoolConfig::default(),
+            runtime: None,
+            max_size_limit: None,
+            name: None,
+            thread_affinity: None,
+            #[cfg(feature = "local-cache")]
+            local_cache: None,
+            prefill: false,
+            #[cfg(feature = "log")]
+            max_borrow_duration: None,
+        }
+    }
+
+    /// Set the maximum number of connections of the pool.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.config.max_size = max_size;
+        self
+    }
+
+    /// Set the timeouts applied by `RedisPool::get()`, see
+    /// [`with_wait_timeout`](Self::with_wait_timeout),
+    /// [`with_create_timeout`](Self::with_create_timeout) &
+    /// [`with_recycle_timeout`](Self::with_recycle_timeout) to set them one by one.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::{Duration, Instant};
+    /// use redis_async_pool::{
+    ///     deadpool::managed::{PoolError, TimeoutType},
+    ///     FakeRedisConnectionManager, PooledConnection, RedisConnectionManager, RedisPool,
+    ///     RedisPoolBuilder, Runtime,
+    /// };
+    ///
+    /// type Set = fn(RedisPoolBuilder) -> RedisPoolBuilder;
+    /// let build = |manager: RedisConnectionManager, set: Set| {
+    ///     set(RedisPool::builder(manager).max_size(2).with_runtime(Runtime::AsyncStd)).build()
+    /// };
+    /// // stall the commands of every connection of the server, through a connection held
+    /// // by the caller
+    /// async fn pause(pool: &RedisPool) -> Result<PooledConnection, Box<dyn std::error::Error>> {
+    ///     let mut con = pool.get().await?;
+    ///     redis::cmd("CLIENT")
+    ///         .arg("PAUSE")
+    ///         .arg(300)
+    ///         .query_async::<_, ()>(&mut *con)
+    ///         .await?;
+    ///     Ok(con)
+    /// }
+    ///
+    /// // no slot
+    /// let pool = build(FakeRedisConnectionManager::new(true, None)?, |builder| {
+    ///     builder.with_wait_timeout(Duration::from_millis(50))
+    /// })?;
+    /// let _held = (pool.get().await?, pool.get().await?);
+    /// assert!(matches!(pool.get().await, Err(PoolError::Timeout(TimeoutType::Wait))));
+    ///
+    /// // a creation querying the stalled server
+    /// let manager = FakeRedisConnectionManager::new(true, None)?.with_client_id();
+    /// let pool = build(manager, |builder| {
+    ///     builder.with_create_timeout(Duration::from_millis(50))
+    /// })?;
+    /// let _admin = pause(&pool).await?;
+    /// let started = Instant::now();
+    /// assert!(matches!(pool.get().await, Err(PoolError::Timeout(TimeoutType::Create))));
+    /// assert!(started.elapsed() < Duration::from_millis(300));
+    ///
+    /// // a stalled recycle check: the connection is replaced
+    /// let pool = build(FakeRedisConnectionManager::new(true, None)?, |builder| {
+    ///     builder.with_recycle_timeout(Duration::from_millis(50))
+    /// })?;
+    /// let con = pool.get().await?;
+    /// let stalled = con.id();
+    /// let _admin = pause(&pool).await?;
+    /// drop(con);
+    /// let started = Instant::now();
+    /// assert_ne!(pool.get().await?.id(), stalled);
+    /// assert!(started.elapsed() < Duration::from_millis(300));
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.config.timeouts = timeouts;
+        self
+    }
+
+    /// Bound the whole acquisition of a connection by `RedisPool::get()`: waiting for a
+    /// slot, then creating or recycling the connection. It fails with
+    /// `PoolError::Timeout(TimeoutType::Wait)`.
+    ///
+    /// The creation and the recycling are also bounded by their own timeouts, so a slow
+    /// creation fails with the first of the wait and create timeouts to elapse.
+    pub fn with_wait_timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeouts.wait = Some(timeout);
+        self
+    }
+
+    /// Bound the creation of a connection, hooks included. It fails with
+    /// `PoolError::Timeout(TimeoutType::Create)`.
+    pub fn with_create_timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeouts.create = Some(timeout);
+        self
+    }
+
+    /// Bound the recycling of a connection, hooks and check included.
+    ///
+    /// A connection whose recycling times out is dropped and the pool moves on to
+    /// another connection, or creates one: this timeout is not reported to the caller.
+    pub fn with_recycle_timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeouts.recycle = Some(timeout);
+        self
+    }
+
+    /// Set the deadpool `config` (maximum size & timeouts).
+    pub fn config(mut self, config: PoolConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Set the runtime providing the timers of the timeouts instead of detecting it.
+    pub fn with_runtime(mut self, runtime: Runtime) -> Self {
+        self.runtime = Some(runtime);
+        self
+    }
+
+    /// Park up to `slots` connections given back by each thread for that thread, instead of
+    /// returning them to the pool, so that the threads of a multi-threaded runtime keep
+    /// reusing the same connections rather than handing them over to each other.
+    ///
+    /// A thread is handed out a connection it parked first; when the pool has no idle
+    /// connection, the connections parked by the other threads are handed out too.
+    /// Connections are not parked while callers are acquiring one from the pool, so that
+    /// they are not starved. Parked connections count as in use in
+    /// [`RedisPool::stats`], and are recycled when handed out again.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::thread;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let pool = RedisPool::builder(FakeRedisConnectionManager::new(true, None)?)
+    ///     .max_size(8)
+    ///     .with_thread_affinity(1)
+    ///     .build()?;
+    /// pool.warm_up(8, 8).await?;
+    /// let workers: Vec<_> = (0..4)
+    ///     .map(|_| {
+    ///         let pool = pool.clone();
+    ///         thread::spawn(move || {
+    ///             async_std::task::block_on(async {
+    ///                 let (mut previous, mut reused) = (None, 0);
+    ///                 for _ in 0..100 {
+    ///                     let id = pool.get().await.unwrap().id();
+    ///                     reused += (previous == Some(id)) as usize;
+    ///                     previous = Some(id);
+    ///                 }
+    ///                 reused
+    ///             })
+    ///         })
+    ///     })
+    ///     .collect();
+    /// let reused: usize = workers.into_iter().map(|worker| worker.join().unwrap()).sum();
+    /// // most acquisitions hand out the connection the thread used last
+    /// assert!(reused >= 4 * 99 * 9 / 10, "{} reuses", reused);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_thread_affinity(mut self, slots: usize) -> Self {
+        self.thread_affinity = Some(slots);
+        self
+    }
+
+    /// Allow the pool to be resized with [`RedisPool::resize`] up to `limit` connections.
+    ///
+    /// The pool is allocated for `limit` connections.
+    pub fn max_size_limit(mut self, limit: usize) -> Self {
+        self.max_size_limit = Some(limit);
+        self
+    }
+
+    /// Name the pool, to tell several pools apart: the name is given in the log messages
+    /// about its connections (`log` feature) and as the `pool` label of its metrics
+    /// (`prometheus` feature).
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Log a warning when a connection is held longer than `duration` after it has been
+    /// handed out, e.g. forgotten in a long lived scope: it holds a slot of the pool.
+    ///
+    /// The warning is logged once per checkout, when `duration` elapses, with the id of
+    /// the connection and the name of the pool: the location of the `get()` call can not
+    /// be captured. A timer is started on the runtime of the pool for every checkout.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::{
+    ///     sync::atomic::{AtomicUsize, Ordering},
+    ///     time::Duration,
+    /// };
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool, Runtime};
+    ///
+    /// // count the long borrows logged
+    /// static HELD: AtomicUsize = AtomicUsize::new(0);
+    /// struct Counter;
+    /// impl log::Log for Counter {
+    ///     fn enabled(&self, _: &log::Metadata) -> bool {
+    ///         true
+    ///     }
+    ///     fn log(&self, record: &log::Record) {
+    ///         let message = record.args().to_string();
+    ///         if record.level() == log::Level::Warn
+    ///             && message.contains("of pool sessions held for more than")
+    ///         {
+    ///             HELD.fetch_add(1, Ordering::Relaxed);
+    ///         }
+    ///     }
+    ///     fn flush(&self) {}
+    /// }
+    /// log::set_logger(&Counter).unwrap();
+    /// log::set_max_level(log::LevelFilter::Warn);
+    ///
+    /// let pool = RedisPool::builder(FakeRedisConnectionManager::new(true, None)?)
+    ///     .max_size(2)
+    ///     .with_runtime(Runtime::AsyncStd)
+    ///     .with_name("sessions")
+    ///     .with_max_borrow_duration(Duration::from_millis(50))
+    ///     .build()?;
+    /// // given back in time
+    /// drop(pool.get().await?);
+    /// let held = pool.get().await?;
+    /// async_std::task::sleep(Duration::from_millis(150)).await;
+    /// assert_eq!(HELD.load(Ordering::Relaxed), 1);
+    /// drop(held);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    #[cfg(feature = "log")]
+    pub fn with_max_borrow_duration(mut self, duration: Duration) -> Self {
+        self.max_borrow_duration = Some(duration);
+        self
+    }
+
+    /// Keep a copy of up to `capacity` values read with [`RedisPool::cache_get`], served
+    /// (possibly stale) instead of an error while the server is unavailable: connection
+    /// failures, pool timeouts or poisoning, `LOADING` & `OOM` errors.
+    ///
+    /// The least recently read keys are evicted first once the cache is full; a key read
+    /// as missing is evicted at once. The copies are not expired: a value is served
+    /// whatever its age until the server is back. Errors are still returned for the keys
+    /// not held.
+    ///
+    /// ```rust
+    /// # #[cfg(all(feature = "fake", feature = "local-cache"))]
+    /// # async_std::task::block_on(async {
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisConnectionManager, RedisPool};
+    ///
+    /// let pool = RedisPool::builder(FakeRedisConnectionManager::new(true, None)?)
+    ///     .with_local_cache(100)
+    ///     .build()?;
+    /// pool.set_key::<_, _, ()>("key", "value").await?;
+    /// let value: Option<String> = pool.cache_get("key").await?;
+    /// assert_eq!(value.as_deref(), Some("value"));
+    ///
+    /// // the server goes down: nothing listens on the new endpoint
+    /// let unreachable = redis::Client::open("redis://127.0.0.1:1")?;
+    /// pool.reconfigure(RedisConnectionManager::new(unreachable, true, None));
+    /// let stale: Option<String> = pool.cache_get("key").await?;
+    /// assert_eq!(stale.as_deref(), Some("value"));
+    /// assert!(pool.cache_get::<_, Option<String>>("other").await.is_err());
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    #[cfg(feature = "local-cache")]
+    pub fn with_local_cache(mut self, capacity: usize) -> Self {
+        self.local_cache = Some(capacity);
+        self
+    }
+
+    /// Open the connections of the pool, up to its maximum size, when it is created with
+    /// [`build_async`](Self::build_async) instead of creating them on demand.
+    ///
+    /// [`build`](Self::build) can not open connections: it fails with a
+    /// [`BuilderError::ConflictingOptions`] error when the pool is to be prefilled.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let pool = RedisPool::builder(FakeRedisConnectionManager::new(true, None)?)
+    ///     .max_size(4)
+    ///     .with_prefill(true)
+    ///     .build_async()
+    ///     .await?;
+    /// let stats = pool.stats();
+    /// assert_eq!((stats.size, stats.available), (4, 4));
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_prefill(mut self, prefill: bool) -> Self {
+        self.prefill = prefill;
+        self
+    }
+
+    /// Create the pool, opening its connections if [`with_prefill`](Self::with_prefill)
+    /// is set, all at once (see [`RedisPool::warm_up`]).
+    ///
+    /// The first connection failing to be created fails the creation of the pool with a
+    /// [`BuilderError::Prefill`] error.
+    pub async fn build_async(self) -> Result<RedisPool, BuilderError> {
+        let prefill = self.prefill;
+        let pool = self.build_pool()?;
+        if prefill {
+            let max_size = pool.stats().max_size;
+            pool.warm_up(max_size, max_size)
+                .await
+                .map_err(BuilderError::Prefill)?;
+        }
+        Ok(pool)
+    }
+
+    /// Create the pool, failing if it is misconfigured, see [`BuilderError`].
+    ///
+    /// Connections are created on demand: use [`build_async`](Self::build_async) to
+    /// create them beforehand, a pool to be [prefilled](Self::with_prefill) fails to be
+    /// built.
+    pub fn build(self) -> Result<RedisPool, BuilderError> {
+        if self.prefill {
+            return Err(BuilderError::ConflictingOptions(
+                "prefilling the pool requires build_async",
+            ));
+        }
+        self.build_pool()
+    }
+
+    /// Validate the options, then create the pool
+    fn build_pool(self) -> Result<RedisPool, BuilderError> {
+        let manager = self.manager.map_err(BuilderError::InvalidUrl)?;
+        match manager.connection_ttl {
+            Some(Ttl::Simple(ttl)) if ttl == Duration::from_secs(0) => {
+                return Err(BuilderError::InvalidTtl("the ttl is zero"))
+            }
+            Some(Ttl::Fuzzy { min, fuzz })
+                if min == Duration::from_secs(0) && fuzz == Duration::from_secs(0) =>
+            {
+                return Err(BuilderError::InvalidTtl(
+                    "the min & fuzz of the ttl are zero",
+                ))
+            }
+            Some(Ttl::Adaptive { base, .. }) if base == Duration::from_secs(0) => {
+                return Err(BuilderError::InvalidTtl("the base of the ttl is zero"))
+            }
+            Some(Ttl::Once) if self.prefill => {
+                return Err(BuilderError::ConflictingOptions(
+                    "prefilled connections are never used with Ttl::Once",
+                ))
+            }
+            _ => {}
+        }
+        if self.config.max_size == 0 {
+            return Err(BuilderError::ConflictingOptions("max_size is zero"));
+        }
+        if self.thread_affinity == Some(0) {
+            return Err(BuilderError::ConflictingOptions(
+                "thread affinity without any slot",
+            ));
+        }
+        #[cfg(feature = "local-cache")]
+        if self.local_cache == Some(0) {
+            return Err(BuilderError::ConflictingOptions(
+                "local cache without any capacity",
+            ));
+        }
+        if matches!(self.max_size_limit, Some(limit) if limit < self.config.max_size) {
+            return Err(BuilderError::ConflictingOptions(
+                "max_size_limit is lower than max_size",
+            ));
+        }
+        Ok(RedisPool::build(
+            manager,
+            self.config,
+            self.runtime,
+            self.max_size_limit,
+            self.name,
+            self.thread_affinity,
+            #[cfg(feature = "local-cache")]
+            self.local_cache,
+            #[cfg(feature = "log")]
+            self.max_borrow_duration,
+        ))
+    }
+}
+
+/// Misconfiguration of a pool reported by [`RedisPoolBuilder::build`], at startup
+/// rather than when connections are used.
+///
+/// ```rust
+/// use std::time::Duration;
+/// use redis_async_pool::{BuilderError, RedisConnectionManager, RedisPool, Ttl};
+///
+/// let manager = |ttl| RedisConnectionManager::from_url("redis://localhost:6379", true, ttl);
+///
+/// let pool = RedisPool::builder_from_url("localhost:6379", true, None).build();
+/// assert!(matches!(pool, Err(BuilderError::InvalidUrl(_))));
+///
+/// let pool = RedisPool::builder(manager(Some(Ttl::Simple(Duration::from_secs(0))))?).build();
+/// assert!(matches!(pool, Err(BuilderError::InvalidTtl(_))));
+///
+/// let pool = RedisPool::builder(manager(None)?).with_prefill(true).build();
+/// assert!(matches!(pool, Err(BuilderError::ConflictingOptions(_))));
+///
+/// let pool = RedisPool::builder(manager(None)?)
+///     .max_size(10)
+///     .max_size_limit(5)
+///     .build();
+/// assert!(matches!(pool, Err(BuilderError::ConflictingOptions(_))));
+///
+/// # async_std::task::block_on(async {
+/// let pool = RedisPool::builder(manager(Some(Ttl::Once))?)
+///     .with_prefill(true)
+///     .build_async()
+///     .await;
+/// assert!(matches!(pool, Err(BuilderError::ConflictingOptions(_))));
+///
+/// // nothing listens on port 1
+/// let pool = RedisPool::builder_from_url("redis://127.0.0.1:1", true, None)
+///     .with_prefill(true)
+///     .build_async()
+///     .await;
+/// assert!(matches!(pool, Err(BuilderError::Prefill(_))));
+/// # Ok::<_, redis::RedisError>(())
+/// # })?;
+/// # Ok::<_, redis::RedisError>(())
+/// ```
+#[derive(Debug)]
+pub enum BuilderError {
+    /// The url of the Redis server can not be parsed
+    InvalidUrl(RedisError),
+    /// The ttl of the connections is invalid, e.g. a zero duration
+    InvalidTtl(&'static str),
+    /// Options which can not be used together, or an invalid size of the pool
+    ConflictingOptions(&'static str),
+    /// A connection could not be opened by [`RedisPoolBuilder::build_async`]
+    Prefill(PoolError<RedisError>),
+}
+
+impl fmt::Display for BuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidUrl(err) => write!(f, "invalid redis url: {}", err),
+            Self::InvalidTtl(reason) => write!(f, "invalid connection ttl: {}", reason),
+            Self::ConflictingOptions(reason) => write!(f, "conflicting pool options: {}", reason),
+            Self::Prefill(err) => write!(f, "unable to prefill the pool: {}", err),
+        }
+    }
+}
+
+impl Error for BuilderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::InvalidUrl(err) => Some(err),
+            Self::Prefill(err) => Some(err),
+            Self::InvalidTtl(_) | Self::ConflictingOptions(_) => None,
+        }
+    }
+}
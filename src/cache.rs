@@ -0,0 +1,97 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use deadpool::managed::PoolError;
+use redis::{RedisError, Value};
+
+use crate::errors;
+
+/// Bounded in-memory copy of the values read with `RedisPool::cache_get`, served when the
+/// server is unavailable, see
+/// [`RedisPoolBuilder::with_local_cache`](crate::RedisPoolBuilder::with_local_cache).
+pub(crate) struct LocalCache {
+    /// Maximum number of keys held, the least recently used ones are evicted first
+    capacity: usize,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    /// Value & last use of each key
+    entries: HashMap<Vec<u8>, (Value, u64)>,
+    /// Keys by last use, oldest first
+    recency: BTreeMap<u64, Vec<u8>>,
+    clock: u64,
+}
+
+impl LocalCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Default::default(),
+        }
+    }
+
+    /// Record the `value` read for `key`, evicting the least recently used key if the
+    /// cache is full
+    pub(crate) fn insert(&self, key: Vec<u8>, value: Value) {
+        let mut state = self.state.lock().unwrap();
+        let used = state.tick();
+        if let Some((_, previous)) = state.entries.insert(key.clone(), (value, used)) {
+            state.recency.remove(&previous);
+        } else if state.entries.len() > self.capacity {
+            // `BTreeMap::pop_first` requires Rust 1.66
+            if let Some(&used) = state.recency.keys().next() {
+                if let Some(oldest) = state.recency.remove(&used) {
+                    state.entries.remove(&oldest);
+                }
+            }
+        }
+        state.recency.insert(used, key);
+    }
+
+    /// Forget `key`, e.g. once it is known to be deleted
+    pub(crate) fn remove(&self, key: &[u8]) {
+        let mut state = self.state.lock().unwrap();
+        if let Some((_, used)) = state.entries.remove(key) {
+            state.recency.remove(&used);
+        }
+    }
+
+    /// The last value read for `key`, if still held
+    pub(crate) fn get(&self, key: &[u8]) -> Option<Value> {
+        let mut state = self.state.lock().unwrap();
+        let used = state.tick();
+        let (value, previous) = state.entries.get_mut(key)?;
+        let previous = std::mem::replace(previous, used);
+        let value = value.clone();
+        let key = state.recency.remove(&previous)?;
+        state.recency.insert(used, key);
+        Some(value)
+    }
+}
+
+impl CacheState {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+}
+
+/// Tell whether the value could not be read because the server is unavailable, rather
+/// than because of the command itself
+pub(crate) fn is_unavailable(err: &PoolError<RedisError>) -> bool {
+    match err {
+        PoolError::Timeout(_) => true,
+        PoolError::Backend(err) => {
+            err.is_io_error()
+                || err.is_connection_refusal()
+                || err.is_connection_dropped()
+                || err.is_timeout()
+                || errors::is_pool_poisoned(err)
+                || errors::is_transient_server_error(err)
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use futures_util::lock::Mutex;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Resizable limit of the number of connections in use, enforced on top of deadpool
+/// whose size is fixed. Deadpool is sized for `limit`, the largest size allowed.
+pub(crate) struct Capacity {
+    /// One permit per connection which can be handed out
+    semaphore: Arc<Semaphore>,
+    max_size: AtomicUsize,
+    pub(crate) limit: usize,
+    /// Number of callers waiting for a permit
+    waiting: AtomicUsize,
+    resizing: Mutex<()>,
+}
+
+/// Decrements the number of waiters when the wait ends, even if interrupted
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Capacity {
+    pub(crate) fn new(max_size: usize, limit: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(max_size)),
+            max_size: AtomicUsize::new(max_size),
+            limit,
+            waiting: AtomicUsize::new(0),
+            resizing: Mutex::new(()),
+        }
+    }
+
+    pub(crate) fn max_size(&self) -> usize {
+        self.max_size.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn waiting(&self) -> usize {
+        self.waiting.load(Ordering::Relaxed)
+    }
+
+    /// Whether a caller would have to wait for a permit
+    pub(crate) fn exhausted(&self) -> bool {
+        self.semaphore.available_permits() == 0
+    }
+
+    /// Wait for a permit to hand out a connection; the permit is given back once dropped.
+    pub(crate) async fn acquire(&self) -> OwnedSemaphorePermit {
+        self.waiting.fetch_add(1, Ordering::Relaxed);
+        let _waiting = Waiting(&self.waiting);
+        self.semaphore.clone().acquire_owned().await
+    }
+
+    pub(crate) fn try_acquire(&self) -> Option<OwnedSemaphorePermit> {
+        self.semaphore.clone().try_acquire_owned().ok()
+    }
+
+    /// Set the number of connections which can be handed out, up to `limit`. Returns the
+    /// new size once the connections in use beyond it have been given back.
+    pub(crate) async fn resize(&self, max_size: usize) -> usize {
+        let _resizing = self.resizing.lock().await;
+        let max_size = max_size.min(self.limit);
+        let current = self.max_size();
+        if max_size > current {
+            self.semaphore.add_permits(max_size - current);
+            self.max_size.store(max_size, Ordering::Relaxed);
+        }
+        // one permit at a time so an interrupted shrink leaves a consistent size
+        for _ in max_size..current {
+            self.semaphore.acquire().await.forget();
+            self.max_size.fetch_sub(1, Ordering::Relaxed);
+        }
+        max_size
+    }
+}
@@ -0,0 +1,70 @@
+//! Fault injection, to exercise the timeout & retry paths of an application.
+
+use std::time::Duration;
+
+use rand::Rng;
+use redis::{ErrorKind, RedisError, RedisResult};
+
+use crate::runtime;
+
+const INJECTED_FAULT: &str = "Injected fault";
+
+/// Latency and faults injected by a manager, see
+/// [`RedisConnectionManager::with_chaos`](crate::RedisConnectionManager::with_chaos).
+///
+/// Rates are probabilities between `0.0` (never) and `1.0` (always); the latency is
+/// added before the fault is drawn. The default configuration injects nothing.
+///
+/// Enabled with the `chaos` feature.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChaosConfig {
+    /// Added to the creation of each connection
+    pub create_latency: Duration,
+    /// Probability for the creation of a connection to fail
+    pub create_fault_rate: f64,
+    /// Added to each command (or pipeline)
+    pub command_latency: Duration,
+    /// Probability for a command (or pipeline) to fail, without being sent
+    pub command_fault_rate: f64,
+}
+
+impl ChaosConfig {
+    /// Disturb the creation of a connection
+    pub(crate) async fn create(&self) -> RedisResult<()> {
+        disturb(self.create_latency, self.create_fault_rate).await
+    }
+
+    /// Disturb a command
+    pub(crate) async fn command(&self) -> RedisResult<()> {
+        disturb(self.command_latency, self.command_fault_rate).await
+    }
+}
+
+async fn disturb(latency: Duration, fault_rate: f64) -> RedisResult<()> {
+    if latency > Duration::default() {
+        runtime::sleep(latency).await;
+    }
+    if fault_rate > 0.0 && rand::thread_rng().gen_bool(fault_rate.min(1.0)) {
+        Err(injected_fault())
+    } else {
+        Ok(())
+    }
+}
+
+fn injected_fault() -> RedisError {
+    (
+        ErrorKind::IoError,
+        INJECTED_FAULT,
+        "fault injected by the chaos configuration".to_string(),
+    )
+        .into()
+}
+
+/// Tell whether the error was injected by a [`ChaosConfig`].
+///
+/// Injected errors are io errors (`RedisError::is_io_error`), like the errors of a
+/// failing connection.
+pub fn is_injected_fault(err: &RedisError) -> bool {
+    err.kind() == ErrorKind::IoError && err.to_string().starts_with(INJECTED_FAULT)
+}
@@ -0,0 +1,149 @@
+use std::{collections::HashMap, sync::Arc, sync::Mutex};
+
+use deadpool::managed::{PoolError, TimeoutType};
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+use redis::{FromRedisValue, RedisError, ToRedisArgs, Value};
+
+use crate::{errors, RedisPool};
+
+type SharedGet = Shared<BoxFuture<'static, Result<Value, Arc<PoolError<RedisError>>>>>;
+
+/// `GET` requests in flight, by key
+#[derive(Default)]
+pub(crate) struct PendingGets(Mutex<HashMap<Vec<Vec<u8>>, SharedGet>>);
+
+impl RedisPool {
+    /// Get the value of a key (`GET`), sharing the request with concurrent identical calls.
+    ///
+    /// While a `GET` of `key` issued through this method is in flight, other calls for
+    /// the same key wait for its reply instead of issuing their own request, which
+    /// spares connections and round-trips in read heavy workloads. An error is given to
+    /// every caller sharing the request, with its kind & code.
+    ///
+    /// The request runs in the background on the runtime of the pool: it completes and
+    /// gives its connection back even if every caller waiting for it is cancelled.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::{
+    ///     sync::{
+    ///         atomic::{AtomicUsize, Ordering},
+    ///         Arc,
+    ///     },
+    ///     time::Duration,
+    /// };
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{
+    ///     deadpool::managed::PoolError, AclUser, FakeRedisConnectionManager, RedisPool,
+    /// };
+    ///
+    /// let gets = Arc::new(AtomicUsize::new(0));
+    /// let counted = gets.clone();
+    /// let manager = FakeRedisConnectionManager::new(true, None)?.with_audit(move |command, _| {
+    ///     if command == "GET" {
+    ///         counted.fetch_add(1, Ordering::Relaxed);
+    ///     }
+    /// });
+    /// let pool = RedisPool::new(manager, 1);
+    /// let mut con = pool.get().await?;
+    /// con.set::<_, _, ()>("key", "value").await?;
+    ///
+    /// // the calls wait for the connection in use, then share a single request
+    /// let release = async move {
+    ///     async_std::task::sleep(Duration::from_millis(10)).await;
+    ///     drop(con);
+    /// };
+    /// let (values, ()) = futures_util::join!(
+    ///     futures_util::future::join_all(
+    ///         (0..10).map(|_| pool.get_coalesced::<_, String>("key")),
+    ///     ),
+    ///     release,
+    /// );
+    /// assert!(values.into_iter().all(|value| value.unwrap() == "value"));
+    /// assert_eq!(gets.load(Ordering::Relaxed), 1);
+    ///
+    /// // a user not allowed to read: every caller gets the `NOPERM` error
+    /// let writer = AclUser {
+    ///     username: "writer".to_string(),
+    ///     password: "secret".to_string(),
+    ///     rules: vec!["reset".to_string(), "on".to_string(), ">secret".to_string()],
+    /// };
+    /// let manager = FakeRedisConnectionManager::new(true, None)?.with_acl_user(writer);
+    /// let pool = RedisPool::new(manager, 5);
+    /// let values = futures_util::future::join_all(
+    ///     (0..10).map(|_| pool.get_coalesced::<_, Option<String>>("key")),
+    /// )
+    /// .await;
+    /// for value in values {
+    ///     match value {
+    ///         Err(PoolError::Backend(err)) => assert_eq!(err.code(), Some("NOPERM")),
+    ///         other => panic!("{:?}", other),
+    ///     }
+    /// }
+    ///
+    /// // a request whose callers are all cancelled still gives its connection back
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 2);
+    /// let mut admin = pool.get().await?;
+    /// redis::cmd("CLIENT")
+    ///     .arg("PAUSE")
+    ///     .arg(50)
+    ///     .query_async::<_, ()>(&mut *admin)
+    ///     .await?;
+    /// drop(admin);
+    /// let timeout = Duration::from_millis(10);
+    /// let get = pool.get_coalesced::<_, Option<String>>("key");
+    /// assert!(async_std::future::timeout(timeout, get).await.is_err());
+    /// async_std::task::sleep(Duration::from_millis(100)).await;
+    /// let (first, second) = (pool.try_get().await, pool.try_get().await);
+    /// assert!(first.is_ok() && second.is_ok());
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn get_coalesced<K, RV>(&self, key: K) -> Result<RV, PoolError<RedisError>>
+    where
+        K: ToRedisArgs,
+        RV: FromRedisValue,
+    {
+        let args = key.to_redis_args();
+        let get = {
+            let mut pending = (self.shared.pending_gets.0).lock().unwrap();
+            pending
+                .entry(args.clone())
+                .or_insert_with(|| {
+                    let pool = self.clone();
+                    let get = async move {
+                        let result = async {
+                            let mut con = pool.get().await?;
+                            Ok(redis::cmd("GET")
+                                .arg(args.clone())
+                                .query_async(&mut *con)
+                                .await?)
+                        }
+                        .await;
+                        (pool.shared.pending_gets.0).lock().unwrap().remove(&args);
+                        result.map_err(Arc::new)
+                    }
+                    .boxed()
+                    .shared();
+                    // run to completion whatever happens to the callers
+                    self.shared.runtime().spawn(get.clone().map(drop));
+                    get
+                })
+                .clone()
+        };
+        let value = get.await.map_err(|err| clone_error(&err))?;
+        Ok(RV::from_redis_value(&value)?)
+    }
+}
+
+/// Errors are shared between all the callers, give each of them its own copy.
+fn clone_error(err: &PoolError<RedisError>) -> PoolError<RedisError> {
+    match err {
+        PoolError::Timeout(TimeoutType::Wait) => PoolError::Timeout(TimeoutType::Wait),
+        PoolError::Timeout(TimeoutType::Create) => PoolError::Timeout(TimeoutType::Create),
+        PoolError::Timeout(TimeoutType::Recycle) => PoolError::Timeout(TimeoutType::Recycle),
+        PoolError::Backend(err) => PoolError::Backend(errors::clone_error(err)),
+    }
+}
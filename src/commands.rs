@@ -0,0 +1,428 @@
+//! Convenience commands issued directly on the pool.
+//!
+//! Each of them gets a connection from the pool, runs its commands and gives
+//! the connection back. They are suffixed by `_key` so they do not shadow
+//! `get()`, used to get a connection from the pool.
+
+use std::time::Duration;
+
+use deadpool::managed::PoolError;
+use redis::{AsyncCommands, FromRedisValue, Pipeline, RedisError, RedisFuture, ToRedisArgs};
+
+#[cfg(feature = "local-cache")]
+use redis::Value;
+
+#[cfg(feature = "local-cache")]
+use crate::cache;
+use crate::{RedisConnection, RedisPool};
+
+impl RedisPool {
+    /// Set the string value of a key (`SET`).
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 1);
+    /// pool.set_key::<_, _, ()>("key", 42).await?;
+    /// let value: u32 = pool.get_key("key").await?;
+    /// assert_eq!(value, 42);
+    /// let deleted: usize = pool.del_key(&["key", "other"][..]).await?;
+    /// assert_eq!(deleted, 1);
+    /// let value: Option<String> = pool.get_key("key").await?;
+    /// assert_eq!(value, None);
+    /// // the connection is given back after each command
+    /// assert_eq!(pool.stats().available, 1);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn set_key<K, V, RV>(&self, key: K, value: V) -> Result<RV, PoolError<RedisError>>
+    where
+        K: ToRedisArgs + Send + Sync,
+        V: ToRedisArgs + Send + Sync,
+        RV: FromRedisValue,
+    {
+        let mut con = self.get().await?;
+        Ok(con.set(key, value).await?)
+    }
+
+    /// Get the value of a key (`GET`).
+    pub async fn get_key<K, RV>(&self, key: K) -> Result<RV, PoolError<RedisError>>
+    where
+        K: ToRedisArgs + Send + Sync,
+        RV: FromRedisValue,
+    {
+        let mut con = self.get().await?;
+        Ok(con.get(key).await?)
+    }
+
+    /// Delete one or more keys (`DEL`).
+    pub async fn del_key<K, RV>(&self, key: K) -> Result<RV, PoolError<RedisError>>
+    where
+        K: ToRedisArgs + Send + Sync,
+        RV: FromRedisValue,
+    {
+        let mut con = self.get().await?;
+        Ok(con.del(key).await?)
+    }
+
+    /// Set the value of a key expiring after `ttl` (`SET` with `EX`, or `PX` when `ttl`
+    /// is not a whole number of seconds).
+    ///
+    /// Redis expires keys with a millisecond precision: a `ttl` which is not a whole
+    /// number of milliseconds is rounded up. A zero `ttl` is rejected with a
+    /// `ClientError`, without sending the command.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::Duration;
+    /// use redis::ErrorKind;
+    /// use redis_async_pool::{deadpool::managed::PoolError, FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 5);
+    /// pool.cache_set("key", "value", Duration::from_secs(60)).await?;
+    /// let value: Option<String> = pool.cache_get("key").await?;
+    /// assert_eq!(value.as_deref(), Some("value"));
+    ///
+    /// // expires after 1ms
+    /// pool.cache_set("short", "value", Duration::from_nanos(1)).await?;
+    /// async_std::task::sleep(Duration::from_millis(5)).await;
+    /// assert_eq!(pool.cache_get::<_, String>("short").await?, None);
+    ///
+    /// let err = pool.cache_set("key", "value", Duration::from_secs(0)).await;
+    /// assert!(matches!(err, Err(PoolError::Backend(err)) if err.kind() == ErrorKind::ClientError));
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn cache_set<K, V>(
+        &self,
+        key: K,
+        value: V,
+        ttl: Duration,
+    ) -> Result<(), PoolError<RedisError>>
+    where
+        K: ToRedisArgs + Send + Sync,
+        V: ToRedisArgs + Send + Sync,
+    {
+        if ttl == Duration::from_secs(0) {
+            return Err(PoolError::Backend(
+                (
+                    redis::ErrorKind::ClientError,
+                    "Invalid ttl",
+                    "the ttl of a cached value must not be zero".to_string(),
+                )
+                    .into(),
+            ));
+        }
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(key).arg(value);
+        if ttl.subsec_nanos() == 0 {
+            cmd.arg("EX").arg(ttl.as_secs());
+        } else {
+            let mut millis = ttl.as_millis() as u64;
+            // rounded up to the next millisecond
+            if Duration::from_millis(millis) < ttl {
+                millis += 1;
+            }
+            cmd.arg("PX").arg(millis);
+        }
+        let mut con = self.get().await?;
+        Ok(cmd.query_async(&mut *con).await?)
+    }
+
+    /// Get the value of a key set with [`cache_set`](Self::cache_set), `None` once expired
+    /// (`GET`).
+    ///
+    /// With the `local-cache` feature, the value last read may be served instead of an
+    /// error while the server is unavailable, see
+    /// [`RedisPoolBuilder::with_local_cache`](crate::RedisPoolBuilder::with_local_cache).
+    pub async fn cache_get<K, RV>(&self, key: K) -> Result<Option<RV>, PoolError<RedisError>>
+    where
+        K: ToRedisArgs + Send + Sync,
+        RV: FromRedisValue,
+    {
+        #[cfg(feature = "local-cache")]
+        if let Some(cache) = &self.shared.local_cache {
+            let cached = key.to_redis_args().concat();
+            let value = match self.get_key(key).await {
+                Ok(Value::Nil) => {
+                    cache.remove(&cached);
+                    Value::Nil
+                }
+                Ok(value) => {
+                    cache.insert(cached, value.clone());
+                    value
+                }
+                Err(err) if cache::is_unavailable(&err) => cache.get(&cached).ok_or(err)?,
+                Err(err) => return Err(err),
+            };
+            return Ok(FromRedisValue::from_redis_value(&value)?);
+        }
+        self.get_key(key).await
+    }
+
+    /// Run the writes issued by `f`, then wait for them to be acknowledged by at least
+    /// `numreplicas` replicas (`WAIT`).
+    ///
+    /// `WAIT` is issued on the same connection as the writes, after `f` completes.
+    /// It returns once `numreplicas` replicas acknowledged the writes or after `timeout`
+    /// (a zero timeout blocks forever). The result of `f` is returned along with the
+    /// number of replicas which acknowledged the writes.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::{
+    ///     sync::{Arc, Mutex},
+    ///     time::{Duration, Instant},
+    /// };
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let sent = Arc::new(Mutex::new(Vec::new()));
+    /// let recorded = sent.clone();
+    /// let manager = FakeRedisConnectionManager::new(false, None)?
+    ///     .with_audit(move |command, _| recorded.lock().unwrap().push(command.to_string()));
+    /// let pool = RedisPool::new(manager, 2);
+    ///
+    /// // the fake server has no replica: `WAIT` blocks until its timeout
+    /// let started = Instant::now();
+    /// let (id, acked) = pool
+    ///     .write_and_wait(
+    ///         |con| {
+    ///             Box::pin(async move {
+    ///                 con.set::<_, _, ()>("key", "value").await?;
+    ///                 Ok(con.id())
+    ///             })
+    ///         },
+    ///         1,
+    ///         Duration::from_millis(100),
+    ///     )
+    ///     .await?;
+    /// assert!(started.elapsed() >= Duration::from_millis(100));
+    /// assert_eq!(acked, 0);
+    /// assert_eq!(*sent.lock().unwrap(), ["SET", "WAIT"]);
+    /// // both commands were sent on the only connection created
+    /// assert_eq!(pool.get().await?.id(), id);
+    /// assert_eq!(pool.stats().size, 1);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn write_and_wait<T, F>(
+        &self,
+        f: F,
+        numreplicas: usize,
+        timeout: Duration,
+    ) -> Result<(T, usize), PoolError<RedisError>>
+    where
+        F: for<'a> FnOnce(&'a mut RedisConnection) -> RedisFuture<'a, T>,
+    {
+        let mut con = self.get().await?;
+        let result = f(&mut con).await?;
+        let acked = redis::cmd("WAIT")
+            .arg(numreplicas)
+            .arg(timeout.as_millis() as u64)
+            .query_async(&mut *con)
+            .await?;
+        Ok((result, acked))
+    }
+
+    /// Run the writes issued by `f`, then wait for them to be written to the append only
+    /// file of the server and of at least `numreplicas` replicas (`WAITAOF`).
+    ///
+    /// `WAITAOF` is issued on the same connection as the writes, after `f` completes,
+    /// requiring the local fsync when `numlocal` is 1. It returns once the writes are
+    /// acknowledged or after `timeout` (a zero timeout blocks forever). The result of `f`
+    /// is returned along with the number of local (0 or 1) and replicas fsyncs.
+    /// `WAITAOF` requires Redis 7.2, with AOF enabled when `numlocal` is 1.
+    ///
+    /// ```rust,no_run
+    /// # use std::{error::Error, time::Duration};
+    /// # use redis::AsyncCommands;
+    /// # use redis_async_pool::{RedisConnectionManager, RedisPool};
+    /// # #[async_std::main]
+    /// # async fn main() -> Result<(), Box<dyn Error>> {
+    /// # let pool = RedisPool::new(
+    /// #     RedisConnectionManager::new(redis::Client::open("redis://localhost:6379")?, true, None),
+    /// #     5,
+    /// # );
+    /// let ((), (local, replicas)) = pool
+    ///     .write_and_wait_aof(
+    ///         |con| Box::pin(async move { con.set("key", "value").await }),
+    ///         1,
+    ///         1,
+    ///         Duration::from_millis(100),
+    ///     )
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::{
+    ///     sync::{Arc, Mutex},
+    ///     time::{Duration, Instant},
+    /// };
+    /// use redis::{AsyncCommands, RedisFuture};
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisConnection, RedisPool};
+    ///
+    /// let sent = Arc::new(Mutex::new(Vec::new()));
+    /// let recorded = sent.clone();
+    /// let manager = FakeRedisConnectionManager::new(false, None)?
+    ///     .with_audit(move |command, _| recorded.lock().unwrap().push(command.to_string()));
+    /// let pool = RedisPool::new(manager, 2);
+    /// fn set(con: &mut RedisConnection) -> RedisFuture<'_, ()> {
+    ///     Box::pin(async move { con.set("key", "value").await })
+    /// }
+    ///
+    /// // the fake server has no replica: waiting for one blocks until the timeout
+    /// let started = Instant::now();
+    /// let ((), acked) = pool
+    ///     .write_and_wait_aof(set, 0, 1, Duration::from_millis(100))
+    ///     .await?;
+    /// assert!(started.elapsed() >= Duration::from_millis(100));
+    /// assert_eq!(acked, (0, 0));
+    /// assert_eq!(*sent.lock().unwrap(), ["SET", "WAITAOF"]);
+    /// assert_eq!(pool.stats().size, 1);
+    ///
+    /// // nor append only file
+    /// let failed = pool.write_and_wait_aof(set, 1, 0, Duration::from_millis(100));
+    /// assert!(failed.await.is_err());
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn write_and_wait_aof<T, F>(
+        &self,
+        f: F,
+        numlocal: usize,
+        numreplicas: usize,
+        timeout: Duration,
+    ) -> Result<(T, (usize, usize)), PoolError<RedisError>>
+    where
+        F: for<'a> FnOnce(&'a mut RedisConnection) -> RedisFuture<'a, T>,
+    {
+        let mut con = self.get().await?;
+        let result = f(&mut con).await?;
+        let acked = redis::cmd("WAITAOF")
+            .arg(numlocal)
+            .arg(numreplicas)
+            .arg(timeout.as_millis() as u64)
+            .query_async(&mut *con)
+            .await?;
+        Ok((result, acked))
+    }
+
+    /// Build a pipeline with `f` and run it on a pooled connection.
+    ///
+    /// The replies of the commands are decoded into `RV`, usually a tuple with one
+    /// element per command not marked with `ignore()`.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 1);
+    /// let (value, exists, deleted): (String, bool, usize) = pool
+    ///     .pipeline(|pipe| {
+    ///         pipe.set("key", "value")
+    ///             .ignore()
+    ///             .get("key")
+    ///             .exists("key")
+    ///             .del(&["key", "other"]);
+    ///     })
+    ///     .await?;
+    /// assert_eq!((value.as_str(), exists, deleted), ("value", true, 1));
+    /// // the connection is given back once the pipeline ran
+    /// assert_eq!(pool.stats().available, 1);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn pipeline<F, RV>(&self, f: F) -> Result<RV, PoolError<RedisError>>
+    where
+        F: FnOnce(&mut Pipeline),
+        RV: FromRedisValue,
+    {
+        let mut pipe = redis::pipe();
+        f(&mut pipe);
+        let mut con = self.get().await?;
+        Ok(pipe.query_async(&mut *con).await?)
+    }
+
+    /// Delete the keys matching a glob-style `pattern`, returning how many were deleted.
+    ///
+    /// Keys are iterated with `SCAN` in batches of 100, each batch being deleted with
+    /// `UNLINK`, so Redis is not blocked as with `KEYS`. If the connection is lost in the
+    /// middle of the scan, the batch is retried once on a new connection from the pool.
+    /// Keys created during the scan may not be deleted.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 5);
+    /// for i in 0..250 {
+    ///     pool.set_key::<_, _, ()>(format!("session:{}", i), i).await?;
+    /// }
+    /// pool.set_key::<_, _, ()>("user:1", 1).await?;
+    ///
+    /// assert_eq!(pool.delete_pattern("session:*").await?, 250);
+    /// assert_eq!(pool.delete_pattern("session:*").await?, 0);
+    /// assert_eq!(pool.get_key::<_, u32>("user:1").await?, 1);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn delete_pattern(&self, pattern: &str) -> Result<usize, PoolError<RedisError>> {
+        let mut con = self.get().await?;
+        let mut cursor = 0u64;
+        let mut deleted = 0;
+        loop {
+            let batch = match scan_and_unlink(&mut con, cursor, pattern).await {
+                Err(err) if err.is_connection_dropped() || err.is_io_error() => {
+                    // the cursor is kept by the client, the scan goes on with any connection
+                    con = self.get().await?;
+                    scan_and_unlink(&mut con, cursor, pattern).await?
+                }
+                batch => batch?,
+            };
+            deleted += batch.1;
+            cursor = batch.0;
+            if cursor == 0 {
+                return Ok(deleted);
+            }
+        }
+    }
+}
+
+/// Scan a batch of keys matching `pattern` from `cursor` and unlink them, returning the
+/// next cursor and the number of keys deleted
+async fn scan_and_unlink(
+    con: &mut RedisConnection,
+    cursor: u64,
+    pattern: &str,
+) -> Result<(u64, usize), RedisError> {
+    let (next, keys): (u64, Vec<Vec<u8>>) = redis::cmd("SCAN")
+        .arg(cursor)
+        .arg("MATCH")
+        .arg(pattern)
+        .arg("COUNT")
+        .arg(100)
+        .query_async(con)
+        .await?;
+    if keys.is_empty() {
+        return Ok((next, 0));
+    }
+    let deleted = redis::cmd("UNLINK").arg(keys).query_async(con).await?;
+    Ok((next, deleted))
+}
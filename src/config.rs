@@ -0,0 +1,312 @@
+use std::{sync::Arc, time::Duration};
+
+use redis::RedisResult;
+
+#[cfg(feature = "chaos")]
+use crate::chaos::ChaosConfig;
+use crate::{
+    health::SharedHealthCheck, replication::ReplicaReadiness, AclUser, ClientLimits,
+    ConnectionBudget, CreateFailurePolicy, RecyclePolicy, RedisConnectionManager, Runtime, Ttl,
+    TtlBasis,
+};
+
+/// Redis server of a [`ManagerConfig`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Endpoint {
+    /// Url of the server, as given to [`RedisConnectionManager::from_url`]
+    Url(String),
+    /// Client of the server, when its url is not known (managers created with
+    /// [`RedisConnectionManager::new`], fallbacks...): it can not be serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Client(redis::Client),
+}
+
+impl Endpoint {
+    /// Client of the endpoint, failing if its url is invalid
+    pub fn client(&self) -> RedisResult<redis::Client> {
+        match self {
+            Endpoint::Url(url) => redis::Client::open(url.as_str()),
+            Endpoint::Client(client) => Ok(client.clone()),
+        }
+    }
+}
+
+/// Configuration of a [`RedisConnectionManager`], captured with
+/// [`RedisConnectionManager::config_snapshot`].
+///
+/// It holds the settings of the manager as plain data, to be changed and turned into an
+/// equivalent manager with [`build`](Self::build), e.g. to reload the configuration of a
+/// pool with [`RedisPool::reconfigure`](crate::RedisPool::reconfigure). Callbacks (hooks,
+/// connector, audit, `is_dead` predicate, observers) are not captured: they must be
+/// registered again on the manager built. The state of the manager (rate limit bucket,
+/// poison streak, last endpoint used...) is not captured either, the manager built
+/// starts afresh.
+///
+/// Everything the manager derives from its url (credentials to authenticate again after
+/// a reset, host name resolved again by each connection) is derived again from the
+/// `endpoint` by `build`: pointing it to another server moves all of it.
+///
+/// With the `serde` feature, the configuration can be serialized & deserialized, except
+/// for the endpoints given as clients and for a [`Ttl::Deadline`]; the connection budget
+/// is shared with the manager and is not serialized.
+///
+/// ```rust
+/// # #[cfg(feature = "fake")]
+/// # async_std::task::block_on(async {
+/// use std::time::Duration;
+/// use redis_async_pool::{FakeRedisConnectionManager, RedisPool, Ttl};
+///
+/// let manager = FakeRedisConnectionManager::new(true, Some(Ttl::Once))?
+///     .with_reset_after_uses(10)
+///     .with_poison_after(3, Duration::from_secs(1));
+/// let config = manager.config_snapshot();
+/// let rebuilt = config.clone().build()?;
+/// assert_eq!(
+///     format!("{:?}", rebuilt.config_snapshot()),
+///     format!("{:?}", config)
+/// );
+///
+/// // the connections of the rebuilt manager are not reused either
+/// let pool = RedisPool::new(manager, 5);
+/// let first = pool.get().await?.id();
+/// assert_ne!(pool.get().await?.id(), first);
+/// pool.reconfigure(rebuilt);
+/// let first = pool.get().await?.id();
+/// assert_ne!(pool.get().await?.id(), first);
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// # })
+/// # .unwrap();
+/// ```
+///
+/// Moving a pool to another server:
+///
+/// ```rust
+/// # #[cfg(feature = "fake")]
+/// # async_std::task::block_on(async {
+/// use redis::AsyncCommands;
+/// use redis_async_pool::{Endpoint, FakeRedisConnectionManager, RedisConnectionManager, RedisPool};
+///
+/// let url = |manager: RedisConnectionManager| match manager.config_snapshot().endpoint {
+///     Endpoint::Url(url) => url,
+///     Endpoint::Client(_) => unreachable!(),
+/// };
+/// let first = url(FakeRedisConnectionManager::new(true, None)?);
+/// let second = url(FakeRedisConnectionManager::new(true, None)?);
+///
+/// // the host name of the first server is resolved by each connection
+/// let manager = RedisConnectionManager::from_url(&first.replace("127.0.0.1", "localhost"), true, None)?;
+/// let mut config = manager.config_snapshot();
+/// let pool = RedisPool::new(manager, 5);
+/// config.endpoint = Endpoint::Url(second.clone());
+/// # #[cfg(feature = "serde")]
+/// # fn serializable<T: serde::Serialize + serde::de::DeserializeOwned>(_: &T) {}
+/// # #[cfg(feature = "serde")]
+/// # serializable(&config);
+/// pool.reconfigure(config.build()?);
+/// pool.get().await?.set::<_, _, ()>("key", "value").await?;
+///
+/// let mut first = redis::Client::open(first.as_str())?.get_async_connection().await?;
+/// let mut second = redis::Client::open(second.as_str())?.get_async_connection().await?;
+/// assert_eq!(first.get::<_, Option<String>>("key").await?, None);
+/// assert_eq!(second.get::<_, String>("key").await?, "value");
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// # })
+/// # .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ManagerConfig {
+    /// Main endpoint
+    pub endpoint: Endpoint,
+    /// See [`RedisConnectionManager::with_fallbacks`]
+    pub fallbacks: Vec<Endpoint>,
+    /// See [`RedisConnectionManager::new`]
+    pub check_on_recycle: bool,
+    /// See [`RedisConnectionManager::new`]
+    pub connection_ttl: Option<Ttl>,
+    /// See [`RedisConnectionManager::with_ttl_basis`]
+    pub ttl_basis: TtlBasis,
+    /// See [`RedisConnectionManager::with_ttl_jitter`]
+    pub ttl_jitter: Option<Duration>,
+    /// See [`RedisConnectionManager::with_recycle_policy`]
+    pub recycle_policy: RecyclePolicy,
+    /// See [`RedisConnectionManager::with_create_failure_policy`]
+    pub create_failure_policy: CreateFailurePolicy,
+    /// See [`RedisConnectionManager::with_reset_after_uses`]
+    pub reset_after_uses: Option<u64>,
+    /// Interval of the shared health check, see
+    /// [`RedisConnectionManager::with_shared_health_check`]
+    pub shared_health_check: Option<Duration>,
+    /// See [`RedisConnectionManager::with_peer_addr`]
+    pub peer_addr: bool,
+    /// See [`RedisConnectionManager::with_client_id`]
+    pub client_id: bool,
+    /// See [`RedisConnectionManager::with_server_version`]
+    pub server_version: bool,
+    /// See [`RedisConnectionManager::with_lib_info`]
+    pub lib_info: bool,
+    /// See [`RedisConnectionManager::with_client_limits`]
+    pub client_limits: Option<ClientLimits>,
+    /// See [`RedisConnectionManager::with_acl_user`]
+    pub acl_user: Option<AclUser>,
+    /// See [`RedisConnectionManager::with_connection_budget`], shared with the manager
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub connection_budget: Option<ConnectionBudget>,
+    /// See [`RedisConnectionManager::with_reconnect_in_place`]
+    pub reconnect_in_place: bool,
+    /// Master client, maximum lag & maximum wait, see
+    /// [`RedisConnectionManager::with_replica_readiness`]
+    pub replica_readiness: Option<(Endpoint, u64, Duration)>,
+    /// See [`RedisConnectionManager::with_pause_detection`]
+    pub pause_detection: Option<Duration>,
+    /// See [`RedisConnectionManager::with_error_eviction`]
+    pub error_eviction: Option<f64>,
+    /// See [`RedisConnectionManager::with_max_reply_bytes`]
+    pub max_reply_bytes: Option<usize>,
+    /// Commands per second, see [`RedisConnectionManager::with_rate_limit`]
+    pub rate_limit: Option<u32>,
+    /// Streak & cooldown, see [`RedisConnectionManager::with_poison_after`]
+    pub poison_after: Option<(u32, Duration)>,
+    /// See [`RedisConnectionManager::with_connection_runtime`]
+    pub connection_runtime: Option<Runtime>,
+    /// See [`RedisConnectionManager::with_slow_command_log`]
+    #[cfg(feature = "log")]
+    pub slow_command_threshold: Option<Duration>,
+    /// See [`RedisConnectionManager::with_chaos`]
+    #[cfg(feature = "chaos")]
+    pub chaos: Option<ChaosConfig>,
+}
+
+impl RedisConnectionManager {
+    /// Capture the configuration of this manager, see [`ManagerConfig`].
+    pub fn config_snapshot(&self) -> ManagerConfig {
+        let settings = &self.settings;
+        ManagerConfig {
+            endpoint: match &self.url {
+                Some(url) => Endpoint::Url(url.clone()),
+                None => Endpoint::Client(settings.client.clone()),
+            },
+            fallbacks: settings
+                .fallbacks
+                .iter()
+                .cloned()
+                .map(Endpoint::Client)
+                .collect(),
+            check_on_recycle: self.check_on_recycle,
+            connection_ttl: self.connection_ttl,
+            ttl_basis: self.ttl_basis,
+            ttl_jitter: self.ttl_jitter,
+            recycle_policy: self.recycle_policy,
+            create_failure_policy: self.create_failure_policy,
+            reset_after_uses: self.reset_after_uses,
+            shared_health_check: self
+                .shared_health_check
+                .as_ref()
+                .map(|check| check.interval),
+            peer_addr: self.peer_addr,
+            client_id: self.client_id,
+            server_version: self.server_version,
+            lib_info: self.lib_info,
+            client_limits: self.client_limits.clone(),
+            acl_user: self.acl_user.clone(),
+            connection_budget: self.connection_budget.clone(),
+            reconnect_in_place: self.reconnect_in_place,
+            replica_readiness: self.replica_readiness.as_ref().map(|readiness| {
+                (
+                    Endpoint::Client(readiness.master.clone()),
+                    readiness.max_lag,
+                    readiness.max_wait,
+                )
+            }),
+            pause_detection: settings.pause_detection,
+            error_eviction: settings.error_eviction,
+            max_reply_bytes: settings.max_reply_bytes,
+            rate_limit: settings
+                .rate_limiter
+                .as_ref()
+                .map(|limiter| limiter.per_second()),
+            poison_after: settings
+                .poison
+                .as_ref()
+                .map(|poison| (poison.after, poison.cooldown)),
+            connection_runtime: settings.runtime,
+            #[cfg(feature = "log")]
+            slow_command_threshold: settings.slow_command_threshold,
+            #[cfg(feature = "chaos")]
+            chaos: settings.chaos.as_deref().cloned(),
+        }
+    }
+}
+
+impl ManagerConfig {
+    /// Create a manager with this configuration, failing if the url of an endpoint is
+    /// invalid.
+    ///
+    /// # Panics
+    ///
+    /// Like the matching `with_` methods, if `rate_limit` or the streak of `poison_after`
+    /// is zero.
+    pub fn build(self) -> RedisResult<RedisConnectionManager> {
+        let mut manager = match &self.endpoint {
+            Endpoint::Url(url) => {
+                RedisConnectionManager::from_url(url, self.check_on_recycle, self.connection_ttl)?
+            }
+            Endpoint::Client(client) => RedisConnectionManager::new(
+                client.clone(),
+                self.check_on_recycle,
+                self.connection_ttl,
+            ),
+        };
+        let fallbacks = self
+            .fallbacks
+            .iter()
+            .map(Endpoint::client)
+            .collect::<RedisResult<_>>()?;
+        manager.ttl_basis = self.ttl_basis;
+        manager.ttl_jitter = self.ttl_jitter;
+        manager.recycle_policy = self.recycle_policy;
+        manager.create_failure_policy = self.create_failure_policy;
+        manager.reset_after_uses = self.reset_after_uses;
+        manager.shared_health_check = self.shared_health_check.map(SharedHealthCheck::new);
+        manager.peer_addr = self.peer_addr;
+        manager.client_id = self.client_id;
+        manager.server_version = self.server_version;
+        manager.lib_info = self.lib_info;
+        manager.client_limits = self.client_limits;
+        manager.acl_user = self.acl_user;
+        manager.connection_budget = self.connection_budget;
+        manager.reconnect_in_place = self.reconnect_in_place;
+        manager.replica_readiness = match self.replica_readiness {
+            Some((master, max_lag, max_wait)) => Some(ReplicaReadiness {
+                master: master.client()?,
+                max_lag,
+                max_wait,
+            }),
+            None => None,
+        };
+        {
+            let settings = Arc::make_mut(&mut manager.settings);
+            settings.fallbacks = fallbacks;
+            settings.pause_detection = self.pause_detection;
+            settings.error_eviction = self.error_eviction;
+            settings.max_reply_bytes = self.max_reply_bytes;
+            settings.runtime = self.connection_runtime;
+            #[cfg(feature = "log")]
+            {
+                settings.slow_command_threshold = self.slow_command_threshold;
+            }
+            #[cfg(feature = "chaos")]
+            {
+                settings.chaos = self.chaos.map(Arc::new);
+            }
+        }
+        if let Some(per_second) = self.rate_limit {
+            manager = manager.with_rate_limit(per_second);
+        }
+        if let Some((after, cooldown)) = self.poison_after {
+            manager = manager.with_poison_after(after, cooldown);
+        }
+        Ok(manager)
+    }
+}
@@ -0,0 +1,920 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    net::SocketAddr,
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use redis::{aio::ConnectionLike, Arg, Cmd, Pipeline, RedisFuture, RedisResult, Value};
+use tokio::sync::OwnedSemaphorePermit;
+
+#[cfg(feature = "chaos")]
+use crate::chaos::ChaosConfig;
+use crate::{
+    errors,
+    info::ServerVersion,
+    poison::Poison,
+    rate::RateLimiter,
+    runtime::{self, Runtime},
+};
+
+/// Settings of the manager needed by the connections it creates.
+#[derive(Clone)]
+pub(crate) struct ConnectionSettings {
+    pub(crate) client: redis::Client,
+    /// Clients tried in order when a connection can not be created with `client`
+    pub(crate) fallbacks: Vec<redis::Client>,
+    pub(crate) pause_detection: Option<Duration>,
+    pub(crate) error_eviction: Option<f64>,
+    pub(crate) max_reply_bytes: Option<usize>,
+    /// Shared by every connection created by the manager
+    pub(crate) rate_limiter: Option<Arc<RateLimiter>>,
+    #[cfg(feature = "log")]
+    pub(crate) slow_command_threshold: Option<Duration>,
+    #[cfg(feature = "chaos")]
+    pub(crate) chaos: Option<Arc<ChaosConfig>>,
+    pub(crate) audit: Option<Arc<AuditFn>>,
+    pub(crate) is_dead: Option<Arc<IsDeadFn>>,
+    /// Shared by every connection created by the manager
+    pub(crate) poison: Option<Arc<Poison>>,
+    /// Runtime whose constructor opens the connections, picked by the redis crate if
+    /// `None`
+    pub(crate) runtime: Option<Runtime>,
+}
+
+/// Callback invoked with the name and the first argument of each command
+pub(crate) type AuditFn = dyn Fn(&str, Option<&[u8]>) + Send + Sync;
+
+/// Predicate telling whether an error leaves the connection unusable
+pub(crate) type IsDeadFn = dyn Fn(&redis::RedisError) -> bool + Send + Sync;
+
+impl ConnectionSettings {
+    pub(crate) fn new(client: redis::Client) -> Self {
+        Self {
+            client,
+            fallbacks: Vec::new(),
+            pause_detection: None,
+            error_eviction: None,
+            max_reply_bytes: None,
+            rate_limiter: None,
+            #[cfg(feature = "log")]
+            slow_command_threshold: None,
+            #[cfg(feature = "chaos")]
+            chaos: None,
+            audit: None,
+            is_dead: None,
+            poison: None,
+            runtime: None,
+        }
+    }
+
+    /// Open a connection with `client`, through the constructor of the runtime selected
+    pub(crate) async fn connect(
+        &self,
+        client: &redis::Client,
+    ) -> RedisResult<redis::aio::Connection> {
+        match self.runtime {
+            Some(runtime) => runtime.connect(client).await,
+            None => client.get_async_connection().await,
+        }
+    }
+
+    /// Number of endpoints, the main one and the fallbacks
+    pub(crate) fn endpoints(&self) -> usize {
+        1 + self.fallbacks.len()
+    }
+
+    /// Client of the endpoint at `index`, 0 being the main one
+    pub(crate) fn endpoint(&self, index: usize) -> &redis::Client {
+        match index {
+            0 => &self.client,
+            _ => &self.fallbacks[index - 1],
+        }
+    }
+}
+
+/// The connection created by the pool manager.
+///
+/// It is Deref & DerefMut to `redis::aio::Connection` so it can be used
+/// like a regular Redis asynchronous connection.
+pub struct RedisConnection {
+    pub(crate) actual: redis::aio::Connection,
+    pub(crate) settings: Arc<ConnectionSettings>,
+    /// Index of the endpoint the connection was created with
+    pub(crate) endpoint: usize,
+    /// Name of the pool the connection was created by, see `RedisPoolBuilder::with_name`
+    pub(crate) pool_name: Option<Arc<str>>,
+    pub(crate) peer_addr: Option<SocketAddr>,
+    pub(crate) server_version: Option<ServerVersion>,
+    pub(crate) expires_at: Option<Instant>,
+    /// Time added to the base ttl of the connection by its recycles, see `Ttl::Adaptive`
+    pub(crate) ttl_growth: Duration,
+    pub(crate) generation: u64,
+    /// Set when the connection is left in an unusable state (e.g. a reply is still
+    /// pending after a timeout)
+    pub(crate) broken: bool,
+    /// Set while the connection is recycled: a recycle interrupted by the cancellation of
+    /// `get()` leaves the connection in the pool, possibly with a reply still pending
+    pub(crate) recycling: bool,
+    /// Set when deadpool hands the connection out again, until `RedisPool` recycles it
+    pub(crate) returned: bool,
+    /// Last time the connection was checked on recycle (or created)
+    pub(crate) last_checked: Instant,
+    /// Whether the last command succeeded, `true` until a command is issued
+    pub(crate) last_command_ok: bool,
+    /// When the last command completed (or the connection was created)
+    pub(crate) last_command_at: Instant,
+    pub(crate) history: CommandHistory,
+    pub(crate) latency: Latency,
+    pub(crate) tracker: Arc<Tracker>,
+    /// Number of requests sent since the connection was created or reset
+    pub(crate) commands_since_reset: u64,
+    /// Server side state left by the commands issued on the connection
+    pub(crate) state: CommandState,
+    /// Hashes of the scripts loaded through the connection
+    pub(crate) scripts: HashSet<String>,
+    pub(crate) connect_timings: ConnectTimings,
+    /// Number of times the socket was reopened on recycle, see
+    /// `RedisConnectionManager::with_reconnect_in_place`
+    pub(crate) reconnect_count: u32,
+    /// Tag set with `RedisConnection::set_tag`
+    pub(crate) tag: Option<String>,
+    /// Share of the connection budget held while the connection is open
+    pub(crate) budget: Option<OwnedSemaphorePermit>,
+    /// Runtime whose constructor opened the connection, if selected
+    pub(crate) runtime: Option<Runtime>,
+}
+
+/// Connection metadata readable while the connection is in use
+pub(crate) struct Tracker {
+    pub(crate) id: u64,
+    /// Identifier of the connection on the server side (`CLIENT ID`), 0 if unknown as
+    /// Redis numbers its clients from 1; it changes when the connection is reconnected
+    client_id: AtomicU64,
+    pub(crate) created_at: Instant,
+    /// Number of times the connection has been handed out by a pool
+    pub(crate) uses: AtomicU64,
+    pub(crate) in_use: AtomicBool,
+    /// The connection was killed with `RedisPool::kill_connection`
+    pub(crate) killed: AtomicBool,
+    /// The connection was taken out of its pool with `PooledConnection::take`
+    pub(crate) taken: AtomicBool,
+    /// Number of migrations of the pool run on the connection, see
+    /// `RedisPool::for_each_connection`
+    pub(crate) migrations: AtomicUsize,
+}
+
+impl Tracker {
+    pub(crate) fn new(client_id: Option<u64>) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            client_id: AtomicU64::new(client_id.unwrap_or(0)),
+            created_at: Instant::now(),
+            uses: AtomicU64::new(0),
+            in_use: AtomicBool::new(false),
+            killed: AtomicBool::new(false),
+            taken: AtomicBool::new(false),
+            migrations: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn client_id(&self) -> Option<u64> {
+        match self.client_id.load(Ordering::Relaxed) {
+            0 => None,
+            client_id => Some(client_id),
+        }
+    }
+
+    pub(crate) fn set_client_id(&self, client_id: Option<u64>) {
+        self.client_id
+            .store(client_id.unwrap_or(0), Ordering::Relaxed);
+    }
+}
+
+/// Outcome of the last commands issued on a connection
+#[derive(Default)]
+pub(crate) struct CommandHistory {
+    /// One bit per command, set for errors, most recent command first
+    errors: u32,
+    /// Number of commands recorded, up to `HISTORY_LEN`
+    len: u32,
+}
+
+pub(crate) const HISTORY_LEN: u32 = u32::BITS;
+
+impl CommandHistory {
+    fn record(&mut self, ok: bool) {
+        self.errors = (self.errors << 1) | (!ok as u32);
+        self.len = (self.len + 1).min(HISTORY_LEN);
+    }
+
+    /// Number of commands recorded
+    pub(crate) fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Ratio of failed commands among the recorded ones
+    pub(crate) fn error_rate(&self) -> f64 {
+        if self.len == 0 {
+            0.0
+        } else {
+            self.errors.count_ones() as f64 / self.len as f64
+        }
+    }
+}
+
+/// Moving average of the round-trip time of the requests sent on a connection
+#[derive(Default)]
+pub(crate) struct Latency {
+    average: Option<Duration>,
+}
+
+impl Latency {
+    /// Weight of a new sample in the average, as the smoothed round-trip time of TCP
+    const WEIGHT: f64 = 1.0 / 8.0;
+
+    fn record(&mut self, sample: Duration) {
+        self.average = Some(match self.average {
+            Some(average) => average.mul_f64(1.0 - Self::WEIGHT) + sample.mul_f64(Self::WEIGHT),
+            None => sample,
+        });
+    }
+}
+
+/// Server side state of a connection, tracked from the commands issued on it
+#[derive(Default, Clone, Copy)]
+pub(crate) struct CommandState {
+    /// A `MULTI` is not followed by `EXEC` or `DISCARD` yet
+    pub(crate) transaction: bool,
+    /// Keys are watched
+    pub(crate) watching: bool,
+    /// The connection is subscribed to channels
+    pub(crate) subscribed: bool,
+}
+
+impl CommandState {
+    fn observe(&mut self, cmd: &Cmd) {
+        let name = match cmd.args_iter().next() {
+            Some(Arg::Simple(name)) => name,
+            _ => return,
+        };
+        let is = |command: &str| name.eq_ignore_ascii_case(command.as_bytes());
+        if is("MULTI") {
+            self.transaction = true;
+        } else if is("EXEC") || is("DISCARD") {
+            self.transaction = false;
+            self.watching = false;
+        } else if is("WATCH") {
+            self.watching = true;
+        } else if is("UNWATCH") {
+            self.watching = false;
+        } else if is("SUBSCRIBE") || is("PSUBSCRIBE") {
+            self.subscribed = true;
+        } else if is("RESET") {
+            *self = Self::default();
+        }
+    }
+
+    /// Describe what is left pending on the connection, `None` if it is clean. Watched
+    /// keys do not count as they are unwatched on recycle.
+    pub(crate) fn pending(&self) -> Option<&'static str> {
+        if self.transaction {
+            Some("in a transaction (MULTI without EXEC or DISCARD)")
+        } else if self.subscribed {
+            Some("subscribed to channels")
+        } else {
+            None
+        }
+    }
+}
+
+/// A request sent through a `RedisConnection`
+#[derive(Clone, Copy)]
+enum Request<'a> {
+    Cmd(&'a Cmd),
+    Pipeline(&'a Pipeline, usize, usize),
+}
+
+#[cfg(feature = "log")]
+impl Request<'_> {
+    /// Name of the command, or of the commands of the pipeline
+    fn name(&self) -> String {
+        match self {
+            Request::Cmd(cmd) => cmd_name(cmd),
+            Request::Pipeline(pipeline, _, _) => pipeline
+                .cmd_iter()
+                .map(cmd_name)
+                .collect::<Vec<_>>()
+                .join(","),
+        }
+    }
+}
+
+/// Name of the pool, if any, appended to the connection of the log messages
+#[cfg(feature = "log")]
+pub(crate) struct OfPool<'a>(pub(crate) Option<&'a str>);
+
+#[cfg(feature = "log")]
+impl std::fmt::Display for OfPool<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(name) => write!(f, " of pool {}", name),
+            None => Ok(()),
+        }
+    }
+}
+
+pub(crate) fn cmd_name(cmd: &Cmd) -> String {
+    match cmd.args_iter().next() {
+        Some(Arg::Simple(name)) => String::from_utf8_lossy(name).to_uppercase(),
+        _ => String::new(),
+    }
+}
+
+fn audit_cmd(audit: &AuditFn, cmd: &Cmd) {
+    let key = match cmd.args_iter().nth(1) {
+        Some(Arg::Simple(key)) => Some(key),
+        _ => None,
+    };
+    audit(&cmd_name(cmd), key);
+}
+
+/// The reply to a `Request`
+enum Reply {
+    Value(Value),
+    Values(Vec<Value>),
+}
+
+impl Reply {
+    /// Number of bytes of data carried by the reply
+    fn size(&self) -> usize {
+        match self {
+            Reply::Value(value) => value_size(value),
+            Reply::Values(values) => values.iter().map(value_size).sum(),
+        }
+    }
+}
+
+fn value_size(value: &Value) -> usize {
+    match value {
+        Value::Nil | Value::Okay => 0,
+        Value::Int(_) => 8,
+        Value::Data(data) => data.len(),
+        Value::Status(status) => status.len(),
+        Value::Bulk(values) => values.iter().map(value_size).sum(),
+    }
+}
+
+impl RedisConnection {
+    /// Send a request to the server; every command issued through the
+    /// `ConnectionLike` implementation goes through this method.
+    async fn send(&mut self, request: Request<'_>) -> RedisResult<Reply> {
+        if let Some(audit) = self.settings.audit.as_deref() {
+            match request {
+                Request::Cmd(cmd) => audit_cmd(audit, cmd),
+                Request::Pipeline(pipeline, _, _) => {
+                    pipeline.cmd_iter().for_each(|cmd| audit_cmd(audit, cmd))
+                }
+            }
+        }
+        if let Some(limiter) = &self.settings.rate_limiter {
+            let commands = match request {
+                Request::Cmd(_) => 1,
+                Request::Pipeline(pipeline, _, _) => pipeline.cmd_iter().count(),
+            };
+            let wait = limiter.reserve(commands);
+            if wait > Duration::default() {
+                runtime::sleep(wait).await;
+            }
+        }
+        let started = Instant::now();
+        let reply = match self.settings.pause_detection {
+            Some(threshold) => match runtime::timeout(threshold, self.send_raw(request)).await {
+                Ok(reply) => reply,
+                Err(elapsed) => {
+                    // a reply may still come on this connection: left broken by send_raw
+                    Err(if self.server_paused(threshold).await {
+                        errors::server_paused(threshold)
+                    } else {
+                        elapsed.into()
+                    })
+                }
+            },
+            None => self.send_raw(request).await,
+        };
+        let elapsed = started.elapsed();
+        self.latency.record(elapsed);
+        #[cfg(feature = "log")]
+        if let Some(threshold) = self.settings.slow_command_threshold {
+            if elapsed >= threshold {
+                log::warn!(
+                    "slow redis command {} on connection {}{}: {:?}",
+                    request.name(),
+                    self.tracker.id,
+                    OfPool(self.pool_name.as_deref()),
+                    elapsed
+                );
+            }
+        }
+        let reply = match (reply, self.settings.max_reply_bytes) {
+            (Ok(reply), Some(max)) if reply.size() > max => {
+                // a client abusing huge replies should not keep its connection
+                self.broken = true;
+                Err(errors::reply_too_large(reply.size(), max))
+            }
+            (reply, _) => reply,
+        };
+        if let (Err(err), Some(is_dead)) = (&reply, &self.settings.is_dead) {
+            if is_dead(err) {
+                self.broken = true;
+            }
+        }
+        if reply.is_ok() {
+            match request {
+                Request::Cmd(cmd) => self.state.observe(cmd),
+                Request::Pipeline(pipeline, _, _) => {
+                    pipeline.cmd_iter().for_each(|cmd| self.state.observe(cmd))
+                }
+            }
+        }
+        self.history.record(reply.is_ok());
+        if let Some(poison) = &self.settings.poison {
+            poison.record(reply.is_ok());
+        }
+        self.last_command_ok = reply.is_ok();
+        self.last_command_at = Instant::now();
+        self.commands_since_reset += 1;
+        reply.map_err(errors::explain_cluster_redirect)
+    }
+
+    async fn send_raw(&mut self, request: Request<'_>) -> RedisResult<Reply> {
+        #[cfg(feature = "chaos")]
+        if let Some(chaos) = &self.settings.chaos {
+            chaos.command().await?;
+        }
+        // flagged until the reply is read: if the request is cancelled meanwhile (timeout,
+        // lost `select!`...), its reply is left pending and the connection must be dropped
+        let broken = std::mem::replace(&mut self.broken, true);
+        let reply = match request {
+            Request::Cmd(cmd) => self.actual.req_packed_command(cmd).await.map(Reply::Value),
+            Request::Pipeline(pipeline, offset, count) => self
+                .actual
+                .req_packed_commands(pipeline, offset, count)
+                .await
+                .map(Reply::Values),
+        };
+        self.broken = broken;
+        reply
+    }
+
+    /// Check whether the server is paused by issuing an `INFO` command on a
+    /// separate connection: a paused server does not answer it either.
+    async fn server_paused(&self, threshold: Duration) -> bool {
+        let check = async {
+            let client = self.settings.endpoint(self.endpoint);
+            let mut con = self.settings.connect(client).await?;
+            redis::cmd("INFO")
+                .arg("server")
+                .query_async::<_, String>(&mut con)
+                .await
+        };
+        runtime::timeout(threshold, check).await.is_err()
+    }
+}
+
+impl RedisConnection {
+    /// Identifier of this connection, unique within the process
+    pub fn id(&self) -> u64 {
+        self.tracker.id
+    }
+
+    /// Attach `tag` to this connection, e.g. `"initialized-by-warmup"`, to tell it apart
+    /// when debugging: the tag is kept for the whole life of the connection, across its
+    /// recycles and reconnections in place, and shows in its `Debug` output.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 1);
+    /// let mut con = pool.get().await?;
+    /// assert_eq!(con.tag(), None);
+    /// con.set_tag("initialized-by-warmup".to_string());
+    /// let id = con.id();
+    /// drop(con);
+    ///
+    /// for _ in 0..3 {
+    ///     let con = pool.get().await?;
+    ///     assert_eq!(con.id(), id);
+    ///     assert_eq!(con.tag(), Some("initialized-by-warmup"));
+    ///     assert!(format!("{:?}", con).contains("initialized-by-warmup"));
+    /// }
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn set_tag(&mut self, tag: String) {
+        self.tag = Some(tag);
+    }
+
+    /// Tag set with [`set_tag`](Self::set_tag)
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+
+    /// Identifier of this connection on the server side, as reported by the server
+    /// (`CLIENT ID`) when the connection was created, or reopened by
+    /// [`RedisConnectionManager::with_reconnect_in_place`](crate::RedisConnectionManager::with_reconnect_in_place).
+    ///
+    /// `None` unless enabled with
+    /// [`RedisConnectionManager::with_client_id`](crate::RedisConnectionManager::with_client_id),
+    /// with servers older than Redis 5 or when the server did not answer.
+    pub fn client_id(&self) -> Option<u64> {
+        self.tracker.client_id()
+    }
+
+    /// Runtime whose constructor opened this connection, as selected with
+    /// [`RedisConnectionManager::with_connection_runtime`](crate::RedisConnectionManager::with_connection_runtime).
+    ///
+    /// `None` when the constructor was picked by the redis crate or the connection was
+    /// opened by a connector.
+    pub fn runtime(&self) -> Option<Runtime> {
+        self.runtime
+    }
+
+    /// Time elapsed since this connection was created
+    pub fn age(&self) -> Duration {
+        self.tracker.created_at.elapsed()
+    }
+
+    /// Number of times this connection has been handed out by a `RedisPool`
+    pub fn uses(&self) -> u64 {
+        self.tracker.uses.load(Ordering::Relaxed)
+    }
+
+    /// Number of times the socket of this connection was found dropped and reopened on
+    /// recycle, see
+    /// [`RedisConnectionManager::with_reconnect_in_place`](crate::RedisConnectionManager::with_reconnect_in_place).
+    ///
+    /// Connections reconnected often hint at an unstable network path to the server.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let manager = FakeRedisConnectionManager::new(true, None)?
+    ///     .with_client_id()
+    ///     .with_reconnect_in_place();
+    /// let pool = RedisPool::new(manager, 2);
+    /// let (first, mut second) = (pool.get().await?, pool.get().await?);
+    /// assert_eq!(first.reconnect_count(), 0);
+    /// // the server drops the socket of the first connection
+    /// redis::cmd("CLIENT")
+    ///     .arg("KILL")
+    ///     .arg("ID")
+    ///     .arg(first.client_id().unwrap())
+    ///     .query_async::<_, ()>(&mut *second)
+    ///     .await?;
+    /// let id = first.id();
+    /// drop((first, second));
+    ///
+    /// let reconnected = pool.get().await?;
+    /// assert_eq!(reconnected.id(), id);
+    /// assert_eq!(reconnected.reconnect_count(), 1);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn reconnect_count(&self) -> u32 {
+        self.reconnect_count
+    }
+
+    /// Address of the server this connection talks to, as reported by the server
+    /// (`CLIENT INFO`) when the connection was created.
+    ///
+    /// `None` unless enabled with
+    /// [`RedisConnectionManager::with_peer_addr`](crate::RedisConnectionManager::with_peer_addr),
+    /// with servers older than Redis 6.2 or when the server did not answer.
+    /// Behind a proxy or a NAT, this is the address the server listens on.
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
+    /// Version of the server this connection talks to (`redis_version` in `INFO server`),
+    /// queried when the connection was created.
+    ///
+    /// `None` unless enabled with
+    /// [`RedisConnectionManager::with_server_version`](crate::RedisConnectionManager::with_server_version),
+    /// or when the server did not answer.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool, ServerVersion};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 1);
+    /// assert_eq!(pool.get().await?.server_version(), None);
+    ///
+    /// let manager = FakeRedisConnectionManager::new(true, None)?.with_server_version();
+    /// let pool = RedisPool::new(manager, 1);
+    /// let version = pool.get().await?.server_version().unwrap();
+    /// assert_eq!(version.to_string(), "7.2.0");
+    /// assert!(version >= ServerVersion { major: 6, minor: 2, patch: 0 });
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn server_version(&self) -> Option<ServerVersion> {
+        self.server_version
+    }
+
+    /// Whether no transaction or subscription is left pending on this connection.
+    ///
+    /// This is tracked from the commands issued on the connection: atomic pipelines leave
+    /// it clean. Connections which are not clean are dropped on recycle.
+    pub fn is_clean(&self) -> bool {
+        self.state.pending().is_none()
+    }
+
+    /// When this connection expires, if it has a ttl
+    pub fn expires_at(&self) -> Option<Instant> {
+        self.expires_at
+    }
+
+    /// Whether the last command issued on this connection succeeded, `true` if no command
+    /// was issued yet.
+    pub fn last_command_ok(&self) -> bool {
+        self.last_command_ok
+    }
+
+    /// Average round-trip time of the requests sent on this connection, `None` if no
+    /// request was sent yet.
+    ///
+    /// This is an exponential moving average: each request weighs for 1/8th, so the
+    /// average follows the latency of the last few dozen requests. The round-trip time of
+    /// a request includes the time the server took to process it.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::Duration;
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 2);
+    /// let (mut admin, mut con) = (pool.get().await?, pool.get().await?);
+    /// assert_eq!(con.avg_latency(), None);
+    /// for _ in 0..5 {
+    ///     con.get::<_, Option<String>>("key").await?;
+    /// }
+    /// assert!(con.avg_latency().unwrap() > Duration::from_secs(0));
+    ///
+    /// // the requests stalled by the pause of the server raise the average
+    /// let fast = con.avg_latency().unwrap();
+    /// redis::cmd("CLIENT")
+    ///     .arg("PAUSE")
+    ///     .arg(50)
+    ///     .query_async::<_, ()>(&mut *admin)
+    ///     .await?;
+    /// con.get::<_, Option<String>>("key").await?;
+    /// assert!(con.avg_latency().unwrap() > fast + Duration::from_millis(40) / 8);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn avg_latency(&self) -> Option<Duration> {
+        self.latency.average
+    }
+
+    /// Time spent in each phase of the creation of this connection.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::Duration;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisConnectionManager, RedisPool};
+    ///
+    /// let manager = FakeRedisConnectionManager::new(true, None)?.with_peer_addr();
+    /// let pool = RedisPool::new(manager, 1);
+    /// let con = pool.get().await?;
+    /// let timings = con.connect_timings();
+    /// // the fake server listens on an ip address, there is nothing to resolve
+    /// assert_eq!(timings.dns, None);
+    /// assert!(timings.connect > Duration::default());
+    /// assert!(timings.handshake > Duration::default());
+    ///
+    /// // the same server named by its host
+    /// let url = format!("redis://localhost:{}", con.peer_addr().unwrap().port());
+    /// let manager = RedisConnectionManager::from_url(&url, true, None)?.with_peer_addr();
+    /// let con = RedisPool::new(manager, 1).get().await?;
+    /// assert!(con.connect_timings().dns.is_some());
+    /// assert!(con.peer_addr().unwrap().ip().is_loopback());
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn connect_timings(&self) -> ConnectTimings {
+        self.connect_timings
+    }
+
+    /// Ratio of failed commands among the last 32 commands issued on this connection.
+    pub fn recent_error_rate(&self) -> f64 {
+        self.history.error_rate()
+    }
+
+    /// Snapshot of the metadata of this connection, gathering the values returned by
+    /// the methods above.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::{Duration, Instant};
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool, ServerVersion, Ttl};
+    ///
+    /// let ttl = Ttl::Simple(Duration::from_secs(60));
+    /// let manager = FakeRedisConnectionManager::new(false, Some(ttl))?
+    ///     .with_peer_addr()
+    ///     .with_server_version();
+    /// let pool = RedisPool::new(manager, 1);
+    /// drop(pool.get().await?);
+    /// let mut con = pool.get().await?;
+    /// con.set::<_, _, ()>("key", "value").await?;
+    /// let _ = redis::cmd("NOPE").query_async::<_, ()>(&mut *con).await;
+    /// redis::cmd("MULTI").query_async::<_, ()>(&mut *con).await?;
+    ///
+    /// let metadata = con.metadata();
+    /// assert_eq!(metadata.id, con.id());
+    /// assert!(metadata.age < Duration::from_secs(1));
+    /// assert_eq!(metadata.uses, 2);
+    /// let expires_in = metadata.expires_at.unwrap() - Instant::now();
+    /// assert!(expires_in > Duration::from_secs(59));
+    /// assert!(metadata.peer_addr.is_some());
+    /// assert_eq!(metadata.peer_addr, con.peer_addr());
+    /// assert_eq!(metadata.server_version, Some(ServerVersion { major: 7, minor: 2, patch: 0 }));
+    /// assert_eq!((metadata.recent_commands, metadata.recent_error_rate), (3, 1.0 / 3.0));
+    /// assert!(metadata.avg_latency.is_some());
+    /// assert!(metadata.commands_since_reset >= 3);
+    /// assert!(!metadata.clean && !metadata.broken);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn metadata(&self) -> ConnectionMetadata {
+        ConnectionMetadata {
+            id: self.id(),
+            age: self.age(),
+            uses: self.uses(),
+            expires_at: self.expires_at(),
+            peer_addr: self.peer_addr(),
+            server_version: self.server_version(),
+            recent_error_rate: self.recent_error_rate(),
+            avg_latency: self.avg_latency(),
+            recent_commands: self.history.len(),
+            commands_since_reset: self.commands_since_reset,
+            clean: self.is_clean(),
+            broken: self.broken,
+        }
+    }
+}
+
+/// Time spent in each phase of the creation of a connection, see
+/// [`RedisConnection::connect_timings`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectTimings {
+    /// Resolution of the host name of the server, `None` when not resolved.
+    ///
+    /// The host is resolved ahead of the connection only for managers created with
+    /// [`RedisConnectionManager::from_url`](crate::RedisConnectionManager::from_url),
+    /// when connecting to their main endpoint by name rather than by ip address and
+    /// without TLS. The connection is then opened to the addresses resolved, in order.
+    pub dns: Option<Duration>,
+    /// Opening of the connection by the redis client: TCP (and TLS) connect, then `AUTH`
+    /// & `SELECT` if needed.
+    pub connect: Duration,
+    /// Setup of the connection by the pool: the queries of its metadata (`CLIENT INFO`,
+    /// `INFO`...) and the post create hooks.
+    pub handshake: Duration,
+}
+
+/// Snapshot of the metadata of a connection, see [`RedisConnection::metadata`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectionMetadata {
+    /// Identifier of the connection, see [`RedisConnection::id`]
+    pub id: u64,
+    /// Time elapsed since the connection was created
+    pub age: Duration,
+    /// Number of times the connection has been handed out by a pool
+    pub uses: u64,
+    /// When the connection expires, if it has a ttl
+    pub expires_at: Option<Instant>,
+    /// Address of the server, see [`RedisConnection::peer_addr`]
+    pub peer_addr: Option<SocketAddr>,
+    /// Version of the server, see [`RedisConnection::server_version`]
+    pub server_version: Option<ServerVersion>,
+    /// Ratio of failed commands among the last 32 commands
+    pub recent_error_rate: f64,
+    /// Average round-trip time of the requests, see [`RedisConnection::avg_latency`]
+    pub avg_latency: Option<Duration>,
+    /// Number of commands the error rate is computed on, up to 32
+    pub recent_commands: u32,
+    /// Number of requests sent since the connection was created or reset
+    pub commands_since_reset: u64,
+    /// Whether no transaction or subscription is left pending, see
+    /// [`RedisConnection::is_clean`]
+    pub clean: bool,
+    /// Whether the connection was left in an unusable state, e.g. with a reply pending
+    /// after a timeout
+    pub broken: bool,
+}
+
+impl fmt::Debug for RedisConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisConnection")
+            .field("id", &self.id())
+            .field("tag", &self.tag)
+            .field("client_id", &self.client_id())
+            .field("pool", &self.pool_name)
+            .field("age", &self.age())
+            .field("uses", &self.uses())
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+#[cfg(feature = "log")]
+impl Drop for RedisConnection {
+    fn drop(&mut self) {
+        log::debug!(
+            "closed redis connection {}{}",
+            self.tracker.id,
+            OfPool(self.pool_name.as_deref())
+        );
+    }
+}
+
+impl ConnectionLike for RedisConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        Box::pin(async move {
+            match self.send(Request::Cmd(cmd)).await? {
+                Reply::Value(value) => Ok(value),
+                Reply::Values(_) => unreachable!(),
+            }
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        Box::pin(async move {
+            match self.send(Request::Pipeline(cmd, offset, count)).await? {
+                Reply::Values(values) => Ok(values),
+                Reply::Value(_) => unreachable!(),
+            }
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        self.actual.get_db()
+    }
+}
+
+// Impl Deref & DefrefMut so the RedisConnection can be used as the real
+// redis::aio::Connection; the commands sent on it bypass the RedisConnection: they are
+// not audited, rate limited, measured against the reply cap nor timed for pauses
+
+impl Deref for RedisConnection {
+    type Target = redis::aio::Connection;
+    fn deref(&self) -> &Self::Target {
+        &self.actual
+    }
+}
+
+impl DerefMut for RedisConnection {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.actual
+    }
+}
+
+impl AsMut<redis::aio::Connection> for RedisConnection {
+    fn as_mut(&mut self) -> &mut redis::aio::Connection {
+        &mut self.actual
+    }
+}
+
+impl AsRef<redis::aio::Connection> for RedisConnection {
+    fn as_ref(&self) -> &redis::aio::Connection {
+        &self.actual
+    }
+}
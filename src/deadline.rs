@@ -0,0 +1,93 @@
+use std::time::{Duration, Instant};
+
+use redis::{aio::ConnectionLike, Cmd, Pipeline, RedisError, RedisFuture, Value};
+
+use crate::{runtime, RedisConnection};
+
+/// A view of a [`RedisConnection`] sharing a single time budget between all its commands.
+///
+/// Each command is given the time remaining until the deadline; a command
+/// issued once the deadline is reached fails right away. When a command times out,
+/// the underlying connection may still receive its reply so it will be dropped instead
+/// of being reused by the pool, like any connection whose command was cancelled.
+///
+/// Created with [`RedisConnection::with_deadline`].
+pub struct DeadlineConnection<'a> {
+    conn: &'a mut RedisConnection,
+    deadline: Instant,
+}
+
+impl RedisConnection {
+    /// Get a view of this connection whose commands must all complete before `deadline`.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::{Duration, Instant};
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 5);
+    /// let mut con = pool.get().await?;
+    /// let mut view = con.with_deadline(Instant::now() + Duration::from_secs(1));
+    /// view.set::<_, _, ()>("key", "value").await?;
+    /// let value: String = view.get("key").await?;
+    /// assert_eq!(value, "value");
+    ///
+    /// // the deadline is reached: the command is not sent
+    /// let mut view = con.with_deadline(Instant::now());
+    /// let err = view.get::<_, String>("key").await.unwrap_err();
+    /// assert!(err.is_timeout());
+    /// drop(con);
+    /// assert_eq!(pool.stats().available, 1);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_deadline(&mut self, deadline: Instant) -> DeadlineConnection<'_> {
+        DeadlineConnection {
+            conn: self,
+            deadline,
+        }
+    }
+}
+
+impl DeadlineConnection<'_> {
+    /// The deadline of this view
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Time left to the next command, failing once the deadline is reached
+    fn remaining(&self) -> Result<Duration, RedisError> {
+        match self.deadline.checked_duration_since(Instant::now()) {
+            Some(remaining) if remaining > Duration::from_secs(0) => Ok(remaining),
+            _ => Err(runtime::Elapsed.into()),
+        }
+    }
+}
+
+impl ConnectionLike for DeadlineConnection<'_> {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        Box::pin(async move {
+            let remaining = self.remaining()?;
+            runtime::timeout(remaining, self.conn.req_packed_command(cmd)).await?
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        Box::pin(async move {
+            let remaining = self.remaining()?;
+            runtime::timeout(remaining, self.conn.req_packed_commands(cmd, offset, count)).await?
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        self.conn.actual.get_db()
+    }
+}
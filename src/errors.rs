@@ -0,0 +1,269 @@
+use std::{io, time::Duration};
+
+use deadpool::managed::PoolError;
+use redis::{ErrorKind, RedisError};
+
+const SERVER_PAUSED: &str = "Server paused";
+
+/// Replace the cryptic `MOVED`/`ASK` errors returned by a cluster node with a clearer one.
+///
+/// Those errors means the manager has been pointed to a node of a Redis Cluster,
+/// which is not supported by this pool. The kind and the detail of the error
+/// (slot and node the key belongs to) are preserved. Other errors are returned untouched.
+///
+/// This is applied to every command issued through a [`RedisConnection`](crate::RedisConnection).
+///
+/// ```rust
+/// # #[cfg(feature = "fake")]
+/// # async_std::task::block_on(async {
+/// use async_std::{
+///     io::{ReadExt, WriteExt},
+///     net::TcpListener,
+/// };
+/// use redis::{AsyncCommands, ErrorKind};
+/// use redis_async_pool::{RedisConnectionManager, RedisPool};
+///
+/// // a cluster node redirecting every command to another node
+/// let node = TcpListener::bind("127.0.0.1:0").await?;
+/// let url = format!("redis://{}", node.local_addr()?);
+/// async_std::task::spawn(async move {
+///     let (mut stream, _) = node.accept().await?;
+///     let mut request = [0; 1024];
+///     while stream.read(&mut request).await? > 0 {
+///         stream.write_all(b"-MOVED 3999 127.0.0.1:6381\r\n").await?;
+///     }
+///     Ok::<_, std::io::Error>(())
+/// });
+///
+/// let pool = RedisPool::new(RedisConnectionManager::from_url(&url, false, None)?, 1);
+/// let err = pool.get().await?.get::<_, String>("key").await.unwrap_err();
+/// assert_eq!(err.kind(), ErrorKind::Moved);
+/// assert!(err.to_string().contains("use a cluster aware client"));
+/// assert_eq!(err.detail(), Some("3999 127.0.0.1:6381"));
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// # })
+/// # .unwrap();
+/// ```
+pub fn explain_cluster_redirect(err: RedisError) -> RedisError {
+    match err.kind() {
+        kind @ ErrorKind::Moved | kind @ ErrorKind::Ask => (
+            kind,
+            "Server is a Redis Cluster node, this pool only supports standalone servers: use a cluster aware client",
+            err.detail().unwrap_or_default().to_string(),
+        )
+            .into(),
+        _ => err,
+    }
+}
+
+/// Error returned when a command stalled because the server is paused.
+pub(crate) fn server_paused(threshold: Duration) -> RedisError {
+    (
+        ErrorKind::ExtensionError,
+        SERVER_PAUSED,
+        format!(
+            "command and server check did not complete within {:?}",
+            threshold
+        ),
+    )
+        .into()
+}
+
+/// Tell whether the error was returned because the server looks paused (`CLIENT PAUSE`).
+///
+/// Such errors are only returned when pause detection is enabled with
+/// [`RedisConnectionManager::with_pause_detection`](crate::RedisConnectionManager::with_pause_detection).
+pub fn is_server_paused(err: &RedisError) -> bool {
+    err.kind() == ErrorKind::ExtensionError && err.to_string().starts_with(SERVER_PAUSED)
+}
+
+/// Tell whether the server is loading its dataset in memory (`LOADING` error).
+///
+/// The connection is fine but the server does not serve commands until the loading
+/// completes; connections are not dropped on recycle because of it.
+pub fn is_server_loading(err: &RedisError) -> bool {
+    err.kind() == ErrorKind::BusyLoadingError
+}
+
+/// Tell whether the command was rejected because the server reached its memory limit
+/// (`OOM` error).
+pub fn is_out_of_memory(err: &RedisError) -> bool {
+    err.code() == Some("OOM")
+}
+
+/// Tell whether the error is caused by a transient state of the server (`LOADING`, `OOM`)
+/// rather than by a failed connection: the command may succeed once retried later.
+///
+/// ```rust
+/// # #[cfg(feature = "fake")]
+/// # async_std::task::block_on(async {
+/// use std::time::Duration;
+/// use redis::AsyncCommands;
+/// use redis_async_pool::{
+///     is_out_of_memory, is_server_loading, is_transient_server_error,
+///     FakeRedisConnectionManager, RedisPool,
+/// };
+///
+/// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 1);
+/// let mut con = pool.get().await?;
+/// let id = con.id();
+///
+/// // writes exceeding the memory limit are rejected
+/// redis::cmd("CONFIG").arg("SET").arg("maxmemory").arg(10).query_async::<_, ()>(&mut *con).await?;
+/// let err = con.set::<_, _, ()>("key", "a value too large").await.unwrap_err();
+/// assert!(is_out_of_memory(&err) && is_transient_server_error(&err));
+///
+/// // the fake server simulates the loading of its dataset for 100ms
+/// redis::cmd("DEBUG").arg("LOADING").arg(100).query_async::<_, ()>(&mut *con).await?;
+/// drop(con);
+/// // the connection is kept while the server is loading, despite its failed check
+/// let mut con = pool.get().await?;
+/// assert_eq!(con.id(), id);
+/// let err = con.get::<_, Option<String>>("key").await.unwrap_err();
+/// assert!(is_server_loading(&err) && is_transient_server_error(&err));
+/// async_std::task::sleep(Duration::from_millis(100)).await;
+/// assert_eq!(con.get::<_, Option<String>>("key").await?, None);
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// # })
+/// # .unwrap();
+/// ```
+pub fn is_transient_server_error(err: &RedisError) -> bool {
+    is_server_loading(err) || is_out_of_memory(err)
+}
+
+const REPLY_TOO_LARGE: &str = "Reply too large";
+
+/// Error returned when a reply exceeds the limit set with `with_max_reply_bytes`.
+pub(crate) fn reply_too_large(size: usize, max: usize) -> RedisError {
+    (
+        ErrorKind::ExtensionError,
+        REPLY_TOO_LARGE,
+        format!("reply of {} bytes exceeds the limit of {} bytes", size, max),
+    )
+        .into()
+}
+
+/// Tell whether the error was returned because a reply exceeded the limit set with
+/// [`RedisConnectionManager::with_max_reply_bytes`](crate::RedisConnectionManager::with_max_reply_bytes).
+pub fn is_reply_too_large(err: &RedisError) -> bool {
+    err.kind() == ErrorKind::ExtensionError && err.to_string().starts_with(REPLY_TOO_LARGE)
+}
+
+const CREATE_TIMEOUT: &str = "Connection creation timed out";
+
+/// Error returned by the pool manager when a connection could not be created in time;
+/// it is reported as `PoolError::Timeout(TimeoutType::Create)` by the pool.
+pub(crate) fn create_timeout() -> RedisError {
+    (ErrorKind::ExtensionError, CREATE_TIMEOUT).into()
+}
+
+pub(crate) fn is_create_timeout(err: &RedisError) -> bool {
+    err.kind() == ErrorKind::ExtensionError && err.to_string().starts_with(CREATE_TIMEOUT)
+}
+
+const POOL_POISONED: &str = "Pool poisoned";
+
+/// Error returned by the pool while it is poisoned.
+pub(crate) fn pool_poisoned(remaining: Duration) -> RedisError {
+    (
+        ErrorKind::ExtensionError,
+        POOL_POISONED,
+        format!(
+            "too many commands failed in a row, retry in {:?}",
+            remaining
+        ),
+    )
+        .into()
+}
+
+/// Tell whether no connection was handed out because the pool is poisoned, see
+/// [`RedisConnectionManager::with_poison_after`](crate::RedisConnectionManager::with_poison_after).
+pub fn is_pool_poisoned(err: &RedisError) -> bool {
+    err.kind() == ErrorKind::ExtensionError && err.to_string().starts_with(POOL_POISONED)
+}
+
+const BUDGET_EXHAUSTED: &str = "Connection budget exhausted";
+
+/// Error returned when a connection can not be created because its budget is exhausted.
+pub(crate) fn budget_exhausted(limit: usize) -> RedisError {
+    (
+        ErrorKind::ExtensionError,
+        BUDGET_EXHAUSTED,
+        format!("the {} connections of the budget are open", limit),
+    )
+        .into()
+}
+
+/// Tell whether a connection could not be created because the connections open by the
+/// managers sharing its budget reached the limit, see
+/// [`RedisConnectionManager::with_connection_budget`](crate::RedisConnectionManager::with_connection_budget).
+pub fn is_budget_exhausted(err: &RedisError) -> bool {
+    err.kind() == ErrorKind::ExtensionError && err.to_string().starts_with(BUDGET_EXHAUSTED)
+}
+
+const RECYCLE_FAILED: &str = "Connection recycle failed";
+
+/// Error reporting why a connection was discarded on recycle.
+pub(crate) fn recycle_failed(reason: String) -> RedisError {
+    (ErrorKind::ExtensionError, RECYCLE_FAILED, reason).into()
+}
+
+/// Errors of this crate, whose description is matched by the `is_*` functions
+const DESCRIPTIONS: &[&str] = &[
+    SERVER_PAUSED,
+    REPLY_TOO_LARGE,
+    CREATE_TIMEOUT,
+    POOL_POISONED,
+    BUDGET_EXHAUSTED,
+    RECYCLE_FAILED,
+];
+
+/// Copy `err`, shared by several callers, `RedisError` not being `Clone`.
+///
+/// Server error replies are parsed again, keeping their kind & code, io errors keep
+/// their classification (timeout, dropped connection, refusal) and the errors of this
+/// crate their description; other errors keep their kind, their description being moved
+/// into the detail.
+pub(crate) fn clone_error(err: &RedisError) -> RedisError {
+    if err.is_io_error() {
+        let kind = if err.is_timeout() {
+            io::ErrorKind::TimedOut
+        } else if err.is_connection_dropped() {
+            io::ErrorKind::ConnectionReset
+        } else if err.is_connection_refusal() {
+            io::ErrorKind::ConnectionRefused
+        } else {
+            io::ErrorKind::Other
+        };
+        return io::Error::new(kind, err.to_string()).into();
+    }
+    if let Some(code) = err.code() {
+        let reply = format!("-{} {}\r\n", code, err.detail().unwrap_or_default());
+        if let Err(err) = redis::parse_redis_value(reply.as_bytes()) {
+            return err;
+        }
+    }
+    let message = err.to_string();
+    match DESCRIPTIONS
+        .iter()
+        .find(|description| message.starts_with(*description))
+    {
+        Some(description) => match err.detail() {
+            Some(detail) => (err.kind(), *description, detail.to_string()).into(),
+            None => (err.kind(), *description).into(),
+        },
+        None => (err.kind(), "Shared error", message).into(),
+    }
+}
+
+/// Turn an error getting a connection from a pool into an error of a command.
+pub(crate) fn from_pool_error(err: PoolError<RedisError>) -> RedisError {
+    match err {
+        PoolError::Backend(err) => err,
+        PoolError::Timeout(timeout) => std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            format!("timed out getting a pooled connection ({:?})", timeout),
+        )
+        .into(),
+    }
+}
@@ -0,0 +1,982 @@
+//! Emulation of the Redis commands beyond the basic ones, to exercise the features of
+//! this crate in its examples. It is not part of the API of the fake server: it is
+//! partial and may change in any release.
+//!
+//! On top of the basic commands, the fake server understands:
+//!
+//! - `MULTI`, `EXEC`, `DISCARD`, `WATCH` & `UNWATCH`,
+//! - `RESET`, discarding the transaction and the authentication of the client,
+//! - `CLIENT ID`, `CLIENT INFO`, `CLIENT KILL ID`, `CLIENT PAUSE` (delaying the commands
+//!   of every client), `CLIENT UNPAUSE` & `CLIENT SETINFO`,
+//! - `AUTH` & `ACL SETUSER`, enforcing the commands allowed to the users, not their key
+//!   patterns,
+//! - `INFO`, with the `server` & `replication` sections: the replication offset is the
+//!   size of the write commands applied, reported as both the master & replica offset,
+//! - `CONFIG GET` & `CONFIG SET`, for the `maxclients`, `maxmemory`, `maxmemory-policy`,
+//!   `notify-keyspace-events` & `timeout` parameters only: `SET` fails with an `OOM`
+//!   error once the keys & values stored exceed `maxmemory`,
+//! - `SUBSCRIBE`, `PSUBSCRIBE` & `PUBLISH`: while a client is subscribed, the expired
+//!   keys are removed every few milliseconds and, if enabled by `notify-keyspace-events`
+//!   (`Ex`), notified on the `__keyevent@0__:expired` channel,
+//! - `DEBUG LOADING <millis>`, specific to the fake server: every command fails with a
+//!   `LOADING` error for the given duration,
+//! - `SCRIPT LOAD`, `SCRIPT FLUSH` & `EVALSHA`, running only the scripts made of a single
+//!   `return redis.call(...)` whose arguments are `KEYS[n]`, `ARGV[n]` or quoted strings,
+//! - `WAIT` & `WAITAOF`: without replica nor append only file, waiting for a replica
+//!   blocks until the timeout and waiting for the local fsync fails,
+//! - `XADD` (with generated ids only), `XGROUP CREATE`, `XREADGROUP` (reading new
+//!   entries of a single stream only) & `XACK`, the streams being stored apart from the
+//!   strings.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    net::Shutdown,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use async_std::{
+    io::{BufReader, WriteExt},
+    net::TcpStream,
+    task,
+};
+
+use super::{array, bulk, execute, glob_match, integer, read_command, Store};
+
+/// Connected clients, by id, to be closed by `CLIENT KILL`
+type Clients = Arc<Mutex<HashMap<u64, TcpStream>>>;
+
+/// ACL users defined with `ACL SETUSER`, by name
+type Users = Arc<Mutex<HashMap<Vec<u8>, User>>>;
+
+/// State of a fake server, shared by the tasks serving its clients
+#[derive(Clone, Default)]
+pub(super) struct Server {
+    store: Store,
+    clients: Clients,
+    users: Users,
+    /// Library name & version reported by each client with `CLIENT SETINFO`
+    lib_info: Arc<Mutex<HashMap<u64, (String, String)>>>,
+    /// End of the pause requested with `CLIENT PAUSE`
+    paused_until: Arc<Mutex<Option<Instant>>>,
+    /// Replication offset: the size of the write commands applied so far
+    repl_offset: Arc<AtomicU64>,
+    /// Number of writes of each key, to abort the transactions watching it
+    versions: Arc<Mutex<HashMap<Vec<u8>, u64>>>,
+    /// Parameters changed with `CONFIG SET`
+    config: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+    /// End of the loading simulated with `DEBUG LOADING`
+    loading_until: Arc<Mutex<Option<Instant>>>,
+    /// Streams, by key, stored apart from the strings
+    streams: Arc<Mutex<HashMap<Vec<u8>, Stream>>>,
+    /// Scripts loaded with `SCRIPT LOAD`, by SHA1 digest
+    scripts: Arc<Mutex<HashMap<String, String>>>,
+    /// Channels & patterns subscribed to with `SUBSCRIBE` & `PSUBSCRIBE`, by client
+    subscriptions: Arc<Mutex<HashMap<u64, Subscriptions>>>,
+}
+
+/// Channels & channel patterns a client subscribed to
+#[derive(Default)]
+struct Subscriptions {
+    channels: Vec<Vec<u8>>,
+    patterns: Vec<Vec<u8>>,
+}
+
+/// Entries & consumer groups of a stream
+#[derive(Default)]
+struct Stream {
+    /// Identifier & fields of the entries, oldest first
+    entries: Vec<(String, Vec<Vec<u8>>)>,
+    /// Consumer groups, by name
+    groups: HashMap<Vec<u8>, Group>,
+}
+
+/// Consumer group of a stream
+#[derive(Default)]
+struct Group {
+    /// Number of entries delivered to the group
+    delivered: usize,
+    /// Entries delivered but not acknowledged
+    pending: HashSet<String>,
+}
+
+/// Identifier of the last entry added to a stream, as generated by `XADD *`
+static LAST_ENTRY: AtomicU64 = AtomicU64::new(0);
+
+/// Delay between two reads of a blocked `XREADGROUP`
+const BLOCKED_POLL: Duration = Duration::from_millis(5);
+
+/// Delay between two runs of the background expiry, while a client is subscribed
+const EXPIRY_CYCLE: Duration = Duration::from_millis(5);
+
+/// Parameters known to `CONFIG`, with their default value
+const CONFIG: &[(&str, &str)] = &[
+    ("maxclients", "10000"),
+    ("maxmemory", "0"),
+    ("maxmemory-policy", "noeviction"),
+    ("notify-keyspace-events", ""),
+    ("timeout", "0"),
+];
+
+/// Transaction of a client
+#[derive(Default)]
+struct Transaction {
+    /// Keys watched with `WATCH`, with their version at that time
+    watched: HashMap<Vec<u8>, u64>,
+    /// Commands queued since `MULTI`
+    queued: Option<Vec<Vec<Vec<u8>>>>,
+}
+
+/// What to do with a command, given the transaction of the client
+enum Step {
+    Reply(Vec<u8>),
+    /// Run the commands of the transaction (`EXEC`)
+    Exec(Vec<Vec<Vec<u8>>>),
+    /// Run the command
+    Run,
+}
+
+impl Transaction {
+    fn step(&mut self, server: &Server, args: &[Vec<u8>]) -> Step {
+        let name = args
+            .first()
+            .map(|name| String::from_utf8_lossy(name).to_uppercase())
+            .unwrap_or_default();
+        let reply = |reply: &[u8]| Step::Reply(reply.to_vec());
+        match (name.as_str(), &mut self.queued) {
+            ("MULTI", Some(_)) => reply(b"-ERR MULTI calls can not be nested\r\n"),
+            ("MULTI", queued) => {
+                *queued = Some(Vec::new());
+                reply(b"+OK\r\n")
+            }
+            ("WATCH", Some(_)) => reply(b"-ERR WATCH inside MULTI is not allowed\r\n"),
+            ("WATCH", None) => {
+                let versions = server.versions.lock().unwrap();
+                for key in &args[1..] {
+                    let version = versions.get(key).copied().unwrap_or_default();
+                    self.watched.insert(key.clone(), version);
+                }
+                reply(b"+OK\r\n")
+            }
+            ("UNWATCH", None) => {
+                self.watched.clear();
+                reply(b"+OK\r\n")
+            }
+            ("EXEC", None) => reply(b"-ERR EXEC without MULTI\r\n"),
+            ("DISCARD", None) => reply(b"-ERR DISCARD without MULTI\r\n"),
+            ("EXEC", queued) => {
+                let queued = queued.take().unwrap_or_default();
+                let versions = server.versions.lock().unwrap();
+                let aborted = self.watched.drain().any(|(key, version)| {
+                    versions.get(&key).copied().unwrap_or_default() != version
+                });
+                if aborted {
+                    reply(b"*-1\r\n")
+                } else {
+                    Step::Exec(queued)
+                }
+            }
+            ("DISCARD", queued) => {
+                *queued = None;
+                self.watched.clear();
+                reply(b"+OK\r\n")
+            }
+            (_, Some(queued)) => {
+                queued.push(args.to_vec());
+                reply(b"+QUEUED\r\n")
+            }
+            (_, None) => Step::Run,
+        }
+    }
+}
+
+/// Version reported by `INFO`
+const VERSION: &str = "7.2.0";
+
+/// ACL user of the fake server: only its commands are enforced, not its key patterns
+#[derive(Default)]
+struct User {
+    enabled: bool,
+    nopass: bool,
+    passwords: HashSet<Vec<u8>>,
+    all_commands: bool,
+    /// Commands allowed, in upper case, by the user or one of its selectors
+    commands: HashSet<String>,
+}
+
+impl User {
+    fn apply(&mut self, rule: &str) {
+        match rule {
+            "reset" => *self = Self::default(),
+            "on" => self.enabled = true,
+            "off" => self.enabled = false,
+            "nopass" => self.nopass = true,
+            "+@all" | "allcommands" => self.all_commands = true,
+            "-@all" | "nocommands" => {
+                self.all_commands = false;
+                self.commands.clear();
+            }
+            _ => {
+                if let Some(password) = rule.strip_prefix('>') {
+                    self.passwords.insert(password.as_bytes().to_vec());
+                } else if let Some(command) = rule.strip_prefix('+') {
+                    self.commands.insert(command.to_uppercase());
+                } else if let Some(command) = rule.strip_prefix('-') {
+                    self.commands.remove(&command.to_uppercase());
+                } else if let Some(selector) = rule
+                    .strip_prefix('(')
+                    .and_then(|rule| rule.strip_suffix(')'))
+                {
+                    // the commands of a selector are allowed on its keys only
+                    for rule in selector.split_whitespace() {
+                        if let Some(command) = rule.strip_prefix('+') {
+                            self.commands.insert(command.to_uppercase());
+                        }
+                    }
+                }
+                // key & channel patterns are not enforced
+            }
+        }
+    }
+
+    fn allows(&self, command: &str) -> bool {
+        self.all_commands || self.commands.contains(command)
+    }
+}
+
+/// Answer the commands sent on `stream` by the client `id` until it is closed
+pub(super) async fn serve(stream: TcpStream, id: u64, server: Server) -> io::Result<()> {
+    server.clients.lock().unwrap().insert(id, stream.clone());
+    let mut reader = BufReader::new(stream.clone());
+    let mut writer = stream;
+    // authenticated user, the default user allowed every command if none
+    let mut user = None;
+    let mut transaction = Transaction::default();
+    let served = async {
+        while let Some(args) = read_command(&mut reader).await? {
+            let paused = *server.paused_until.lock().unwrap();
+            if let Some(pause) =
+                paused.and_then(|until| until.checked_duration_since(Instant::now()))
+            {
+                task::sleep(pause).await;
+            }
+            if let Some(timeout) = wait_timeout(&args) {
+                // no replica acknowledges the writes: block until the timeout
+                match timeout {
+                    Some(timeout) => task::sleep(timeout).await,
+                    None => async_std::future::pending().await,
+                }
+            }
+            if args.len() == 1 && args[0].eq_ignore_ascii_case(b"RESET") {
+                // back to a new client: unauthenticated, without transaction
+                user = None;
+                transaction = Transaction::default();
+                writer.write_all(b"+RESET\r\n").await?;
+                continue;
+            }
+            let reply = match transaction.step(&server, &args) {
+                Step::Reply(reply) => reply,
+                Step::Exec(queued) => {
+                    let mut reply = format!("*{}\r\n", queued.len()).into_bytes();
+                    for args in &queued {
+                        reply.extend(answer(id, &server, &mut user, args));
+                    }
+                    reply
+                }
+                Step::Run => {
+                    let blocked_until = blocked_until(&args);
+                    loop {
+                        let reply = answer(id, &server, &mut user, &args);
+                        match blocked_until {
+                            // no entry yet
+                            Some(until) if reply == b"*-1\r\n" && Instant::now() < until => {
+                                task::sleep(BLOCKED_POLL).await
+                            }
+                            _ => break reply,
+                        }
+                    }
+                }
+            };
+            writer.write_all(&reply).await?;
+        }
+        Ok(())
+    }
+    .await;
+    server.clients.lock().unwrap().remove(&id);
+    server.lib_info.lock().unwrap().remove(&id);
+    server.subscriptions.lock().unwrap().remove(&id);
+    served
+}
+
+/// Answer `SUBSCRIBE`, `PSUBSCRIBE` & `PUBLISH`, `None` for the other commands
+fn pubsub_command(id: u64, server: &Server, args: &[Vec<u8>]) -> Option<Vec<u8>> {
+    let (name, args) = args.split_first()?;
+    let name = String::from_utf8_lossy(name).to_lowercase();
+    Some(match (name.as_str(), args) {
+        ("subscribe", _) | ("psubscribe", _) if !args.is_empty() => {
+            let mut subscriptions = server.subscriptions.lock().unwrap();
+            let subscribed = subscriptions.entry(id).or_insert_with(|| {
+                task::spawn(expire_keys(id, server.clone()));
+                Subscriptions::default()
+            });
+            let mut reply = Vec::new();
+            for channel in args {
+                if name == "subscribe" {
+                    subscribed.channels.push(channel.clone());
+                } else {
+                    subscribed.patterns.push(channel.clone());
+                }
+                let count = subscribed.channels.len() + subscribed.patterns.len();
+                reply.extend(b"*3\r\n");
+                reply.extend(bulk(name.as_bytes()));
+                reply.extend(bulk(channel));
+                reply.extend(integer(count));
+            }
+            reply
+        }
+        ("publish", [channel, message]) => integer(publish(server, channel, message)),
+        ("subscribe", _) | ("psubscribe", _) | ("publish", _) => {
+            format!("-ERR wrong number of arguments for '{}' command\r\n", name).into_bytes()
+        }
+        _ => return None,
+    })
+}
+
+/// Send `message` to the subscribers of `channel`, returning the number of messages sent
+fn publish(server: &Server, channel: &[u8], message: &[u8]) -> usize {
+    let mut sent = Vec::new();
+    {
+        let subscriptions = server.subscriptions.lock().unwrap();
+        let clients = server.clients.lock().unwrap();
+        for (client, subscribed) in subscriptions.iter() {
+            let stream = match clients.get(client) {
+                Some(stream) => stream,
+                None => continue,
+            };
+            if subscribed
+                .channels
+                .iter()
+                .any(|subscribed| subscribed == channel)
+            {
+                sent.push((stream.clone(), array(&[b"message", channel, message])));
+            }
+            for pattern in &subscribed.patterns {
+                if glob_match(pattern, channel) {
+                    let pmessage = array(&[b"pmessage", pattern, channel, message]);
+                    sent.push((stream.clone(), pmessage));
+                }
+            }
+        }
+    }
+    let count = sent.len();
+    task::spawn(async move {
+        for (mut stream, message) in sent {
+            let _ = stream.write_all(&message).await;
+        }
+    });
+    count
+}
+
+/// Remove the expired keys in the background while the client `id` is subscribed,
+/// notifying them if enabled by `notify-keyspace-events`
+async fn expire_keys(id: u64, server: Server) {
+    while server.subscriptions.lock().unwrap().contains_key(&id) {
+        let now = Instant::now();
+        let mut expired = Vec::new();
+        server
+            .store
+            .lock()
+            .unwrap()
+            .retain(|key, (_, expires_at)| match expires_at {
+                Some(expires_at) if *expires_at <= now => {
+                    expired.push(key.clone());
+                    false
+                }
+                _ => true,
+            });
+        let flags = server
+            .config
+            .lock()
+            .unwrap()
+            .get("notify-keyspace-events")
+            .cloned()
+            .unwrap_or_default();
+        if flags.contains(&b'E') && (flags.contains(&b'x') || flags.contains(&b'A')) {
+            for key in &expired {
+                publish(&server, b"__keyevent@0__:expired", key);
+            }
+        }
+        task::sleep(EXPIRY_CYCLE).await;
+    }
+}
+
+/// Answer a command sent by the client `id`, authenticated as `user`
+fn answer(id: u64, server: &Server, user: &mut Option<Vec<u8>>, args: &[Vec<u8>]) -> Vec<u8> {
+    let users = &server.users;
+    let reply = debug_command(server, args)
+        .or_else(|| unavailable(server, args))
+        .or_else(|| auth(users, user, args))
+        .or_else(|| denied(users, user.as_deref(), args))
+        .or_else(|| acl_command(users, args))
+        .or_else(|| client_command(id, server, args))
+        .or_else(|| info(server, args))
+        .or_else(|| config_command(server, args))
+        .or_else(|| stream_command(server, args))
+        .or_else(|| wait_command(args))
+        .or_else(|| script_command(id, server, user, args))
+        .or_else(|| pubsub_command(id, server, args))
+        .unwrap_or_else(|| execute(&server.store, args));
+    if let (Some(keys), false) = (written_keys(args), reply.starts_with(b"-")) {
+        let size = args.iter().map(Vec::len).sum::<usize>();
+        server.repl_offset.fetch_add(size as u64, Ordering::Relaxed);
+        let mut versions = server.versions.lock().unwrap();
+        for key in keys {
+            *versions.entry(key.clone()).or_default() += 1;
+        }
+    }
+    reply
+}
+
+/// Authenticate the client with `AUTH`, `None` for the other commands
+fn auth(users: &Users, user: &mut Option<Vec<u8>>, args: &[Vec<u8>]) -> Option<Vec<u8>> {
+    let (name, args) = args.split_first()?;
+    if !name.eq_ignore_ascii_case(b"AUTH") {
+        return None;
+    }
+    Some(match args {
+        [username, password] => {
+            let valid = match users.lock().unwrap().get(username) {
+                Some(known) => {
+                    known.enabled && (known.nopass || known.passwords.contains(password))
+                }
+                None => false,
+            };
+            if valid {
+                *user = Some(username.clone());
+                b"+OK\r\n".to_vec()
+            } else {
+                b"-WRONGPASS invalid username-password pair or user is disabled.\r\n".to_vec()
+            }
+        }
+        [_password] => b"-ERR AUTH <password> called without any password configured for \
+            the default user. Are you sure your configuration is correct?\r\n"
+            .to_vec(),
+        _ => b"-ERR wrong number of arguments for 'auth' command\r\n".to_vec(),
+    })
+}
+
+/// Reject the commands not allowed to the authenticated `user`
+fn denied(users: &Users, user: Option<&[u8]>, args: &[Vec<u8>]) -> Option<Vec<u8>> {
+    let user = user?;
+    let name = String::from_utf8_lossy(args.first()?).to_uppercase();
+    let users = users.lock().unwrap();
+    if matches!(users.get(user), Some(known) if known.allows(&name)) {
+        return None;
+    }
+    Some(
+        format!(
+            "-NOPERM User {} has no permissions to run the '{}' command\r\n",
+            String::from_utf8_lossy(user),
+            name.to_lowercase()
+        )
+        .into_bytes(),
+    )
+}
+
+/// Answer `ACL SETUSER`, `None` for the other commands
+fn acl_command(users: &Users, args: &[Vec<u8>]) -> Option<Vec<u8>> {
+    let (name, args) = args.split_first()?;
+    if !name.eq_ignore_ascii_case(b"ACL") {
+        return None;
+    }
+    Some(match args {
+        [subcommand, username, rules @ ..] if subcommand.eq_ignore_ascii_case(b"SETUSER") => {
+            let mut users = users.lock().unwrap();
+            let user = users.entry(username.clone()).or_default();
+            for rule in rules {
+                user.apply(&String::from_utf8_lossy(rule));
+            }
+            b"+OK\r\n".to_vec()
+        }
+        _ => format!(
+            "-ERR unknown subcommand '{}'\r\n",
+            String::from_utf8_lossy(args.first().map_or(&[][..], |arg| &arg[..]))
+        )
+        .into_bytes(),
+    })
+}
+
+/// Answer the `CLIENT` commands, `None` for the other commands
+fn client_command(id: u64, server: &Server, args: &[Vec<u8>]) -> Option<Vec<u8>> {
+    let clients = &server.clients;
+    let (name, args) = args.split_first()?;
+    if !name.eq_ignore_ascii_case(b"CLIENT") {
+        return None;
+    }
+    let subcommand = args
+        .first()
+        .map(|subcommand| String::from_utf8_lossy(subcommand).to_uppercase())
+        .unwrap_or_default();
+    Some(match (subcommand.as_str(), &args[1.min(args.len())..]) {
+        ("ID", []) => integer(id as usize),
+        ("INFO", []) => {
+            let clients = clients.lock().unwrap();
+            let stream = clients.get(&id)?;
+            let (addr, laddr) = (stream.peer_addr().ok()?, stream.local_addr().ok()?);
+            let lib_info = server.lib_info.lock().unwrap();
+            let (lib_name, lib_ver) = lib_info.get(&id).cloned().unwrap_or_default();
+            bulk(
+                format!(
+                    "id={} addr={} laddr={} lib-name={} lib-ver={}\n",
+                    id, addr, laddr, lib_name, lib_ver
+                )
+                .as_bytes(),
+            )
+        }
+        ("SETINFO", [attribute, value]) => {
+            let value = String::from_utf8_lossy(value).into_owned();
+            let mut lib_info = server.lib_info.lock().unwrap();
+            let (lib_name, lib_ver) = lib_info.entry(id).or_default();
+            match String::from_utf8_lossy(attribute).to_uppercase().as_str() {
+                "LIB-NAME" => *lib_name = value,
+                "LIB-VER" => *lib_ver = value,
+                attribute => {
+                    return Some(
+                        format!("-ERR Unrecognized option '{}'\r\n", attribute).into_bytes(),
+                    )
+                }
+            }
+            b"+OK\r\n".to_vec()
+        }
+        ("KILL", [filter, killed]) if filter.eq_ignore_ascii_case(b"ID") => {
+            let killed = String::from_utf8_lossy(killed)
+                .parse()
+                .ok()
+                .and_then(|killed| clients.lock().unwrap().remove(&killed));
+            if let Some(stream) = &killed {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+            integer(killed.is_some() as usize)
+        }
+        ("PAUSE", [millis, ..]) => match String::from_utf8_lossy(millis).parse() {
+            Ok(millis) => {
+                let until = Instant::now() + Duration::from_millis(millis);
+                *server.paused_until.lock().unwrap() = Some(until);
+                b"+OK\r\n".to_vec()
+            }
+            Err(_) => b"-ERR timeout is not an integer or out of range\r\n".to_vec(),
+        },
+        ("UNPAUSE", []) => {
+            *server.paused_until.lock().unwrap() = None;
+            b"+OK\r\n".to_vec()
+        }
+        _ => format!(
+            "-ERR unknown subcommand '{}'\r\n",
+            String::from_utf8_lossy(args.first().map_or(&[][..], |arg| &arg[..]))
+        )
+        .into_bytes(),
+    })
+}
+
+/// Answer the `INFO` command, `None` for the other commands
+fn info(server: &Server, args: &[Vec<u8>]) -> Option<Vec<u8>> {
+    let (name, sections) = args.split_first()?;
+    if !name.eq_ignore_ascii_case(b"INFO") {
+        return None;
+    }
+    let wanted = |section: &[u8]| {
+        sections.is_empty()
+            || sections
+                .iter()
+                .any(|wanted| wanted.eq_ignore_ascii_case(section) || wanted == b"all")
+    };
+    let mut info = String::new();
+    if wanted(b"server") {
+        info.push_str(&format!("# Server\r\nredis_version:{}\r\n", VERSION));
+    }
+    if wanted(b"replication") {
+        let offset = server.repl_offset.load(Ordering::Relaxed);
+        info.push_str(&format!(
+            "# Replication\r\nrole:master\r\nmaster_repl_offset:{}\r\nslave_repl_offset:{}\r\n",
+            offset, offset
+        ));
+    }
+    Some(bulk(info.as_bytes()))
+}
+
+/// Answer the `DEBUG LOADING <millis>` command, specific to the fake server, simulating
+/// the loading of the dataset for `millis`; `None` for the other commands
+fn debug_command(server: &Server, args: &[Vec<u8>]) -> Option<Vec<u8>> {
+    let (name, args) = args.split_first()?;
+    if !name.eq_ignore_ascii_case(b"DEBUG") {
+        return None;
+    }
+    Some(match args {
+        [subcommand, millis] if subcommand.eq_ignore_ascii_case(b"LOADING") => {
+            match String::from_utf8_lossy(millis).parse() {
+                Ok(millis) => {
+                    let until = Instant::now() + Duration::from_millis(millis);
+                    *server.loading_until.lock().unwrap() = Some(until);
+                    b"+OK\r\n".to_vec()
+                }
+                Err(_) => b"-ERR value is not an integer or out of range\r\n".to_vec(),
+            }
+        }
+        _ => b"-ERR unknown DEBUG subcommand\r\n".to_vec(),
+    })
+}
+
+/// Reject the command while the dataset is loading, or if it is a `SET` exceeding the
+/// `maxmemory` limit; `None` if the command can run
+fn unavailable(server: &Server, args: &[Vec<u8>]) -> Option<Vec<u8>> {
+    let loading = *server.loading_until.lock().unwrap();
+    if matches!(loading, Some(until) if until > Instant::now()) {
+        return Some(b"-LOADING Redis is loading the dataset in memory\r\n".to_vec());
+    }
+    let maxmemory: usize = match server.config.lock().unwrap().get("maxmemory") {
+        Some(maxmemory) => String::from_utf8_lossy(maxmemory)
+            .parse()
+            .unwrap_or_default(),
+        None => 0,
+    };
+    match args {
+        [name, key, value, ..] if maxmemory > 0 && name.eq_ignore_ascii_case(b"SET") => {
+            let used: usize = server
+                .store
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(key, (value, _))| key.len() + value.len())
+                .sum();
+            if used + key.len() + value.len() > maxmemory {
+                Some(b"-OOM command not allowed when used memory > 'maxmemory'.\r\n".to_vec())
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Answer the `XADD`, `XGROUP CREATE`, `XREADGROUP` & `XACK` commands, `None` for the
+/// other commands
+fn stream_command(server: &Server, args: &[Vec<u8>]) -> Option<Vec<u8>> {
+    let (name, args) = args.split_first()?;
+    let name = String::from_utf8_lossy(name).to_uppercase();
+    let mut streams = server.streams.lock().unwrap();
+    Some(match (name.as_str(), args) {
+        ("XADD", [key, id, fields @ ..])
+            if id == b"*" && !fields.is_empty() && fields.len() % 2 == 0 =>
+        {
+            let id = format!("{}-0", LAST_ENTRY.fetch_add(1, Ordering::Relaxed) + 1);
+            let stream = streams.entry(key.clone()).or_default();
+            stream.entries.push((id.clone(), fields.to_vec()));
+            bulk(id.as_bytes())
+        }
+        ("XGROUP", [subcommand, key, group, start, options @ ..])
+            if subcommand.eq_ignore_ascii_case(b"CREATE") =>
+        {
+            let mkstream = options
+                .iter()
+                .any(|option| option.eq_ignore_ascii_case(b"MKSTREAM"));
+            let stream = match streams.get_mut(key) {
+                Some(stream) => stream,
+                None if mkstream => streams.entry(key.clone()).or_default(),
+                None => {
+                    return Some(
+                        b"-ERR The XGROUP subcommand requires the key to exist\r\n".to_vec(),
+                    )
+                }
+            };
+            if stream.groups.contains_key(group) {
+                return Some(b"-BUSYGROUP Consumer Group name already exists\r\n".to_vec());
+            }
+            let delivered = if start == b"$" {
+                stream.entries.len()
+            } else {
+                0
+            };
+            stream.groups.insert(
+                group.clone(),
+                Group {
+                    delivered,
+                    pending: HashSet::new(),
+                },
+            );
+            b"+OK\r\n".to_vec()
+        }
+        ("XREADGROUP", [.., streams_option, key, id])
+            if streams_option.eq_ignore_ascii_case(b"STREAMS") =>
+        {
+            let group = match option(args, b"GROUP") {
+                Some(group) => group,
+                None => return Some(b"-ERR syntax error\r\n".to_vec()),
+            };
+            let count = option(args, b"COUNT")
+                .and_then(|count| String::from_utf8_lossy(count).parse().ok())
+                .unwrap_or(usize::MAX);
+            if id != b">" {
+                return Some(b"-ERR only new entries (>) can be read\r\n".to_vec());
+            }
+            let stream = streams.get_mut(key);
+            let stream = match stream {
+                Some(stream) if stream.groups.contains_key(group) => stream,
+                _ => return Some(b"-NOGROUP No such key or consumer group\r\n".to_vec()),
+            };
+            let group = stream.groups.get_mut(group)?;
+            let read = &stream.entries[group.delivered..];
+            let read = &read[..count.min(read.len())];
+            if read.is_empty() {
+                return Some(b"*-1\r\n".to_vec());
+            }
+            group.delivered += read.len();
+            let mut reply = b"*1\r\n*2\r\n".to_vec();
+            reply.extend(bulk(key));
+            reply.extend(format!("*{}\r\n", read.len()).into_bytes());
+            for (id, fields) in read {
+                group.pending.insert(id.clone());
+                reply.extend(b"*2\r\n");
+                reply.extend(bulk(id.as_bytes()));
+                reply.extend(format!("*{}\r\n", fields.len()).into_bytes());
+                for field in fields {
+                    reply.extend(bulk(field));
+                }
+            }
+            reply
+        }
+        ("XACK", [key, group, ids @ ..]) if !ids.is_empty() => {
+            let group = streams
+                .get_mut(key)
+                .and_then(|stream| stream.groups.get_mut(group));
+            match group {
+                Some(group) => integer(
+                    ids.iter()
+                        .filter(|id| group.pending.remove(&*String::from_utf8_lossy(id)))
+                        .count(),
+                ),
+                None => integer(0),
+            }
+        }
+        ("XADD", _) | ("XGROUP", _) | ("XREADGROUP", _) | ("XACK", _) => format!(
+            "-ERR wrong number of arguments for '{}' command\r\n",
+            name.to_lowercase()
+        )
+        .into_bytes(),
+        _ => return None,
+    })
+}
+
+/// Answer the `SCRIPT LOAD`, `SCRIPT FLUSH` & `EVALSHA` commands, `None` for the other
+/// commands
+fn script_command(
+    id: u64,
+    server: &Server,
+    user: &mut Option<Vec<u8>>,
+    args: &[Vec<u8>],
+) -> Option<Vec<u8>> {
+    let (name, args) = args.split_first()?;
+    let name = String::from_utf8_lossy(name).to_uppercase();
+    Some(match (name.as_str(), args) {
+        ("SCRIPT", [subcommand, script]) if subcommand.eq_ignore_ascii_case(b"LOAD") => {
+            let script = String::from_utf8_lossy(script).into_owned();
+            let hash = redis::Script::new(&script).get_hash().to_string();
+            server.scripts.lock().unwrap().insert(hash.clone(), script);
+            bulk(hash.as_bytes())
+        }
+        ("SCRIPT", [subcommand, ..]) if subcommand.eq_ignore_ascii_case(b"FLUSH") => {
+            server.scripts.lock().unwrap().clear();
+            b"+OK\r\n".to_vec()
+        }
+        ("EVALSHA", [hash, numkeys, args @ ..]) => {
+            let script = server
+                .scripts
+                .lock()
+                .unwrap()
+                .get(&*String::from_utf8_lossy(hash).to_lowercase())
+                .cloned();
+            let script = match script {
+                Some(script) => script,
+                None => {
+                    return Some(b"-NOSCRIPT No matching script. Please use EVAL.\r\n".to_vec())
+                }
+            };
+            let numkeys = match String::from_utf8_lossy(numkeys).parse::<usize>() {
+                Ok(numkeys) if numkeys <= args.len() => numkeys,
+                _ => {
+                    return Some(
+                        b"-ERR Number of keys can't be greater than number of args\r\n".to_vec(),
+                    )
+                }
+            };
+            let (keys, argv) = args.split_at(numkeys);
+            match script_call(&script, keys, argv) {
+                Some(call) => answer(id, server, user, &call),
+                None => {
+                    b"-ERR the fake server only runs `return redis.call(...)` scripts\r\n".to_vec()
+                }
+            }
+        }
+        ("SCRIPT", _) | ("EVALSHA", _) => format!(
+            "-ERR wrong number of arguments for '{}' command\r\n",
+            name.to_lowercase()
+        )
+        .into_bytes(),
+        _ => return None,
+    })
+}
+
+/// Arguments of the command run by a `return redis.call(...)` script, whose arguments are
+/// `KEYS[n]`, `ARGV[n]` or quoted strings; `None` for the other scripts
+fn script_call(script: &str, keys: &[Vec<u8>], argv: &[Vec<u8>]) -> Option<Vec<Vec<u8>>> {
+    let call = script
+        .trim()
+        .strip_prefix("return redis.call(")?
+        .strip_suffix(')')?;
+    call.split(',')
+        .map(|arg| {
+            let arg = arg.trim();
+            let indexed = |name: &str, values: &[Vec<u8>]| {
+                let index: usize = arg.strip_prefix(name)?.strip_suffix(']')?.parse().ok()?;
+                values.get(index.checked_sub(1)?).cloned()
+            };
+            indexed("KEYS[", keys)
+                .or_else(|| indexed("ARGV[", argv))
+                .or_else(|| {
+                    let quoted = arg
+                        .strip_prefix('\'')
+                        .and_then(|arg| arg.strip_suffix('\''));
+                    let quoted = quoted.or_else(|| arg.strip_prefix('"')?.strip_suffix('"'));
+                    Some(quoted?.as_bytes().to_vec())
+                })
+        })
+        .collect()
+}
+
+/// Time until which a `XREADGROUP ... BLOCK <millis>` command waits for entries, `None`
+/// for the other commands
+fn blocked_until(args: &[Vec<u8>]) -> Option<Instant> {
+    let (name, args) = args.split_first()?;
+    if !name.eq_ignore_ascii_case(b"XREADGROUP") {
+        return None;
+    }
+    let millis: u64 = String::from_utf8_lossy(option(args, b"BLOCK")?)
+        .parse()
+        .ok()?;
+    // blocking forever is blocking for a day
+    let millis = if millis == 0 { 86_400_000 } else { millis };
+    Some(Instant::now() + Duration::from_millis(millis))
+}
+
+/// Value following the option `name` in the arguments of a command
+fn option<'a>(args: &'a [Vec<u8>], name: &[u8]) -> Option<&'a Vec<u8>> {
+    args.windows(2)
+        .find(|option| option[0].eq_ignore_ascii_case(name))
+        .map(|option| &option[1])
+}
+
+/// Answer the `CONFIG GET` & `CONFIG SET` commands, `None` for the other commands
+fn config_command(server: &Server, args: &[Vec<u8>]) -> Option<Vec<u8>> {
+    let (name, args) = args.split_first()?;
+    if !name.eq_ignore_ascii_case(b"CONFIG") {
+        return None;
+    }
+    let subcommand = args
+        .first()
+        .map(|subcommand| String::from_utf8_lossy(subcommand).to_uppercase())
+        .unwrap_or_default();
+    let mut config = server.config.lock().unwrap();
+    Some(match (subcommand.as_str(), &args[1.min(args.len())..]) {
+        ("GET", [parameter]) => {
+            let parameter = String::from_utf8_lossy(parameter).to_lowercase();
+            let value = config.get(&parameter).cloned().or_else(|| {
+                CONFIG
+                    .iter()
+                    .find(|(known, _)| *known == parameter)
+                    .map(|(_, default)| default.as_bytes().to_vec())
+            });
+            match value {
+                Some(value) => {
+                    let mut reply = b"*2\r\n".to_vec();
+                    reply.extend(bulk(parameter.as_bytes()));
+                    reply.extend(bulk(&value));
+                    reply
+                }
+                None => b"*0\r\n".to_vec(),
+            }
+        }
+        ("SET", [parameter, value]) => {
+            let parameter = String::from_utf8_lossy(parameter).to_lowercase();
+            if CONFIG.iter().any(|(known, _)| *known == parameter) {
+                config.insert(parameter, value.clone());
+                b"+OK\r\n".to_vec()
+            } else {
+                format!(
+                    "-ERR Unknown option or number of arguments for CONFIG SET - '{}'\r\n",
+                    parameter
+                )
+                .into_bytes()
+            }
+        }
+        _ => format!(
+            "-ERR unknown subcommand '{}'\r\n",
+            String::from_utf8_lossy(args.first().map_or(&[][..], |arg| &arg[..]))
+        )
+        .into_bytes(),
+    })
+}
+
+/// Answer the `WAIT` & `WAITAOF` commands, the fake server having no replica and no
+/// append only file, `None` for the other commands
+fn wait_command(args: &[Vec<u8>]) -> Option<Vec<u8>> {
+    let (name, args) = args.split_first()?;
+    let name = String::from_utf8_lossy(name).to_uppercase();
+    Some(match (name.as_str(), integers(args).as_deref()) {
+        ("WAIT", Some([_, _])) => integer(0),
+        ("WAITAOF", Some([0, _, _])) => b"*2\r\n:0\r\n:0\r\n".to_vec(),
+        ("WAITAOF", Some([_, _, _])) => {
+            b"-ERR WAITAOF cannot be used when numlocal is set but appendonly is disabled.\r\n"
+                .to_vec()
+        }
+        ("WAIT", _) | ("WAITAOF", _) => {
+            b"-ERR value is not an integer or out of range\r\n".to_vec()
+        }
+        _ => return None,
+    })
+}
+
+/// Time the `WAIT` or `WAITAOF` command waits for the replicas, `None` if forever, as none
+/// acknowledges the writes
+fn wait_timeout(args: &[Vec<u8>]) -> Option<Option<Duration>> {
+    let (name, args) = args.split_first()?;
+    let name = String::from_utf8_lossy(name).to_uppercase();
+    let timeout = match (name.as_str(), integers(args)?.as_slice()) {
+        ("WAIT", [replicas, timeout]) | ("WAITAOF", [0, replicas, timeout]) if *replicas > 0 => {
+            *timeout
+        }
+        _ => return None,
+    };
+    Some(Some(Duration::from_millis(timeout)).filter(|_| timeout > 0))
+}
+
+/// Parse arguments which are all integers
+fn integers(args: &[Vec<u8>]) -> Option<Vec<u64>> {
+    args.iter()
+        .map(|arg| String::from_utf8_lossy(arg).parse().ok())
+        .collect()
+}
+
+/// Keys written by the command, `None` if it does not change the dataset
+fn written_keys(args: &[Vec<u8>]) -> Option<&[Vec<u8>]> {
+    let (name, args) = args.split_first()?;
+    if name.eq_ignore_ascii_case(b"SET") {
+        Some(&args[..1.min(args.len())])
+    } else if name.eq_ignore_ascii_case(b"DEL") || name.eq_ignore_ascii_case(b"UNLINK") {
+        Some(args)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,227 @@
+//! In-memory fake Redis server, to exercise the pool without a running Redis.
+
+mod emulation;
+
+use std::{
+    collections::HashMap,
+    io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use async_std::{
+    io::{prelude::BufReadExt, BufReader, ReadExt},
+    net::{TcpListener, TcpStream},
+    task,
+};
+
+use crate::{RedisConnectionManager, Ttl};
+use emulation::{serve, Server};
+
+/// Values & expiry of the keys
+type Store = Arc<Mutex<HashMap<Vec<u8>, (Vec<u8>, Option<Instant>)>>>;
+
+/// Manager of connections to an in-memory fake Redis server.
+///
+/// The fake server understands a minimal subset of the Redis commands: `PING`, `SET`
+/// (with the `EX` or `PX` option only), `GET`, `DEL`, `UNLINK`, `EXISTS` & `SCAN`
+/// (returning every matching key at once).
+///
+/// It also answers a few other commands (transactions, `CLIENT`, `ACL`, `CONFIG`,
+/// pub/sub...) to exercise the features of this crate in its examples: their emulation
+/// is partial, is not part of the API and may change in any release. The commands it
+/// does not know fail with an `ERR unknown command` error.
+///
+/// The server listens on a random local port and is run by the async-std runtime until
+/// the process exits. Each manager gets its own empty server.
+///
+/// Enabled with the `fake` feature, it is intended for tests.
+///
+/// ```rust
+/// use redis::AsyncCommands;
+/// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+///
+/// # #[async_std::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 5);
+/// let mut con = pool.get().await?;
+/// con.set::<_, _, ()>("key", "value").await?;
+/// let value: String = con.get("key").await?;
+/// assert_eq!(value, "value");
+/// # Ok(())
+/// # }
+/// ```
+pub struct FakeRedisConnectionManager;
+
+impl FakeRedisConnectionManager {
+    /// Start a fake server and create a manager of connections to it.
+    ///
+    /// See [`RedisConnectionManager::new`] for the parameters.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        check_on_recycle: bool,
+        connection_ttl: Option<Ttl>,
+    ) -> io::Result<RedisConnectionManager> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let listener = TcpListener::from(listener);
+        let server = Server::default();
+        task::spawn(async move {
+            let next_id = AtomicU64::new(1);
+            while let Ok((stream, _)) = listener.accept().await {
+                let id = next_id.fetch_add(1, Ordering::Relaxed);
+                task::spawn(serve(stream, id, server.clone()));
+            }
+        });
+        RedisConnectionManager::from_url(
+            &format!("redis://{}", addr),
+            check_on_recycle,
+            connection_ttl,
+        )
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+    }
+}
+
+/// Read a command sent as an array of bulk strings, `None` once the stream is closed
+async fn read_command(reader: &mut BufReader<TcpStream>) -> io::Result<Option<Vec<Vec<u8>>>> {
+    let count = match read_header(reader, b'*').await? {
+        Some(count) => count,
+        None => return Ok(None),
+    };
+    let mut args = Vec::with_capacity(count);
+    for _ in 0..count {
+        let len = read_header(reader, b'$')
+            .await?
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        let mut arg = vec![0; len + 2];
+        reader.read_exact(&mut arg).await?;
+        arg.truncate(len);
+        args.push(arg);
+    }
+    Ok(Some(args))
+}
+
+/// Read a `<prefix><number>\r\n` line
+async fn read_header(reader: &mut BufReader<TcpStream>, prefix: u8) -> io::Result<Option<usize>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).await? == 0 {
+        return Ok(None);
+    }
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid RESP request");
+    line.trim_end()
+        .strip_prefix(prefix as char)
+        .and_then(|number| number.parse().ok())
+        .map(Some)
+        .ok_or_else(invalid)
+}
+
+fn execute(store: &Store, args: &[Vec<u8>]) -> Vec<u8> {
+    let name = args
+        .first()
+        .map(|name| String::from_utf8_lossy(name).to_uppercase())
+        .unwrap_or_default();
+    let mut store = store.lock().unwrap();
+    let now = Instant::now();
+    store.retain(|_, (_, expires_at)| match expires_at {
+        Some(expires_at) => *expires_at > now,
+        None => true,
+    });
+    match (name.as_str(), &args[1.min(args.len())..]) {
+        ("PING", []) => b"+PONG\r\n".to_vec(),
+        ("PING", [message]) => bulk(message),
+        ("SET", [key, value]) => {
+            store.insert(key.clone(), (value.clone(), None));
+            b"+OK\r\n".to_vec()
+        }
+        ("SET", [key, value, unit, ttl])
+            if unit.eq_ignore_ascii_case(b"EX") || unit.eq_ignore_ascii_case(b"PX") =>
+        {
+            let ttl = match String::from_utf8_lossy(ttl).parse::<u64>() {
+                Ok(ttl) if ttl > 0 => ttl,
+                _ => return b"-ERR invalid expire time in 'set' command\r\n".to_vec(),
+            };
+            let ttl = if unit.eq_ignore_ascii_case(b"EX") {
+                Duration::from_secs(ttl)
+            } else {
+                Duration::from_millis(ttl)
+            };
+            store.insert(key.clone(), (value.clone(), Some(now + ttl)));
+            b"+OK\r\n".to_vec()
+        }
+        ("GET", [key]) => match store.get(key) {
+            Some((value, _)) => bulk(value),
+            None => b"$-1\r\n".to_vec(),
+        },
+        ("DEL", keys) | ("UNLINK", keys) if !keys.is_empty() => integer(
+            keys.iter()
+                .filter(|key| store.remove(*key).is_some())
+                .count(),
+        ),
+        ("EXISTS", keys) if !keys.is_empty() => {
+            integer(keys.iter().filter(|key| store.contains_key(*key)).count())
+        }
+        ("SCAN", [_cursor, options @ ..]) => {
+            let pattern = options
+                .chunks(2)
+                .find(|option| option[0].eq_ignore_ascii_case(b"MATCH") && option.len() == 2)
+                .map_or(&b"*"[..], |option| &option[1]);
+            let keys: Vec<_> = store
+                .keys()
+                .filter(|key| glob_match(pattern, key))
+                .collect();
+            let mut reply = b"*2\r\n".to_vec();
+            reply.extend(bulk(b"0"));
+            reply.extend(format!("*{}\r\n", keys.len()).into_bytes());
+            for key in keys {
+                reply.extend(bulk(key));
+            }
+            reply
+        }
+        ("PING", _)
+        | ("SET", _)
+        | ("GET", _)
+        | ("DEL", _)
+        | ("UNLINK", _)
+        | ("EXISTS", _)
+        | ("SCAN", _) => format!(
+            "-ERR wrong number of arguments for '{}' command\r\n",
+            name.to_lowercase()
+        )
+        .into_bytes(),
+        _ => format!("-ERR unknown command '{}'\r\n", name).into_bytes(),
+    }
+}
+
+/// Match `key` against a glob-style `pattern` made of `*`, `?` & `\` escapes
+fn glob_match(pattern: &[u8], key: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => key.is_empty(),
+        Some((b'*', rest)) => (0..=key.len()).any(|skip| glob_match(rest, &key[skip..])),
+        Some((b'?', rest)) => !key.is_empty() && glob_match(rest, &key[1..]),
+        Some((b'\\', [escaped, rest @ ..])) | Some((escaped, rest)) => {
+            key.first() == Some(escaped) && glob_match(rest, &key[1..])
+        }
+    }
+}
+
+fn bulk(value: &[u8]) -> Vec<u8> {
+    let mut reply = format!("${}\r\n", value.len()).into_bytes();
+    reply.extend_from_slice(value);
+    reply.extend_from_slice(b"\r\n");
+    reply
+}
+
+fn array(values: &[&[u8]]) -> Vec<u8> {
+    let mut reply = format!("*{}\r\n", values.len()).into_bytes();
+    for value in values {
+        reply.extend(bulk(value));
+    }
+    reply
+}
+
+fn integer(value: usize) -> Vec<u8> {
+    format!(":{}\r\n", value).into_bytes()
+}
@@ -0,0 +1,121 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex as SyncMutex,
+    },
+    time::{Duration, Instant},
+};
+
+use futures_util::lock::Mutex;
+
+use crate::{connection::ConnectionSettings, runtime};
+
+/// Server reachability checked on a connection dedicated to it, see
+/// [`RedisConnectionManager::with_shared_health_check`](crate::RedisConnectionManager::with_shared_health_check).
+pub(crate) struct SharedHealthCheck {
+    pub(crate) interval: Duration,
+    healthy: AtomicBool,
+    state: Mutex<HealthState>,
+}
+
+#[derive(Default)]
+struct HealthState {
+    con: Option<redis::aio::Connection>,
+    checked_at: Option<Instant>,
+}
+
+impl SharedHealthCheck {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            healthy: AtomicBool::new(true),
+            state: Mutex::new(HealthState::default()),
+        }
+    }
+
+    /// Result of the last check
+    pub(crate) fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    /// Tell whether the server is reachable, checking it again if the last check is older
+    /// than the interval. While a check is running, the result of the previous one is used.
+    pub(crate) async fn check(&self, settings: &ConnectionSettings) -> bool {
+        let mut state = match self.state.try_lock() {
+            Some(state) => state,
+            None => return self.is_healthy(),
+        };
+        if let Some(checked_at) = state.checked_at {
+            if checked_at.elapsed() < self.interval {
+                return self.is_healthy();
+            }
+        }
+        let con = state.con.take();
+        let ping = async move {
+            let mut con = match con {
+                Some(con) => con,
+                None => settings.connect(&settings.client).await?,
+            };
+            redis::cmd("PING").query_async::<_, ()>(&mut con).await?;
+            Ok::<_, redis::RedisError>(con)
+        };
+        // a dead server may not even reset the connection
+        let healthy = match runtime::timeout(self.interval, ping).await {
+            Ok(Ok(con)) => {
+                state.con = Some(con);
+                true
+            }
+            _ => false,
+        };
+        state.checked_at = Some(Instant::now());
+        self.healthy.store(healthy, Ordering::Relaxed);
+        healthy
+    }
+}
+
+/// Number of consecutive failed creations after which the pool is reported unhealthy
+pub(crate) const UNHEALTHY_AFTER_FAILURES: u32 = 3;
+
+/// Transitions of the pool between being able to create connections and not, see
+/// [`RedisConnectionManager::with_health_transition_callback`](crate::RedisConnectionManager::with_health_transition_callback).
+pub(crate) struct HealthTransitions {
+    callback: Box<dyn Fn(bool) + Send + Sync>,
+    state: SyncMutex<CreateState>,
+}
+
+struct CreateState {
+    healthy: bool,
+    /// Number of consecutive failed creations
+    failures: u32,
+}
+
+impl HealthTransitions {
+    pub(crate) fn new(callback: Box<dyn Fn(bool) + Send + Sync>) -> Self {
+        Self {
+            callback,
+            state: SyncMutex::new(CreateState {
+                healthy: true,
+                failures: 0,
+            }),
+        }
+    }
+
+    /// Record the outcome of a creation, calling the callback if the state changes
+    pub(crate) fn record(&self, created: bool) {
+        // the callback is called with the lock held so transitions are reported in order
+        let mut state = self.state.lock().unwrap();
+        if created {
+            state.failures = 0;
+            if !state.healthy {
+                state.healthy = true;
+                (self.callback)(true);
+            }
+        } else {
+            state.failures = state.failures.saturating_add(1);
+            if state.healthy && state.failures >= UNHEALTHY_AFTER_FAILURES {
+                state.healthy = false;
+                (self.callback)(false);
+            }
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use redis::{RedisFuture, RedisResult};
+
+use crate::RedisConnection;
+
+/// A hook run by the manager at some point of the lifecycle of a connection.
+///
+/// Hooks are asynchronous closures taking the connection and returning a
+/// `redis::RedisFuture`:
+///
+/// ```rust
+/// # #[cfg(feature = "fake")]
+/// # async_std::task::block_on(async {
+/// use std::sync::{Arc, Mutex};
+/// use redis::AsyncCommands;
+/// use redis_async_pool::{FakeRedisConnectionManager, Hook, RedisPool};
+///
+/// let events = Arc::new(Mutex::new(Vec::new()));
+/// // record the lifecycle point and the value of `key` when the hook runs
+/// let hook = |event: &'static str| -> Hook {
+///     let events = events.clone();
+///     Box::new(move |con| {
+///         let events = events.clone();
+///         Box::pin(async move {
+///             let value: Option<String> = con.get("key").await?;
+///             events.lock().unwrap().push((event, value));
+///             Ok(())
+///         })
+///     })
+/// };
+/// let manager = FakeRedisConnectionManager::new(true, None)?
+///     .with_post_create(hook("post_create"))
+///     .with_pre_recycle(hook("pre_recycle"))
+///     .with_post_recycle(hook("post_recycle"));
+/// let pool = RedisPool::new(manager, 1);
+///
+/// pool.get().await?.set::<_, _, ()>("key", "value").await?;
+/// pool.get().await?;
+/// let value = || Some("value".to_string());
+/// assert_eq!(
+///     *events.lock().unwrap(),
+///     [("post_create", None), ("pre_recycle", value()), ("post_recycle", value())]
+/// );
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// # })
+/// # .unwrap();
+/// ```
+pub type Hook =
+    Box<dyn for<'a> Fn(&'a mut RedisConnection) -> RedisFuture<'a, ()> + Send + Sync + 'static>;
+
+/// Hooks registered on a manager
+#[derive(Default)]
+pub(crate) struct Hooks {
+    pub(crate) post_create: Vec<Hook>,
+    pub(crate) pre_recycle: Vec<Hook>,
+    pub(crate) post_recycle: Vec<Hook>,
+    pub(crate) on_checkout: Vec<Hook>,
+}
+
+/// Run `hooks` in registration order, stopping at the first failure.
+pub(crate) async fn run(hooks: &[Hook], conn: &mut RedisConnection) -> RedisResult<()> {
+    for hook in hooks {
+        hook(conn).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,65 @@
+//! Parsing of the `INFO` & `CLIENT INFO` commands output.
+
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+};
+
+/// Get the value of `field` in the output of the `INFO` command.
+pub(crate) fn field<'a>(info: &'a str, field: &str) -> Option<&'a str> {
+    info.lines().find_map(|line| {
+        let mut parts = line.trim_end().splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some(name), Some(value)) if name == field => Some(value),
+            _ => None,
+        }
+    })
+}
+
+/// Get the value of `field` in the output of the `CLIENT INFO` command.
+pub(crate) fn client_field<'a>(info: &'a str, field: &str) -> Option<&'a str> {
+    info.split_whitespace().find_map(|pair| {
+        let mut parts = pair.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(name), Some(value)) if name == field => Some(value),
+            _ => None,
+        }
+    })
+}
+
+/// Parse an `ip:port` address as formatted by Redis, IPv6 addresses being not bracketed.
+pub(crate) fn socket_addr(addr: &str) -> Option<SocketAddr> {
+    let (ip, port) = addr.rsplit_once(':')?;
+    let ip: IpAddr = ip
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()?;
+    Some(SocketAddr::new(ip, port.parse().ok()?))
+}
+
+/// Version of a Redis server, see [`RedisConnection::server_version`](crate::RedisConnection::server_version).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ServerVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl ServerVersion {
+    /// Parse a `major.minor.patch` version as found in the `redis_version` field of `INFO`.
+    pub(crate) fn parse(version: &str) -> Option<Self> {
+        let mut parts = version.trim().splitn(3, '.').map(|part| part.parse().ok());
+        Some(Self {
+            major: parts.next()??,
+            minor: parts.next().unwrap_or(Some(0))?,
+            patch: parts.next().unwrap_or(Some(0))?,
+        })
+    }
+}
+
+impl fmt::Display for ServerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
@@ -0,0 +1,134 @@
+use futures_util::stream::{Stream, StreamExt};
+use redis::{Msg, RedisResult};
+
+use crate::RedisPool;
+
+/// Keyspace notification channels of the events forwarded by [`RedisPool::key_events`]
+const EVENT_CHANNELS: &[&str] = &["__keyevent@*__:expired", "__keyevent@*__:evicted"];
+
+/// What happened to a key, see [`KeyEvent`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyEventKind {
+    /// The key expired (ttl)
+    Expired,
+    /// The key was evicted to free memory (`maxmemory`)
+    Evicted,
+}
+
+/// An expiry or eviction notified by the server, see [`RedisPool::key_events`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyEvent {
+    /// What happened to the key
+    pub kind: KeyEventKind,
+    /// The database of the key
+    pub db: i64,
+    /// The name of the key
+    pub key: Vec<u8>,
+}
+
+impl KeyEvent {
+    /// Parse a message received on a `__keyevent@<db>__:<event>` channel
+    fn parse(msg: &Msg) -> Option<Self> {
+        let (db, event) = msg
+            .get_channel_name()
+            .strip_prefix("__keyevent@")?
+            .split_once("__:")?;
+        let kind = match event {
+            "expired" => KeyEventKind::Expired,
+            "evicted" => KeyEventKind::Evicted,
+            _ => return None,
+        };
+        Some(Self {
+            kind,
+            db: db.parse().ok()?,
+            key: msg.get_payload_bytes().to_vec(),
+        })
+    }
+}
+
+impl RedisPool {
+    /// Subscribe to the expiry and eviction events of the keys of every database.
+    ///
+    /// The events are received on a dedicated connection, opened out of the pool and
+    /// closed once the stream is dropped; the stream ends if the connection fails.
+    ///
+    /// The server only notifies those events when enabled by its `notify-keyspace-events`
+    /// parameter: the flags needed (`Exe`) are added to it with `CONFIG SET` if missing.
+    /// As this parameter is global, this enables the notifications for every client.
+    /// If `CONFIG` is not available (e.g. renamed by a managed service), the parameter
+    /// must be set beforehand.
+    ///
+    /// Notifications are not reliable: events sent while the connection is down are lost,
+    /// and a key expires when accessed or found by the background expiry, possibly well
+    /// after its ttl elapsed.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::Duration;
+    /// use futures_util::{future, stream::StreamExt};
+    /// use redis_async_pool::{FakeRedisConnectionManager, KeyEvent, KeyEventKind, RedisPool};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 5);
+    /// let mut events = Box::pin(pool.key_events().await?);
+    /// let mut con = pool.get().await?;
+    /// redis::cmd("SET")
+    ///     .arg("session")
+    ///     .arg("token")
+    ///     .arg("PX")
+    ///     .arg(50)
+    ///     .query_async::<_, ()>(&mut *con)
+    ///     .await?;
+    ///
+    /// let timeout = Box::pin(async_std::task::sleep(Duration::from_secs(5)));
+    /// let event = match future::select(events.next(), timeout).await {
+    ///     future::Either::Left((event, _)) => event,
+    ///     future::Either::Right(_) => panic!("no expiry notified"),
+    /// };
+    /// assert_eq!(
+    ///     event,
+    ///     Some(KeyEvent {
+    ///         kind: KeyEventKind::Expired,
+    ///         db: 0,
+    ///         key: b"session".to_vec(),
+    ///     })
+    /// );
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn key_events(&self) -> RedisResult<impl Stream<Item = KeyEvent>> {
+        let client = self.shared.current().client().clone();
+        let mut con = client.get_async_connection().await?;
+        let config = redis::cmd("CONFIG")
+            .arg("GET")
+            .arg("notify-keyspace-events")
+            .query_async::<_, (String, String)>(&mut con)
+            .await;
+        if let Ok((_, current)) = config {
+            let mut flags = current.clone();
+            for flag in &['E', 'x', 'e'] {
+                // `A` stands for every event type, keyevent (`E`) excepted
+                let all_events = *flag != 'E' && flags.contains('A');
+                if !all_events && !flags.contains(*flag) {
+                    flags.push(*flag);
+                }
+            }
+            if flags != current {
+                redis::cmd("CONFIG")
+                    .arg("SET")
+                    .arg("notify-keyspace-events")
+                    .arg(flags)
+                    .query_async::<_, ()>(&mut con)
+                    .await?;
+            }
+        }
+        let mut pubsub = con.into_pubsub();
+        for channel in EVENT_CHANNELS {
+            pubsub.psubscribe(*channel).await?;
+        }
+        Ok(pubsub
+            .into_on_message()
+            .filter_map(|msg| async move { KeyEvent::parse(&msg) }))
+    }
+}
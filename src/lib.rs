@@ -16,12 +16,43 @@
 //! - optional check of connection on recycle
 //! - optional ttl on connections
 //!
+//! ## Cargo features
+//!
+//! - `tokio-comp` (default): timers of the tokio runtime
+//! - `async-std-comp` (default): timers of the async-std runtime
+//!
+//! At least one runtime feature must be enabled, see [`Runtime`].
+//!
+//! - `fake`: in-memory fake server for tests, see `FakeRedisConnectionManager`
+//!
+//! - `log`: log connection lifecycle events (creation & failures at the debug & warn
+//!   levels, recycling at the trace level, discarding & closing at the debug level) and
+//!   slow commands through the [log crate](https://crates.io/crates/log), see
+//!   [Logging](#logging)
+//!
+//! - `tracing`: trace the acquisitions of connections, with the source location of the
+//!   callers, through the [tracing crate](https://crates.io/crates/tracing), see
+//!   `RedisPool::get`
+//!
+//! - `chaos`: inject latency and faults in the creation of connections and in the
+//!   commands, see `ChaosConfig`
+//!
+//! - `local-cache`: serve the values last read with `RedisPool::cache_get` while the
+//!   server is unavailable, see `RedisPoolBuilder::with_local_cache`
+//!
+//! - `serde`: serialize & deserialize the configuration of a manager, see `ManagerConfig`
+//!
+//! - `prometheus`: export the pool statistics to a [Prometheus](https://crates.io/crates/prometheus)
+//!   registry, see `RedisPool::register_prometheus`
+//!
 //! ## Example
 //!
-//! ```rust
-//! use redis::AsyncCommands;
+//! ```rust,no_run
+//! use redis::{aio::ConnectionLike, AsyncCommands, Cmd, Pipeline, RedisFuture, Value};
 //! use redis_async_pool::{RedisConnectionManager, RedisPool};
 //!
+//! # #[async_std::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
 //! // Create a pool of maximum 5 connections, checked on reuse without ttl.
 //!
 //! let pool = RedisPool::new(
@@ -31,34 +62,196 @@
 //!
 //! // get a connection with the get() async method and use it as regular redis connection
 //! let mut con = pool.get().await?;
-//! con.set(b"key", b"value").await?;
+//! con.set::<_, _, ()>(b"key", b"value").await?;
 //! let value: Vec<u8> = con.get(b"key").await?;
 //! assert_eq!(value, b"value");
+//! # Ok(())
+//! # }
 //! ```
 //!
 //! You can set a ttl for each created connection by the pool,
 //! this helps avoiding huge memory consumption when keeping many connections
 //! open during a too long time.
+//!
+//! ## Logging
+//!
+//! With the `log` feature, the lifecycle of the connections is logged through the
+//! logger of the application:
+//!
+//! ```rust
+//! # #[cfg(all(feature = "fake", feature = "log"))]
+//! # async_std::task::block_on(async {
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+//!
+//! // count the lifecycle events logged
+//! static EVENTS: [AtomicUsize; 3] = [AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0)];
+//! struct Counter;
+//! impl log::Log for Counter {
+//!     fn enabled(&self, _: &log::Metadata) -> bool {
+//!         true
+//!     }
+//!     fn log(&self, record: &log::Record) {
+//!         let message = record.args().to_string();
+//!         let events = ["created redis connection", "discarding redis connection", "closed redis connection"];
+//!         if let Some(event) = events.iter().position(|event| message.starts_with(event)) {
+//!             assert_eq!(record.level(), log::Level::Debug);
+//!             EVENTS[event].fetch_add(1, Ordering::Relaxed);
+//!         }
+//!     }
+//!     fn flush(&self) {}
+//! }
+//! log::set_logger(&Counter).unwrap();
+//! log::set_max_level(log::LevelFilter::Debug);
+//! let events = || -> Vec<_> { EVENTS.iter().map(|count| count.load(Ordering::Relaxed)).collect() };
+//!
+//! let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 1);
+//! drop(pool.get().await?);
+//! assert_eq!(events(), [1, 0, 0]);
+//! // the connection is discarded and replaced on its next checkout
+//! pool.roll();
+//! drop(pool.get().await?);
+//! assert_eq!(events(), [2, 1, 1]);
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! # })
+//! # .unwrap();
+//! ```
 
 use std::{
-    ops::{Deref, DerefMut},
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
     time::{Duration, Instant},
 };
 
 use async_trait::async_trait;
 use deadpool::managed::RecycleError;
 use rand::Rng;
-use redis::AsyncCommands;
+use redis::{AsyncCommands, ConnectionAddr, IntoConnectionInfo};
 
 pub use deadpool;
 
-/// The redis connection pool
-///
-/// Use the `new` method to create a new pool. You can find
-/// more information in the documentation of the `deadpool` crate.
-pub type RedisPool = deadpool::managed::Pool<RedisConnection, redis::RedisError>;
+mod acl;
+mod affinity;
+mod batch;
+mod budget;
+mod builder;
+#[cfg(feature = "local-cache")]
+mod cache;
+mod capacity;
+#[cfg(feature = "chaos")]
+mod chaos;
+mod coalesce;
+mod commands;
+mod config;
+mod connection;
+mod deadline;
+mod errors;
+#[cfg(feature = "fake")]
+mod fake;
+mod health;
+mod hooks;
+mod info;
+mod keyspace;
+mod limits;
+#[cfg(feature = "prometheus")]
+mod metrics;
+mod multiplexed;
+mod poison;
+mod pool;
+mod priority;
+mod rate;
+mod replication;
+mod runtime;
+mod scripts;
+mod sentinel;
+mod session;
+mod split;
+mod streams;
+mod typed;
+
+pub use acl::AclUser;
+pub use batch::BatchedConnection;
+pub use budget::ConnectionBudget;
+pub use builder::{BuilderError, RedisPoolBuilder};
+#[cfg(feature = "chaos")]
+pub use chaos::{is_injected_fault, ChaosConfig};
+pub use config::{Endpoint, ManagerConfig};
+pub use connection::{ConnectTimings, ConnectionMetadata, RedisConnection};
+pub use deadline::DeadlineConnection;
+pub use errors::{
+    explain_cluster_redirect, is_budget_exhausted, is_out_of_memory, is_pool_poisoned,
+    is_reply_too_large, is_server_loading, is_server_paused, is_transient_server_error,
+};
+#[cfg(feature = "fake")]
+pub use fake::FakeRedisConnectionManager;
+pub use hooks::Hook;
+pub use info::ServerVersion;
+pub use keyspace::{KeyEvent, KeyEventKind};
+pub use limits::{ClientLimits, OutputBufferLimit};
+#[cfg(feature = "prometheus")]
+pub use metrics::encode_prometheus;
+pub use multiplexed::SharedConnection;
+pub use pool::{
+    AcquireStats, ConnectionInfo, ConnectionState, MigrationReport, PoolStats, PooledConnection,
+    RedisPool, WarmUpReport,
+};
+pub use priority::Priority;
+pub use runtime::Runtime;
+pub use sentinel::Failover;
+pub use session::Session;
+pub use split::{SplitConnection, SplitPool};
+pub use streams::{StreamAcker, StreamConsumer};
+pub use typed::{Codec, TypedConnection};
+
+use connection::{ConnectionSettings, Tracker, HISTORY_LEN};
+use health::{HealthTransitions, SharedHealthCheck};
+use hooks::Hooks;
+use poison::Poison;
+use rate::RateLimiter;
+use replication::ReplicaReadiness;
 
 /// Time to live of a connection
+///
+/// A ttl too large to be represented from now (e.g. `Duration::MAX`) is
+/// handled as no ttl at all: the connection never expires. Likewise, the timeouts of
+/// a pool too large to be represented never elapse.
+///
+/// ```rust
+/// # #[cfg(feature = "fake")]
+/// # async_std::task::block_on(async {
+/// use std::time::Duration;
+/// use redis::AsyncCommands;
+/// use redis_async_pool::{FakeRedisConnectionManager, RedisPool, Ttl};
+///
+/// let manager = FakeRedisConnectionManager::new(true, Some(Ttl::Simple(Duration::MAX)))?;
+/// let pool = RedisPool::builder(manager)
+///     .max_size(1)
+///     .with_wait_timeout(Duration::MAX)
+///     .with_create_timeout(Duration::MAX)
+///     .with_recycle_timeout(Duration::MAX)
+///     .build()?;
+/// let mut con = pool.get().await?;
+/// assert_eq!(con.expires_at(), None);
+/// con.set::<_, _, ()>("key", "value").await?;
+/// drop(con);
+/// // recycled
+/// let value: String = pool.get().await?.get("key").await?;
+/// assert_eq!(value, "value");
+/// let value: Option<String> = pool
+///     .execute_within(Duration::MAX, |con| {
+///         Box::pin(async move { con.get("key").await })
+///     })
+///     .await?;
+/// assert_eq!(value.as_deref(), Some("value"));
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// # })
+/// # .unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Ttl {
     /// Connection will expire after the given duration
     Simple(Duration),
@@ -74,14 +267,259 @@ pub enum Ttl {
     /// Enabling Once ttl means the pool will not keep any connection opened.
     /// So it won't really act as a pool of connection.
     Once,
+    /// Connections created before the given instant expire at that instant, e.g. to
+    /// renew every connection before a scheduled maintenance.
+    ///
+    /// Connections created once the instant has passed never expire. An instant has no
+    /// meaning out of the process: this ttl can not be serialized (`serde` feature).
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::{Duration, Instant};
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool, Ttl};
+    ///
+    /// let deadline = Instant::now() + Duration::from_millis(100);
+    /// let manager = FakeRedisConnectionManager::new(true, Some(Ttl::Deadline(deadline)))?;
+    /// let pool = RedisPool::new(manager, 1);
+    /// let con = pool.get().await?;
+    /// let created = con.id();
+    /// assert_eq!(con.expires_at(), Some(deadline));
+    /// drop(con);
+    /// // reused until the deadline
+    /// assert_eq!(pool.get().await?.id(), created);
+    ///
+    /// async_std::task::sleep(Duration::from_millis(150)).await;
+    /// // expired on recycle, replaced by a connection created past the deadline
+    /// let con = pool.get().await?;
+    /// assert_ne!(con.id(), created);
+    /// assert_eq!(con.expires_at(), None);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Deadline(Instant),
+    /// Connections expire after `base` at first, their ttl being multiplied by `factor`,
+    /// up to `max`, each time they are recycled successfully: long-lived healthy
+    /// connections are renewed less and less often.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::Duration;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool, Ttl};
+    ///
+    /// let ttl = Ttl::Adaptive {
+    ///     base: Duration::from_secs(10),
+    ///     max: Duration::from_secs(60),
+    ///     factor: 2,
+    /// };
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, Some(ttl))?, 1);
+    /// let mut expiries = Vec::new();
+    /// for _ in 0..5 {
+    ///     expiries.push(pool.get().await?.expires_at().unwrap());
+    /// }
+    /// let growths: Vec<_> = expiries.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    /// // the ttl grows from 10s to 20s, 40s, then 60s at most
+    /// let secs = Duration::from_secs;
+    /// assert_eq!(growths, [secs(10), secs(20), secs(20), secs(0)]);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    Adaptive {
+        base: Duration,
+        max: Duration,
+        factor: u32,
+    },
+}
+
+/// Point in time the ttl of a connection is measured from, see
+/// [`RedisConnectionManager::with_ttl_basis`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TtlBasis {
+    /// The connection expires once its ttl elapsed since its creation, however often it
+    /// is used
+    SinceCreation,
+    /// The connection expires once its ttl elapsed since it was last handed out by the
+    /// pool: only connections left idle for longer than their ttl expire
+    SinceLastUse,
+}
+
+/// When the connection check is run before a connection reuse
+///
+/// Only relevant when the manager is created with `check_on_recycle`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RecyclePolicy {
+    /// Check the connection each time it is handed out by the pool
+    Always,
+    /// Check the connection only if it has not been checked (or created) during
+    /// the given duration, saving a round-trip on most checkouts.
+    Interval(Duration),
+    /// Check the connection only if its last command failed or was issued more than the
+    /// given duration ago: a recent successful command shows the connection is fine.
+    AfterFailure(Duration),
+}
+
+/// Decision taken when recycling a connection, see
+/// [`RedisConnectionManager::with_recycle_observer`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecycleOutcome {
+    /// The connection is handed out
+    Kept,
+    /// The connection is dropped for the given reason, e.g. `Connection expired`
+    Discarded(String),
+}
+
+/// Decision taken from the metadata of a connection being recycled, see
+/// [`RedisConnectionManager::should_recycle`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecycleDecision {
+    /// The connection can be reused, once checked if `check_on_recycle` is enabled
+    Keep,
+    /// The connection must be reset before being reused
+    Reset,
+    /// The connection must be dropped for the given reason
+    Discard(String),
+}
+
+/// Callback observing the recycle decisions, see
+/// [`RedisConnectionManager::with_recycle_observer`]
+pub(crate) type RecycleObserver = Box<dyn Fn(&RedisConnection, RecycleOutcome) + Send + Sync>;
+
+/// Function opening connections, see [`RedisConnectionManager::with_connector`]
+pub type Connector =
+    Box<dyn Fn() -> redis::RedisFuture<'static, redis::aio::Connection> + Send + Sync>;
+
+/// Recycle error message of the connections whose ttl elapsed
+pub(crate) const CONNECTION_EXPIRED: &str = "Connection expired";
+
+/// What happens when a connection can not be created
+///
+/// ```rust
+/// # #[cfg(feature = "fake")]
+/// # async_std::task::block_on(async {
+/// use std::{
+///     sync::{
+///         atomic::{AtomicUsize, Ordering},
+///         Arc,
+///     },
+///     time::Duration,
+/// };
+/// use redis_async_pool::{
+///     CreateFailurePolicy, FakeRedisConnectionManager, RedisConnectionManager, RedisPool, Ttl,
+/// };
+///
+/// let fake = RedisPool::new(FakeRedisConnectionManager::new(true, None)?.with_peer_addr(), 1);
+/// let client = redis::Client::open(format!("redis://{}", fake.get().await?.peer_addr().unwrap()))?;
+/// // a server refusing the given number of next connections
+/// let (attempts, refused) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+/// let pool = |policy, ttl| {
+///     let (client, attempts, refused) = (client.clone(), attempts.clone(), refused.clone());
+///     let manager = RedisConnectionManager::new(client.clone(), true, ttl)
+///         .with_create_failure_policy(policy)
+///         .with_connector(move || {
+///             attempts.fetch_add(1, Ordering::Relaxed);
+///             let refuse = refused.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+///             let client = client.clone();
+///             Box::pin(async move {
+///                 match refuse {
+///                     Ok(_) => Err(std::io::Error::from(std::io::ErrorKind::ConnectionRefused).into()),
+///                     Err(_) => client.get_async_connection().await,
+///                 }
+///             })
+///         });
+///     RedisPool::new(manager, 1)
+/// };
+///
+/// refused.store(2, Ordering::Relaxed);
+/// assert!(pool(CreateFailurePolicy::FailFast, None).get().await.is_err());
+/// assert_eq!(attempts.swap(0, Ordering::Relaxed), 1);
+///
+/// refused.store(2, Ordering::Relaxed);
+/// let retry = CreateFailurePolicy::Retry {
+///     retries: 2,
+///     backoff: Duration::from_millis(10),
+/// };
+/// pool(retry, None).get().await?;
+/// assert_eq!(attempts.swap(0, Ordering::Relaxed), 3);
+///
+/// // the expired connection is handed out while it can not be renewed
+/// let pool = pool(CreateFailurePolicy::UseStale, Some(Ttl::Simple(Duration::from_millis(50))));
+/// let stale = pool.get().await?.id();
+/// async_std::task::sleep(Duration::from_millis(50)).await;
+/// refused.store(1, Ordering::Relaxed);
+/// assert_eq!(pool.get().await?.id(), stale);
+/// assert_ne!(pool.get().await?.id(), stale);
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// # })
+/// # .unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CreateFailurePolicy {
+    /// Return the creation error to the caller
+    FailFast,
+    /// Try again up to `retries` times, waiting `backoff` before the first retry and
+    /// twice as long before each next one, then return the last error
+    Retry {
+        /// Number of retries after the first attempt
+        retries: u32,
+        /// Wait before the first retry
+        backoff: Duration,
+    },
+    /// Renew expired connections (ttl) on recycle and keep handing out the expired
+    /// connection while a new one can not be created; the creation error is returned
+    /// when there is no such connection.
+    UseStale,
 }
 
 /// Manages creation and destruction of redis connections.
 ///
 pub struct RedisConnectionManager {
-    client: redis::Client,
     check_on_recycle: bool,
+    recycle_policy: RecyclePolicy,
+    create_failure_policy: CreateFailurePolicy,
     connection_ttl: Option<Ttl>,
+    ttl_basis: TtlBasis,
+    ttl_jitter: Option<Duration>,
+    replica_readiness: Option<ReplicaReadiness>,
+    reset_after_uses: Option<u64>,
+    shared_health_check: Option<SharedHealthCheck>,
+    /// Whether the address of the server is queried on creation
+    peer_addr: bool,
+    /// Whether the server side identifier is queried on creation
+    client_id: bool,
+    /// Whether the server version is queried on creation
+    server_version: bool,
+    /// Whether the library name & version are reported on creation
+    lib_info: bool,
+    /// Limits applied on creation
+    client_limits: Option<ClientLimits>,
+    /// User the connections authenticate as on creation
+    acl_user: Option<AclUser>,
+    /// Limit of the connections open at once, shared with other managers
+    connection_budget: Option<ConnectionBudget>,
+    /// Whether a dropped connection is reopened on recycle instead of being discarded
+    reconnect_in_place: bool,
+    /// Url of the main endpoint, when created with `from_url`
+    url: Option<String>,
+    /// Username & password to authenticate again after a reset, when known
+    auth: Option<(Option<String>, String)>,
+    /// Host name, port & connection info of the main endpoint, resolved ahead of the
+    /// connection to time the resolution, when known and connected to without TLS
+    host: Option<(String, u16, redis::ConnectionInfo)>,
+    hooks: Hooks,
+    pub(crate) recycle_observer: Option<RecycleObserver>,
+    pub(crate) health_transitions: Option<HealthTransitions>,
+    connector: Option<Connector>,
+    /// Index of the last endpoint a connection was created with, tried first
+    last_endpoint: AtomicUsize,
+    /// Settings shared with every created connection
+    settings: Arc<ConnectionSettings>,
 }
 
 impl RedisConnectionManager {
@@ -89,95 +527,1852 @@ impl RedisConnectionManager {
     ///
     /// If `check_on_recycle` is true, before each connection reuse, an `exists` command
     /// is issued, if it fails to complete, the connection is dropped and a fresh connection
-    /// is created.
+    /// is created. A server loading its dataset (`LOADING` error) does not fail the check.
     ///
     /// If `connection_ttl` is set, the connection will be recreated after the given duration.
     pub fn new(client: redis::Client, check_on_recycle: bool, connection_ttl: Option<Ttl>) -> Self {
         Self {
-            client,
             check_on_recycle,
+            recycle_policy: RecyclePolicy::Always,
+            create_failure_policy: CreateFailurePolicy::FailFast,
             connection_ttl,
+            ttl_basis: TtlBasis::SinceCreation,
+            ttl_jitter: None,
+            replica_readiness: None,
+            reset_after_uses: None,
+            shared_health_check: None,
+            peer_addr: false,
+            client_id: false,
+            server_version: false,
+            lib_info: false,
+            client_limits: None,
+            acl_user: None,
+            connection_budget: None,
+            reconnect_in_place: false,
+            url: None,
+            auth: None,
+            host: None,
+            hooks: Hooks::default(),
+            recycle_observer: None,
+            health_transitions: None,
+            connector: None,
+            last_endpoint: AtomicUsize::new(0),
+            settings: Arc::new(ConnectionSettings::new(client)),
         }
     }
+
+    /// Create a new connection manager for the Redis server at `url`.
+    ///
+    /// The url is validated right away so a misconfiguration is reported at startup
+    /// rather than on the first `get()`. See [`new`](Self::new) for the other parameters.
+    ///
+    /// ```rust
+    /// use redis_async_pool::RedisConnectionManager;
+    ///
+    /// assert!(RedisConnectionManager::from_url("redis://localhost:6379", true, None).is_ok());
+    /// assert!(RedisConnectionManager::from_url("localhost:6379", true, None).is_err());
+    /// ```
+    pub fn from_url(
+        url: &str,
+        check_on_recycle: bool,
+        connection_ttl: Option<Ttl>,
+    ) -> redis::RedisResult<Self> {
+        let info = url.into_connection_info()?;
+        let auth = info
+            .passwd
+            .clone()
+            .map(|passwd| (info.username.clone(), passwd));
+        let host = match &*info.addr {
+            // TLS connections verify the name of the host, not its addresses
+            ConnectionAddr::Tcp(host, port) if host.parse::<IpAddr>().is_err() => {
+                Some((host.clone(), *port, info.clone()))
+            }
+            _ => None,
+        };
+        let mut manager = Self::new(redis::Client::open(info)?, check_on_recycle, connection_ttl);
+        manager.url = Some(url.to_string());
+        manager.auth = auth;
+        manager.host = host;
+        Ok(manager)
+    }
+
+    /// Add a random duration between 0 and `jitter` to the ttl of each connection.
+    ///
+    /// Connections created at the same time (e.g. when the pool warms up) have
+    /// the same expiry with a `Ttl::Simple` ttl, leading to a reconnection storm
+    /// when they all expire. The jitter staggers their expiry. It applies to any
+    /// ttl except `Ttl::Once`.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::{Duration, Instant};
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool, Ttl};
+    ///
+    /// let ttl = Duration::from_secs(60);
+    /// let manager = FakeRedisConnectionManager::new(true, Some(Ttl::Simple(ttl)))?
+    ///     .with_ttl_jitter(Duration::from_secs(30));
+    /// let pool = RedisPool::new(manager, 10);
+    /// let started = Instant::now();
+    /// let connections = futures_util::future::try_join_all((0..10).map(|_| pool.get())).await?;
+    /// let created = started.elapsed();
+    ///
+    /// let mut ttls: Vec<_> = connections
+    ///     .iter()
+    ///     .map(|con| con.expires_at().unwrap() - started)
+    ///     .collect();
+    /// ttls.sort();
+    /// assert!(ttls[0] >= ttl && ttls[9] <= ttl + Duration::from_secs(30) + created);
+    /// // the connections created at once do not expire at once
+    /// assert!(ttls[9] - ttls[0] > Duration::from_secs(1));
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_ttl_jitter(mut self, jitter: Duration) -> Self {
+        self.ttl_jitter = Some(jitter);
+        self
+    }
+
+    /// Set the point in time the ttl of the connections is measured from, defaults to
+    /// `TtlBasis::SinceCreation`.
+    ///
+    /// With `TtlBasis::SinceLastUse`, the expiry of a connection is computed again each
+    /// time it is handed out by the pool, after its recycle: a connection used more often
+    /// than its ttl never expires. This only makes a difference with `Ttl::Simple`,
+    /// `Ttl::Fuzzy` & `Ttl::Adaptive` ttls.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::Duration;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool, Ttl, TtlBasis};
+    ///
+    /// let ttl = Some(Ttl::Simple(Duration::from_millis(200)));
+    /// let manager = FakeRedisConnectionManager::new(true, ttl)?;
+    /// let pool = RedisPool::new(manager.with_ttl_basis(TtlBasis::SinceLastUse), 1);
+    ///
+    /// let id = pool.get().await?.id();
+    /// // used every 100ms, the connection outlives its ttl
+    /// for _ in 0..5 {
+    ///     async_std::task::sleep(Duration::from_millis(100)).await;
+    ///     assert_eq!(pool.get().await?.id(), id);
+    /// }
+    /// // left idle for longer than its ttl, it expires
+    /// async_std::task::sleep(Duration::from_millis(300)).await;
+    /// assert_ne!(pool.get().await?.id(), id);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_ttl_basis(mut self, basis: TtlBasis) -> Self {
+        self.ttl_basis = basis;
+        self
+    }
+
+    /// Issue a `RESET` on recycle once `uses` commands were sent on a connection, instead
+    /// of recreating it, to clear its state (selected database, watched keys, client name...)
+    /// while keeping the socket.
+    ///
+    /// The database is selected again, the [client limits](Self::with_client_limits) are
+    /// applied again and the post create hooks are run again after the reset. `RESET` also deauthenticates the connection: the credentials are only known
+    /// to managers created with [`from_url`](Self::from_url) or with an
+    /// [ACL user](Self::with_acl_user), for other managers the connection is dropped if
+    /// the server requires authentication. A pipeline counts
+    /// as a single command. `RESET` requires Redis 6.2.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::sync::{Arc, Mutex};
+    /// use redis_async_pool::{ClientLimits, FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let sent = Arc::new(Mutex::new(Vec::new()));
+    /// let recorded = sent.clone();
+    /// let manager = FakeRedisConnectionManager::new(false, None)?
+    ///     .with_reset_after_uses(2)
+    ///     .with_audit(move |command, _| recorded.lock().unwrap().push(command.to_string()));
+    /// let pool = RedisPool::new(manager, 1);
+    ///
+    /// let id = pool.get().await?.id();
+    /// for _ in 0..3 {
+    ///     let mut con = pool.get().await?;
+    ///     redis::cmd("GET").arg("key").query_async::<_, ()>(&mut *con).await?;
+    ///     // the socket is kept
+    ///     assert_eq!(con.id(), id);
+    /// }
+    /// // reset after 2 commands, then checked to be usable without authentication
+    /// assert_eq!(*sent.lock().unwrap(), ["GET", "GET", "RESET", "PING", "GET"]);
+    ///
+    /// // the client limits cleared by `RESET` are applied again
+    /// let sent = Arc::new(Mutex::new(Vec::new()));
+    /// let recorded = sent.clone();
+    /// let manager = FakeRedisConnectionManager::new(false, None)?
+    ///     .with_reset_after_uses(1)
+    ///     .with_client_limits(ClientLimits { no_evict: true, ..Default::default() })
+    ///     .with_audit(move |command, _| recorded.lock().unwrap().push(command.to_string()));
+    /// let pool = RedisPool::new(manager, 1);
+    /// for _ in 0..2 {
+    ///     let mut con = pool.get().await?;
+    ///     redis::cmd("GET").arg("key").query_async::<_, ()>(&mut *con).await?;
+    /// }
+    /// assert_eq!(*sent.lock().unwrap(), ["GET", "RESET", "PING", "CLIENT", "GET"]);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_reset_after_uses(mut self, uses: u64) -> Self {
+        self.reset_after_uses = Some(uses);
+        self
+    }
+
+    /// Create connections with the `fallbacks` clients, tried in order, when the main one
+    /// fails to connect.
+    ///
+    /// The last endpoint a connection was created with is tried first for the next
+    /// connections, so the pool sticks to a fallback until it fails in turn; the creation
+    /// fails with the last error when no endpoint can be connected to.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisConnectionManager, RedisPool};
+    ///
+    /// let fake = RedisPool::new(FakeRedisConnectionManager::new(true, None)?.with_peer_addr(), 1);
+    /// let addr = fake.get().await?.peer_addr().unwrap();
+    ///
+    /// // nothing listens on ports 1 & 2
+    /// let down = |port| redis::Client::open(format!("redis://127.0.0.1:{}", port));
+    /// let manager = RedisConnectionManager::new(down(1)?, true, None)
+    ///     .with_fallbacks(vec![down(2)?, redis::Client::open(format!("redis://{}", addr))?])
+    ///     .with_peer_addr();
+    /// let pool = RedisPool::new(manager, 2);
+    /// let connections = vec![pool.get().await?, pool.get().await?];
+    /// assert!(connections.iter().all(|con| con.peer_addr() == Some(addr)));
+    ///
+    /// let manager = RedisConnectionManager::new(down(1)?, true, None).with_fallbacks(vec![down(2)?]);
+    /// assert!(RedisPool::new(manager, 1).get().await.is_err());
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_fallbacks(mut self, fallbacks: Vec<redis::Client>) -> Self {
+        Arc::make_mut(&mut self.settings).fallbacks = fallbacks;
+        self
+    }
+
+    /// Check the server on a dedicated connection instead of checking each connection on recycle.
+    ///
+    /// The dedicated connection is sent a `PING` when a connection is recycled and the last
+    /// check is older than `interval`. While the server answers, connections are not checked
+    /// on recycle, saving a round-trip on their checkout; otherwise they are checked as usual
+    /// and [`RedisPool::is_healthy`] reports the pool as unhealthy. This only applies when
+    /// `check_on_recycle` is enabled.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::{
+    ///     sync::{
+    ///         atomic::{AtomicUsize, Ordering},
+    ///         Arc,
+    ///     },
+    ///     time::Duration,
+    /// };
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// // count the checks (`EXISTS`) of the pooled connections
+    /// let checks = Arc::new(AtomicUsize::new(0));
+    /// let counted = checks.clone();
+    /// let manager = FakeRedisConnectionManager::new(true, None)?
+    ///     .with_shared_health_check(Duration::from_millis(50))
+    ///     .with_audit(move |command, _| {
+    ///         if command == "EXISTS" {
+    ///             counted.fetch_add(1, Ordering::Relaxed);
+    ///         }
+    ///     });
+    /// let pool = RedisPool::new(manager, 1);
+    /// for _ in 0..3 {
+    ///     pool.get().await?;
+    /// }
+    /// assert!(pool.is_healthy());
+    /// assert_eq!(checks.load(Ordering::Relaxed), 0);
+    ///
+    /// // the dedicated connection detects the server does not serve commands
+    /// let mut con = pool.get().await?;
+    /// redis::cmd("DEBUG").arg("LOADING").arg(500).query_async::<_, ()>(&mut *con).await?;
+    /// drop(con);
+    /// async_std::task::sleep(Duration::from_millis(50)).await;
+    /// pool.get().await?;
+    /// assert!(!pool.is_healthy());
+    /// assert_eq!(checks.load(Ordering::Relaxed), 1);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_shared_health_check(mut self, interval: Duration) -> Self {
+        self.shared_health_check = Some(SharedHealthCheck::new(interval));
+        self
+    }
+
+    /// Set what happens when a connection can not be created, defaults to
+    /// `CreateFailurePolicy::FailFast`.
+    pub fn with_create_failure_policy(mut self, policy: CreateFailurePolicy) -> Self {
+        self.create_failure_policy = policy;
+        self
+    }
+
+    /// Open connections with `connector` instead of the client (and the fallbacks).
+    ///
+    /// This allows to use custom transports. The connections are then managed as usual
+    /// (ttl, recycling, hooks...). The client is still used for the checks run on separate
+    /// connections (pause detection, shared health check, `RedisPool::is_reachable`).
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::sync::{
+    ///     atomic::{AtomicUsize, Ordering},
+    ///     Arc,
+    /// };
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisConnectionManager, RedisPool};
+    ///
+    /// // address of a fake server
+    /// let fake = RedisPool::new(FakeRedisConnectionManager::new(true, None)?.with_peer_addr(), 1);
+    /// let addr = fake.get().await?.peer_addr().unwrap();
+    ///
+    /// let client = redis::Client::open(format!("redis://{}", addr))?;
+    /// let opened = Arc::new(AtomicUsize::new(0));
+    /// let counted = opened.clone();
+    /// // nothing listens on port 1: the connections are opened by the connector only
+    /// let manager = RedisConnectionManager::from_url("redis://127.0.0.1:1", true, None)?
+    ///     .with_connector(move || {
+    ///         counted.fetch_add(1, Ordering::Relaxed);
+    ///         let client = client.clone();
+    ///         Box::pin(async move { client.get_async_connection().await })
+    ///     });
+    /// let pool = RedisPool::new(manager, 2);
+    /// let mut con = pool.get().await?;
+    /// con.set::<_, _, ()>("key", "value").await?;
+    /// assert_eq!(opened.load(Ordering::Relaxed), 1);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_connector<F>(mut self, connector: F) -> Self
+    where
+        F: Fn() -> redis::RedisFuture<'static, redis::aio::Connection> + Send + Sync + 'static,
+    {
+        self.connector = Some(Box::new(connector));
+        self
+    }
+
+    /// Open the connections with the constructor of `runtime` (tokio's
+    /// `get_tokio_connection_tokio` or async-std's `get_async_std_connection`) instead of
+    /// `get_async_connection`, which picks tokio from within a tokio runtime and async-std
+    /// otherwise.
+    ///
+    /// This also applies to the connections opened for the checks (pause detection,
+    /// shared health check, `RedisPool::is_reachable`), not to the connections opened by a
+    /// connector. Selecting tokio fails the creation of the connections outside of a tokio
+    /// runtime, instead of picking async-std.
+    ///
+    /// ```rust
+    /// # #[cfg(all(feature = "fake", feature = "tokio-comp"))]
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool, Runtime};
+    ///
+    /// let manager = FakeRedisConnectionManager::new(true, None)?;
+    /// let pool = RedisPool::new(manager.with_connection_runtime(Runtime::Tokio), 5);
+    /// let con = pool.get().await?;
+    /// assert_eq!(con.runtime(), Some(Runtime::Tokio));
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    ///
+    /// ```rust
+    /// # #[cfg(all(feature = "fake", feature = "async-std-comp"))]
+    /// # async_std::task::block_on(async {
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool, Runtime};
+    ///
+    /// let manager = FakeRedisConnectionManager::new(true, None)?;
+    /// let pool = RedisPool::new(manager.with_connection_runtime(Runtime::AsyncStd), 5);
+    /// let con = pool.get().await?;
+    /// assert_eq!(con.runtime(), Some(Runtime::AsyncStd));
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_connection_runtime(mut self, runtime: Runtime) -> Self {
+        Arc::make_mut(&mut self.settings).runtime = Some(runtime);
+        self
+    }
+
+    /// Call `audit` with the name and the key of each command before it is sent.
+    ///
+    /// The key is the first argument after the command name, which is the key for most
+    /// commands; values are never given. Commands of a pipeline are audited one by one.
+    /// The callback is called on the command path: it must be cheap and must not block.
+    ///
+    /// Only the commands sent through the [`RedisConnection`] are audited: those sent on
+    /// the underlying `redis::aio::Connection`, reached through `Deref`/`AsMut` (e.g.
+    /// `&mut **con`), are not.
+    ///
+    /// ```rust
+    /// # use redis_async_pool::RedisConnectionManager;
+    /// # fn main() -> redis::RedisResult<()> {
+    /// let manager = RedisConnectionManager::from_url("redis://localhost:6379", true, None)?
+    ///     .with_audit(|command, key| {
+    ///         println!("{} {}", command, String::from_utf8_lossy(key.unwrap_or_default()))
+    ///     });
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::sync::{Arc, Mutex};
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let audited = Arc::new(Mutex::new(Vec::new()));
+    /// let log = audited.clone();
+    /// let manager = FakeRedisConnectionManager::new(false, None)?.with_audit(move |command, key| {
+    ///     let key = key.map(|key| String::from_utf8_lossy(key).into_owned());
+    ///     log.lock().unwrap().push((command.to_string(), key));
+    /// });
+    /// let pool = RedisPool::new(manager, 1);
+    /// let mut con = pool.get().await?;
+    /// con.set::<_, _, ()>("key", "secret").await?;
+    /// redis::pipe()
+    ///     .get("key")
+    ///     .del("other")
+    ///     .query_async::<_, ()>(&mut *con)
+    ///     .await?;
+    /// redis::cmd("PING").query_async::<_, ()>(&mut *con).await?;
+    ///
+    /// let key = || Some("key".to_string());
+    /// assert_eq!(
+    ///     *audited.lock().unwrap(),
+    ///     [
+    ///         ("SET".to_string(), key()),
+    ///         ("GET".to_string(), key()),
+    ///         ("DEL".to_string(), Some("other".to_string())),
+    ///         ("PING".to_string(), None),
+    ///     ]
+    /// );
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_audit<F>(mut self, audit: F) -> Self
+    where
+        F: Fn(&str, Option<&[u8]>) + Send + Sync + 'static,
+    {
+        Arc::make_mut(&mut self.settings).audit = Some(Arc::new(audit));
+        self
+    }
+
+    /// Decide with `is_dead` which errors leave a connection unusable, instead of the
+    /// default: connections are only discarded when failing the connection check.
+    ///
+    /// The predicate is called on the error of each failed command: the connection is
+    /// discarded on recycle if it returns `true`, e.g. to drop a connection on any
+    /// timeout. It also decides on the errors of the connection check: the connection
+    /// is kept if it returns `false`. The predicate is called on the command path: it
+    /// must be cheap and must not block.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let manager = FakeRedisConnectionManager::new(true, None)?.with_is_dead(|err| {
+    ///     err.detail().unwrap_or_default().starts_with("unknown command")
+    /// });
+    /// let pool = RedisPool::new(manager, 1);
+    ///
+    /// let mut con = pool.get().await?;
+    /// let id = con.id();
+    /// // considered alive: the connection is reused
+    /// assert!(redis::cmd("GET").query_async::<_, ()>(&mut *con).await.is_err());
+    /// drop(con);
+    /// let mut con = pool.get().await?;
+    /// assert_eq!(con.id(), id);
+    /// // considered dead: the connection is discarded
+    /// assert!(redis::cmd("NOPE").query_async::<_, ()>(&mut *con).await.is_err());
+    /// drop(con);
+    /// assert_ne!(pool.get().await?.id(), id);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_is_dead<F>(mut self, is_dead: F) -> Self
+    where
+        F: Fn(&redis::RedisError) -> bool + Send + Sync + 'static,
+    {
+        Arc::make_mut(&mut self.settings).is_dead = Some(Arc::new(is_dead));
+        self
+    }
+
+    /// Set when the connection check is run, defaults to `RecyclePolicy::Always`.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::{
+    ///     sync::{
+    ///         atomic::{AtomicUsize, Ordering},
+    ///         Arc,
+    ///     },
+    ///     time::Duration,
+    /// };
+    /// use redis_async_pool::{FakeRedisConnectionManager, RecyclePolicy, RedisPool};
+    ///
+    /// // count the connection checks (`EXISTS`) run by 3 checkouts of the same connection
+    /// let checks = |policy, fail| async move {
+    ///     let checks = Arc::new(AtomicUsize::new(0));
+    ///     let counted = checks.clone();
+    ///     let manager = FakeRedisConnectionManager::new(true, None)?
+    ///         .with_recycle_policy(policy)
+    ///         .with_audit(move |command, _| {
+    ///             if command == "EXISTS" {
+    ///                 counted.fetch_add(1, Ordering::Relaxed);
+    ///             }
+    ///         });
+    ///     let pool = RedisPool::new(manager, 1);
+    ///     for _ in 0..3 {
+    ///         let mut con = pool.get().await?;
+    ///         if fail {
+    ///             let _ = redis::cmd("NOPE").query_async::<_, ()>(&mut *con).await;
+    ///         }
+    ///     }
+    ///     Ok::<_, Box<dyn std::error::Error>>(checks.load(Ordering::Relaxed))
+    /// };
+    /// let hour = Duration::from_secs(3600);
+    /// // the connection is checked on each reuse
+    /// assert_eq!(checks(RecyclePolicy::Always, false).await?, 2);
+    /// // checked on creation only
+    /// assert_eq!(checks(RecyclePolicy::Interval(hour), false).await?, 0);
+    /// // checked only after a failed command
+    /// assert_eq!(checks(RecyclePolicy::AfterFailure(hour), false).await?, 0);
+    /// assert_eq!(checks(RecyclePolicy::AfterFailure(hour), true).await?, 2);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_recycle_policy(mut self, policy: RecyclePolicy) -> Self {
+        self.recycle_policy = policy;
+        self
+    }
+
+    /// Detect commands stalled by a paused server (`CLIENT PAUSE`).
+    ///
+    /// When a command does not complete within `threshold`, the server is checked
+    /// with an `INFO` command issued on a separate connection. If the check stalls
+    /// too, or reports the server as paused, the command fails with an error for
+    /// which [`is_server_paused`] returns true, so the application can back off.
+    /// Otherwise a regular timeout error is returned. In both cases the stalled
+    /// connection is not reused.
+    ///
+    /// As `INFO` is not a write command, pauses limited to writes (`CLIENT PAUSE WRITE`)
+    /// are reported as regular timeouts.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::Duration;
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{is_server_paused, FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let manager = FakeRedisConnectionManager::new(true, None)?
+    ///     .with_pause_detection(Duration::from_millis(50));
+    /// let pool = RedisPool::new(manager, 2);
+    /// let mut admin = pool.get().await?;
+    /// redis::cmd("CLIENT")
+    ///     .arg("PAUSE")
+    ///     .arg(200)
+    ///     .query_async::<_, ()>(&mut *admin)
+    ///     .await?;
+    ///
+    /// let mut con = pool.get().await?;
+    /// let stalled = con.id();
+    /// let err = con.get::<_, Option<String>>("key").await.unwrap_err();
+    /// assert!(is_server_paused(&err));
+    /// drop((con, admin));
+    ///
+    /// // the stalled connection is not reused once the pause is over
+    /// async_std::task::sleep(Duration::from_millis(200)).await;
+    /// let con = pool.get().await?;
+    /// let mut other = pool.get().await?;
+    /// assert!(con.id() != stalled && other.id() != stalled);
+    /// other.set::<_, _, ()>("key", "value").await?;
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_pause_detection(mut self, threshold: Duration) -> Self {
+        Arc::make_mut(&mut self.settings).pause_detection = Some(threshold);
+        self
+    }
+
+    /// Query the address of the server (`CLIENT INFO`) on each newly created connection,
+    /// see [`RedisConnection::peer_addr`].
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 1);
+    /// assert_eq!(pool.get().await?.peer_addr(), None);
+    ///
+    /// let manager = FakeRedisConnectionManager::new(true, None)?.with_peer_addr();
+    /// let pool = RedisPool::new(manager, 1);
+    /// let addr = pool.get().await?.peer_addr().unwrap();
+    /// assert!(addr.ip().is_loopback());
+    /// assert_ne!(addr.port(), 0);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_peer_addr(mut self) -> Self {
+        self.peer_addr = true;
+        self
+    }
+
+    /// Query the identifier of each newly created connection on the server side (`CLIENT
+    /// ID`), see [`RedisConnection::client_id`]: it is needed by
+    /// [`RedisPool::kill_connection`] to kill connections on the server side.
+    pub fn with_client_id(mut self) -> Self {
+        self.client_id = true;
+        self
+    }
+
+    /// Query the version of the server (`INFO server`) on each newly created connection,
+    /// see [`RedisConnection::server_version`].
+    pub fn with_server_version(mut self) -> Self {
+        self.server_version = true;
+        self
+    }
+
+    /// Report the name and the version of this crate to the server (`CLIENT SETINFO
+    /// LIB-NAME` & `LIB-VER`) on each newly created connection, shown by `CLIENT INFO`
+    /// and `CLIENT LIST`.
+    ///
+    /// `CLIENT SETINFO` requires Redis 7.2, older servers reject it: the connection is
+    /// then created without reporting them.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let manager = FakeRedisConnectionManager::new(true, None)?.with_lib_info();
+    /// let pool = RedisPool::new(manager, 1);
+    /// let mut con = pool.get().await?;
+    /// let info: String = redis::cmd("CLIENT").arg("INFO").query_async(&mut *con).await?;
+    /// assert!(info.contains(" lib-name=redis-async-pool "));
+    /// assert!(info.contains(&format!(" lib-ver={}", env!("CARGO_PKG_VERSION"))));
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_lib_info(mut self) -> Self {
+        self.lib_info = true;
+        self
+    }
+
+    /// Apply `limits` on each newly created connection, see [`ClientLimits`] for which
+    /// ones apply to the connection and which ones to the whole server.
+    ///
+    /// Each limit is set by its own command; the limits the server does not support
+    /// (older version, `CONFIG` disabled or denied by the ACL...) are skipped, the
+    /// connection is created anyway.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis_async_pool::{ClientLimits, FakeRedisConnectionManager, OutputBufferLimit, RedisPool};
+    ///
+    /// let limits = ClientLimits {
+    ///     no_evict: true,
+    ///     output_buffer_limit: Some(OutputBufferLimit {
+    ///         hard_bytes: 64 << 20,
+    ///         soft_bytes: 16 << 20,
+    ///         soft_seconds: 60,
+    ///     }),
+    ///     ..Default::default()
+    /// };
+    /// // the fake server supports none of them
+    /// let manager = FakeRedisConnectionManager::new(true, None)?.with_client_limits(limits);
+    /// let pool = RedisPool::new(manager, 5);
+    /// pool.set_key::<_, _, ()>("key", "value").await?;
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_client_limits(mut self, limits: ClientLimits) -> Self {
+        self.client_limits = Some(limits);
+        self
+    }
+
+    /// Authenticate each newly created connection as the ACL `user`, after defining it with
+    /// its rules if any, to restrict the commands and keys the connections of the pool can
+    /// use, e.g. for a pool dedicated to restricted operations.
+    ///
+    /// The user is set once the pool has set up the connection (`CLIENT` queries, limits,
+    /// replica readiness) as the user of the url of the client; the post create hooks run
+    /// as `user`. The permissions are those of the user on the server, not of the
+    /// connection, see [`AclUser`]. The user must be allowed the commands issued by the
+    /// pool on the connections: `EXISTS` for the check on recycle and `RESET`, `AUTH` &
+    /// `SELECT` for resets. The creation of a connection fails if the user can not be
+    /// defined or authenticated as.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{AclUser, FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let reader = AclUser {
+    ///     username: "reader".to_string(),
+    ///     password: "secret".to_string(),
+    ///     // reads of the cache keys only
+    ///     rules: ["reset", "on", ">secret", "+exists", "(~cache:* +get)"]
+    ///         .iter()
+    ///         .map(|rule| rule.to_string())
+    ///         .collect(),
+    /// };
+    /// let manager = FakeRedisConnectionManager::new(true, None)?.with_acl_user(reader);
+    /// let pool = RedisPool::new(manager, 5);
+    /// let mut con = pool.get().await?;
+    /// assert_eq!(con.get::<_, Option<String>>("cache:key").await?, None);
+    /// let err = con.set::<_, _, ()>("cache:key", "value").await.unwrap_err();
+    /// assert_eq!(err.code(), Some("NOPERM"));
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_acl_user(mut self, user: AclUser) -> Self {
+        self.acl_user = Some(user);
+        self
+    }
+
+    /// Count the connections created by this manager against `budget`, shared with other
+    /// managers, to limit the connections open at once by several pools, see
+    /// [`ConnectionBudget`].
+    ///
+    /// A connection takes its share of the budget before its socket is opened and gives it
+    /// back once closed.
+    pub fn with_connection_budget(mut self, budget: ConnectionBudget) -> Self {
+        self.connection_budget = Some(budget);
+        self
+    }
+
+    /// Reopen the socket of a connection found dropped by the check on recycle, instead of
+    /// discarding the connection.
+    ///
+    /// The connection keeps its id, age, usage count and expiry; the new socket is set up
+    /// like a newly created connection (post create hooks included). If it can not be
+    /// opened, the connection is discarded. This only applies when `check_on_recycle` is
+    /// enabled. The reconnections of a connection are counted by
+    /// [`RedisConnection::reconnect_count`].
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisConnectionManager, RedisPool};
+    ///
+    /// // the id, uses & server side id of the connection handed out after the server
+    /// // dropped the socket of the idle one
+    /// let after_drop = |manager: RedisConnectionManager| async move {
+    ///     let pool = RedisPool::new(manager.with_client_id(), 2);
+    ///     let (dropped, mut admin) = (pool.get().await?, pool.get().await?);
+    ///     let before = (dropped.id(), dropped.uses(), dropped.client_id());
+    ///     redis::cmd("CLIENT")
+    ///         .arg("KILL")
+    ///         .arg("ID")
+    ///         .arg(dropped.client_id().unwrap())
+    ///         .query_async::<_, ()>(&mut *admin)
+    ///         .await?;
+    ///     drop(dropped);
+    ///     let con = pool.get().await?;
+    ///     let after = (con.id(), con.uses(), con.client_id());
+    ///     Ok::<_, Box<dyn std::error::Error>>((before, after))
+    /// };
+    ///
+    /// let manager = FakeRedisConnectionManager::new(true, None)?;
+    /// let ((id, _, _), (replaced, uses, _)) = after_drop(manager).await?;
+    /// assert_ne!(replaced, id);
+    /// assert_eq!(uses, 1);
+    ///
+    /// let manager = FakeRedisConnectionManager::new(true, None)?.with_reconnect_in_place();
+    /// let ((id, _, client_id), (reconnected, uses, new_client_id)) = after_drop(manager).await?;
+    /// assert_eq!(reconnected, id);
+    /// assert_eq!(uses, 2);
+    /// assert_ne!(new_client_id, client_id);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_reconnect_in_place(mut self) -> Self {
+        self.reconnect_in_place = true;
+        self
+    }
+
+    /// Only hand out connections to a replica once it has caught up with its `master`.
+    ///
+    /// On creation, the replication offset of the replica (`slave_repl_offset` in
+    /// `INFO replication`) is compared to the one of the master: while the replica lags
+    /// more than `max_lag` bytes behind, creation waits, up to `max_wait`, after which it
+    /// fails. This helps reading your own writes when reading from replicas.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::Duration;
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisConnectionManager, RedisPool};
+    ///
+    /// // fake servers, a replica only catching up with the writes sent to it
+    /// let fake = || -> Result<_, Box<dyn std::error::Error>> {
+    ///     let manager = FakeRedisConnectionManager::new(true, None)?.with_peer_addr();
+    ///     Ok(RedisPool::new(manager, 1))
+    /// };
+    /// let (master, replica) = (fake()?, fake()?);
+    /// let (mut master, mut replica) = (master.get().await?, replica.get().await?);
+    /// let url = |con: &redis_async_pool::RedisConnection| format!("redis://{}", con.peer_addr().unwrap());
+    /// master.set::<_, _, ()>("key", "value").await?;
+    ///
+    /// let manager = RedisConnectionManager::from_url(&url(&replica), true, None)?
+    ///     .with_replica_readiness(redis::Client::open(url(&master))?, 0, Duration::from_millis(300));
+    /// let pool = RedisPool::new(manager, 2);
+    /// let err = pool.get().await.unwrap_err();
+    /// assert!(err.to_string().contains("Replica is lagging behind its master"));
+    ///
+    /// // creation waits for the replica to catch up
+    /// let (con, caught_up) = futures_util::future::join(pool.get(), async {
+    ///     async_std::task::sleep(Duration::from_millis(100)).await;
+    ///     replica.set::<_, _, ()>("key", "value").await
+    /// })
+    /// .await;
+    /// caught_up?;
+    /// con?;
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_replica_readiness(
+        mut self,
+        master: redis::Client,
+        max_lag: u64,
+        max_wait: Duration,
+    ) -> Self {
+        self.replica_readiness = Some(ReplicaReadiness {
+            master,
+            max_lag,
+            max_wait,
+        });
+        self
+    }
+
+    /// Drop connections on which too many commands fail.
+    ///
+    /// The outcome of the last 32 commands issued on each connection is recorded; once
+    /// the ratio of failed commands exceeds `threshold` (between `0.0` and `1.0`) the
+    /// connection is dropped on recycle. Every error counts, including errors returned by
+    /// the server. Connections on which less than 32 commands were issued are never
+    /// evicted this way.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let manager = FakeRedisConnectionManager::new(true, None)?.with_error_eviction(0.5);
+    /// let pool = RedisPool::new(manager, 1);
+    /// // fail 1 command out of 4 then 3 out of 4, `NOPE` being an unknown command
+    /// for failing in &[1, 3] {
+    ///     let mut con = pool.get().await?;
+    ///     let id = con.id();
+    ///     for i in 0..32 {
+    ///         let command = if i % 4 < *failing { "NOPE" } else { "PING" };
+    ///         let _ = redis::cmd(command).query_async::<_, ()>(&mut *con).await;
+    ///     }
+    ///     drop(con);
+    ///     let evicted = pool.get().await?.id() != id;
+    ///     assert_eq!(evicted, *failing == 3);
+    /// }
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_error_eviction(mut self, threshold: f64) -> Self {
+        Arc::make_mut(&mut self.settings).error_eviction = Some(threshold);
+        self
+    }
+
+    /// Poison the pool once `after` commands failed in a row, across its connections: it
+    /// then fails fast, handing out no connection, until `cooldown` elapsed or
+    /// [`RedisPool::clear_poison`] is called, see [`is_pool_poisoned`].
+    ///
+    /// This protects the server, and the callers, from a pool whose connections all
+    /// fail, e.g. a server refusing every command. Every error counts, including errors
+    /// returned by the server; any successful command ends the streak.
+    ///
+    /// # Panics
+    ///
+    /// If `after` is zero.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::Duration;
+    /// use redis_async_pool::{is_pool_poisoned, FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let manager = FakeRedisConnectionManager::new(true, None)?
+    ///     .with_poison_after(3, Duration::from_millis(100));
+    /// let pool = RedisPool::new(manager, 5);
+    ///
+    /// let mut con = pool.get().await?;
+    /// for _ in 0..3 {
+    ///     // unknown to the fake server
+    ///     assert!(redis::cmd("NOPE").query_async::<_, ()>(&mut *con).await.is_err());
+    /// }
+    /// drop(con);
+    /// assert!(pool.is_poisoned());
+    /// match pool.get().await {
+    ///     Err(redis_async_pool::deadpool::managed::PoolError::Backend(err)) => {
+    ///         assert!(is_pool_poisoned(&err))
+    ///     }
+    ///     _ => panic!("the pool is poisoned"),
+    /// }
+    ///
+    /// async_std::task::sleep(Duration::from_millis(150)).await;
+    /// assert!(!pool.is_poisoned());
+    /// pool.get().await?;
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_poison_after(mut self, after: u32, cooldown: Duration) -> Self {
+        assert!(after > 0, "the poison streak must be positive");
+        Arc::make_mut(&mut self.settings).poison = Some(Arc::new(Poison::new(after, cooldown)));
+        self
+    }
+
+    /// Fail the commands whose reply carries more than `max` bytes of data and drop
+    /// their connection, see [`is_reply_too_large`].
+    ///
+    /// The reply is fully read before being measured: this bounds the size of the
+    /// replies handed to the application, not the memory used to read them. The replies
+    /// to the commands sent on the underlying `redis::aio::Connection`, reached through
+    /// `Deref`/`AsMut` (e.g. `&mut **con`), are not measured.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{is_reply_too_large, FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let manager = FakeRedisConnectionManager::new(true, None)?.with_max_reply_bytes(10);
+    /// let pool = RedisPool::new(manager, 1);
+    /// let mut con = pool.get().await?;
+    /// let id = con.id();
+    /// con.set::<_, _, ()>("small", "0123456789").await?;
+    /// con.set::<_, _, ()>("large", "0123456789a").await?;
+    /// assert_eq!(con.get::<_, String>("small").await?, "0123456789");
+    ///
+    /// let err = con.get::<_, String>("large").await.unwrap_err();
+    /// assert!(is_reply_too_large(&err));
+    /// drop(con);
+    /// let mut con = pool.get().await?;
+    /// assert_ne!(con.id(), id);
+    ///
+    /// // the replies of a pipeline are measured together
+    /// let read: redis::RedisResult<(String, String)> = redis::pipe()
+    ///     .get("small")
+    ///     .get("small")
+    ///     .query_async(&mut *con)
+    ///     .await;
+    /// assert!(is_reply_too_large(&read.unwrap_err()));
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_max_reply_bytes(mut self, max: usize) -> Self {
+        Arc::make_mut(&mut self.settings).max_reply_bytes = Some(max);
+        self
+    }
+
+    /// Limit the commands sent by the connections of this manager to `per_second`
+    /// commands per second, with bursts of up to `per_second` commands.
+    ///
+    /// Commands exceeding the rate wait for their turn, in arrival order, before being
+    /// sent. Each command of a pipeline counts, as do the commands issued by the
+    /// manager itself (checks, hooks...). The commands sent on the underlying
+    /// `redis::aio::Connection`, reached through `Deref`/`AsMut` (e.g. `&mut **con`), are
+    /// neither counted nor delayed.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::{Duration, Instant};
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let manager = FakeRedisConnectionManager::new(false, None)?.with_rate_limit(100);
+    /// let pool = RedisPool::new(manager, 2);
+    /// let (mut first, mut second) = (pool.get().await?, pool.get().await?);
+    /// let started = Instant::now();
+    /// // a burst of a second of commands
+    /// for _ in 0..100 {
+    ///     redis::cmd("PING").query_async::<_, ()>(&mut *first).await?;
+    /// }
+    /// assert!(started.elapsed() < Duration::from_millis(400));
+    ///
+    /// // then 100 commands per second, shared by the connections
+    /// let mut pipe = redis::pipe();
+    /// for _ in 0..30 {
+    ///     pipe.cmd("PING").ignore();
+    /// }
+    /// pipe.query_async::<_, ()>(&mut *first).await?;
+    /// for _ in 0..20 {
+    ///     redis::cmd("PING").query_async::<_, ()>(&mut *second).await?;
+    /// }
+    /// let elapsed = started.elapsed();
+    /// assert!(elapsed >= Duration::from_millis(450), "{:?}", elapsed);
+    /// assert!(elapsed < Duration::from_millis(2000), "{:?}", elapsed);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `per_second` is 0.
+    pub fn with_rate_limit(mut self, per_second: u32) -> Self {
+        assert!(
+            per_second > 0,
+            "the rate limit must be at least 1 command per second"
+        );
+        Arc::make_mut(&mut self.settings).rate_limiter =
+            Some(Arc::new(RateLimiter::new(per_second)));
+        self
+    }
+
+    /// Inject latency and faults in the creation of connections and in the commands, as
+    /// configured by `chaos`.
+    ///
+    /// Enabled with the `chaos` feature, it is intended for tests.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{
+    ///     deadpool::managed::PoolError, is_injected_fault, ChaosConfig,
+    ///     FakeRedisConnectionManager, RedisPool,
+    /// };
+    ///
+    /// let manager = FakeRedisConnectionManager::new(true, None)?.with_chaos(ChaosConfig {
+    ///     create_fault_rate: 1.0,
+    ///     ..Default::default()
+    /// });
+    /// match RedisPool::new(manager, 2).get().await {
+    ///     Err(PoolError::Backend(err)) => assert!(is_injected_fault(&err)),
+    ///     _ => panic!("the creation of the connection should fail"),
+    /// }
+    ///
+    /// let manager = FakeRedisConnectionManager::new(true, None)?.with_chaos(ChaosConfig {
+    ///     command_fault_rate: 1.0,
+    ///     ..Default::default()
+    /// });
+    /// let mut con = RedisPool::new(manager, 2).get().await?;
+    /// let err = con.get::<_, Option<String>>("key").await.unwrap_err();
+    /// assert!(is_injected_fault(&err));
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    #[cfg(feature = "chaos")]
+    pub fn with_chaos(mut self, chaos: ChaosConfig) -> Self {
+        Arc::make_mut(&mut self.settings).chaos = Some(Arc::new(chaos));
+        self
+    }
+
+    /// Run `hook` on each newly created connection.
+    ///
+    /// If the hook fails, the connection is dropped and the creation fails with the
+    /// hook error. Hooks are run in registration order.
+    pub fn with_post_create<F>(mut self, hook: F) -> Self
+    where
+        F: for<'a> Fn(&'a mut RedisConnection) -> redis::RedisFuture<'a, ()>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.hooks.post_create.push(Box::new(hook));
+        self
+    }
+
+    /// Issue a `CONFIG SET` for each `(parameter, value)` pair on each newly created connection.
+    ///
+    /// The creation fails if a parameter can not be set. This is run as a post create
+    /// hook, in registration order with the hooks of [`with_post_create`](Self::with_post_create).
+    ///
+    /// Most `CONFIG` parameters are global to the server: setting them on connect changes
+    /// them for every client, and the last connection created wins.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let config = |parameter: &str, value: &str| {
+    ///     let manager = FakeRedisConnectionManager::new(true, None).unwrap();
+    ///     let config = vec![(parameter.to_string(), value.to_string())];
+    ///     RedisPool::new(manager.with_config_on_create(config), 1)
+    /// };
+    ///
+    /// let pool = config("maxmemory-policy", "allkeys-lru");
+    /// let mut con = pool.get().await?;
+    /// let (_, policy): (String, String) = redis::cmd("CONFIG")
+    ///     .arg("GET")
+    ///     .arg("maxmemory-policy")
+    ///     .query_async(&mut *con)
+    ///     .await?;
+    /// assert_eq!(policy, "allkeys-lru");
+    ///
+    /// // the creation fails when a parameter can not be set
+    /// let pool = config("no-such-parameter", "1");
+    /// assert!(pool.get().await.is_err());
+    /// assert_eq!(pool.stats().size, 0);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_config_on_create(self, config: Vec<(String, String)>) -> Self {
+        let config = Arc::new(config);
+        self.with_post_create(move |con| {
+            let config = config.clone();
+            Box::pin(async move {
+                for (parameter, value) in config.iter() {
+                    redis::cmd("CONFIG")
+                        .arg("SET")
+                        .arg(parameter)
+                        .arg(value)
+                        .query_async::<_, ()>(con)
+                        .await?;
+                }
+                Ok(())
+            })
+        })
+    }
+
+    /// Run `hook` before a connection is recycled (checked for reuse).
+    ///
+    /// If the hook fails, the connection is dropped.
+    pub fn with_pre_recycle<F>(mut self, hook: F) -> Self
+    where
+        F: for<'a> Fn(&'a mut RedisConnection) -> redis::RedisFuture<'a, ()>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.hooks.pre_recycle.push(Box::new(hook));
+        self
+    }
+
+    /// Run `hook` after a connection has been successfully recycled, right before it
+    /// is handed out.
+    ///
+    /// If the hook fails, the connection is dropped.
+    pub fn with_post_recycle<F>(mut self, hook: F) -> Self
+    where
+        F: for<'a> Fn(&'a mut RedisConnection) -> redis::RedisFuture<'a, ()>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.hooks.post_recycle.push(Box::new(hook));
+        self
+    }
+
+    /// Run `hook` each time a connection is handed out by the pool, whether it was just
+    /// created or recycled, e.g. to set a request scoped client name.
+    ///
+    /// The hooks run after the post create or recycle hooks, in registration order, in
+    /// the `get()` call: keep them cheap, they delay every acquisition. If a hook fails,
+    /// `get()` fails with its error and the connection is dropped.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::sync::{
+    ///     atomic::{AtomicUsize, Ordering},
+    ///     Arc,
+    /// };
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let checkouts = Arc::new(AtomicUsize::new(0));
+    /// let counted = checkouts.clone();
+    /// let manager = FakeRedisConnectionManager::new(true, None)?.with_on_checkout(move |_con| {
+    ///     counted.fetch_add(1, Ordering::Relaxed);
+    ///     Box::pin(async { Ok(()) })
+    /// });
+    /// let pool = RedisPool::new(manager, 2);
+    /// // created connections, then recycled ones
+    /// let (first, second) = (pool.get().await?, pool.get().await?);
+    /// drop((first, second));
+    /// for _ in 0..3 {
+    ///     pool.get().await?;
+    /// }
+    /// assert_eq!(checkouts.load(Ordering::Relaxed), 5);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_on_checkout<F>(mut self, hook: F) -> Self
+    where
+        F: for<'a> Fn(&'a mut RedisConnection) -> redis::RedisFuture<'a, ()>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.hooks.on_checkout.push(Box::new(hook));
+        self
+    }
+
+    /// Call `observer` at the end of each recycle with the connection and whether it is
+    /// kept or discarded, and why.
+    ///
+    /// Every recycle is observed, including the ones failing before the manager is
+    /// involved (connection created before a `RedisPool::roll()`, left in a transaction...).
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::{
+    ///     sync::{Arc, Mutex},
+    ///     time::Duration,
+    /// };
+    /// use redis_async_pool::{FakeRedisConnectionManager, RecycleOutcome, RedisPool, Ttl};
+    ///
+    /// let outcomes = Arc::new(Mutex::new(Vec::new()));
+    /// let observed = outcomes.clone();
+    /// let ttl = Ttl::Simple(Duration::from_millis(100));
+    /// let manager = FakeRedisConnectionManager::new(true, Some(ttl))?
+    ///     .with_recycle_observer(move |con, outcome| {
+    ///         observed.lock().unwrap().push((con.id(), outcome))
+    ///     });
+    /// let pool = RedisPool::new(manager, 1);
+    /// let id = pool.get().await?.id();
+    /// assert_eq!(pool.get().await?.id(), id);
+    /// async_std::task::sleep(Duration::from_millis(150)).await;
+    /// assert_ne!(pool.get().await?.id(), id);
+    ///
+    /// assert_eq!(
+    ///     *outcomes.lock().unwrap(),
+    ///     [
+    ///         (id, RecycleOutcome::Kept),
+    ///         (id, RecycleOutcome::Discarded("Connection expired".to_string())),
+    ///     ]
+    /// );
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_recycle_observer<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(&RedisConnection, RecycleOutcome) + Send + Sync + 'static,
+    {
+        self.recycle_observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Call `callback` when the pool becomes unable to create connections, with `false`,
+    /// and when it is able to again, with `true`.
+    ///
+    /// The pool is reported unhealthy after 3 consecutive creations failed (after their
+    /// retries, see [`with_create_failure_policy`](Self::with_create_failure_policy)),
+    /// healthy again on the first creation succeeding: the callback is only called on
+    /// these transitions, not on each creation. It is called from within the creation
+    /// and should return quickly.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::sync::{
+    ///     atomic::{AtomicBool, Ordering},
+    ///     Arc, Mutex,
+    /// };
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool, Ttl};
+    ///
+    /// let down = Arc::new(AtomicBool::new(false));
+    /// let transitions = Arc::new(Mutex::new(Vec::new()));
+    /// let manager = {
+    ///     let (down, transitions) = (down.clone(), transitions.clone());
+    ///     // a new connection for each `get()`
+    ///     FakeRedisConnectionManager::new(true, Some(Ttl::Once))?
+    ///         .with_post_create(move |_| {
+    ///             let down = down.load(Ordering::Relaxed);
+    ///             Box::pin(async move {
+    ///                 if down {
+    ///                     Err((redis::ErrorKind::IoError, "server down").into())
+    ///                 } else {
+    ///                     Ok(())
+    ///                 }
+    ///             })
+    ///         })
+    ///         .with_health_transition_callback(move |healthy| {
+    ///             transitions.lock().unwrap().push(healthy)
+    ///         })
+    /// };
+    /// let pool = RedisPool::new(manager, 1);
+    ///
+    /// down.store(true, Ordering::Relaxed);
+    /// for _ in 0..5 {
+    ///     assert!(pool.get().await.is_err());
+    /// }
+    /// assert_eq!(*transitions.lock().unwrap(), [false]);
+    ///
+    /// down.store(false, Ordering::Relaxed);
+    /// pool.get().await?;
+    /// pool.get().await?;
+    /// assert_eq!(*transitions.lock().unwrap(), [false, true]);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn with_health_transition_callback<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(bool) + Send + Sync + 'static,
+    {
+        self.health_transitions = Some(HealthTransitions::new(Box::new(callback)));
+        self
+    }
+
+    /// Log commands taking longer than `threshold` to complete.
+    ///
+    /// Slow commands are logged at the warn level, with their name and duration,
+    /// through the `log` crate.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::{
+    ///     sync::atomic::{AtomicUsize, Ordering},
+    ///     time::Duration,
+    /// };
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// // count the slow commands logged
+    /// static SLOW: AtomicUsize = AtomicUsize::new(0);
+    /// struct Counter;
+    /// impl log::Log for Counter {
+    ///     fn enabled(&self, _: &log::Metadata) -> bool {
+    ///         true
+    ///     }
+    ///     fn log(&self, record: &log::Record) {
+    ///         if record.level() == log::Level::Warn
+    ///             && record.args().to_string().starts_with("slow redis command GET")
+    ///         {
+    ///             SLOW.fetch_add(1, Ordering::Relaxed);
+    ///         }
+    ///     }
+    ///     fn flush(&self) {}
+    /// }
+    /// log::set_logger(&Counter).unwrap();
+    /// log::set_max_level(log::LevelFilter::Warn);
+    ///
+    /// let manager = FakeRedisConnectionManager::new(true, None)?
+    ///     .with_slow_command_log(Duration::from_millis(50));
+    /// let pool = RedisPool::new(manager, 2);
+    /// let (mut admin, mut con) = (pool.get().await?, pool.get().await?);
+    /// con.get::<_, Option<String>>("key").await?;
+    /// assert_eq!(SLOW.load(Ordering::Relaxed), 0);
+    /// // the command is stalled by the pause of the server
+    /// redis::cmd("CLIENT")
+    ///     .arg("PAUSE")
+    ///     .arg(100)
+    ///     .query_async::<_, ()>(&mut *admin)
+    ///     .await?;
+    /// con.get::<_, Option<String>>("key").await?;
+    /// assert_eq!(SLOW.load(Ordering::Relaxed), 1);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    #[cfg(feature = "log")]
+    pub fn with_slow_command_log(mut self, threshold: Duration) -> Self {
+        Arc::make_mut(&mut self.settings).slow_command_threshold = Some(threshold);
+        self
+    }
+
+    pub(crate) fn client(&self) -> &redis::Client {
+        &self.settings.client
+    }
+}
+
+/// Random duration between 0 and `max`
+fn random_duration(max: Duration) -> Duration {
+    if max == Duration::from_secs(0) {
+        max
+    } else {
+        Duration::from_secs_f64(rand::thread_rng().gen_range(0.0, max.as_secs_f64()))
+    }
 }
 
 #[async_trait]
 impl deadpool::managed::Manager<RedisConnection, redis::RedisError> for RedisConnectionManager {
     async fn create(&self) -> Result<RedisConnection, redis::RedisError> {
-        Ok(RedisConnection {
-            actual: self.client.get_async_connection().await?,
-            expires_at: self
-                .connection_ttl
-                .as_ref()
-                .map(|max_duration| match max_duration {
-                    Ttl::Simple(ttl) => Instant::now() + *ttl,
-                    Ttl::Fuzzy { min, fuzz } => {
-                        Instant::now()
-                            + *min
-                            + Duration::from_secs_f64(
-                                rand::thread_rng().gen_range(0.0, fuzz.as_secs_f64()),
-                            )
-                    }
-                    // already expired ;)
-                    Ttl::Once => Instant::now(),
-                }),
-        })
+        let (mut retries, mut backoff) = match self.create_failure_policy {
+            CreateFailurePolicy::Retry { retries, backoff } => (retries, backoff),
+            _ => (0, Duration::default()),
+        };
+        loop {
+            match self.try_create().await {
+                Err(_) if retries > 0 => {
+                    runtime::sleep(backoff).await;
+                    retries -= 1;
+                    backoff *= 2;
+                }
+                result => return result,
+            }
+        }
     }
     async fn recycle(
         &self,
         conn: &mut RedisConnection,
     ) -> deadpool::managed::RecycleResult<redis::RedisError> {
-        if self.check_on_recycle {
-            let _r: bool = conn.exists(b"key").await?;
-        }
-        match &conn.expires_at {
-            // check if connection is expired
-            Some(expires_at) => {
-                if &Instant::now() >= expires_at {
-                    Err(RecycleError::Message("Connection expired".to_string()))
-                } else {
-                    Ok(())
+        self.recycle_checked(conn, true).await
+    }
+}
+
+impl RedisConnectionManager {
+    /// Open a new connection and run the post create hooks
+    async fn try_create(&self) -> Result<RedisConnection, redis::RedisError> {
+        #[cfg(feature = "chaos")]
+        if let Some(chaos) = &self.settings.chaos {
+            chaos.create().await?;
+        }
+        let mut conn = self.connect().await?;
+        let started = Instant::now();
+        hooks::run(&self.hooks.post_create, &mut conn).await?;
+        conn.connect_timings.handshake += started.elapsed();
+        Ok(conn)
+    }
+
+    /// Recycle a connection, running the connection check only if `health_check` is set
+    pub(crate) async fn recycle_checked(
+        &self,
+        conn: &mut RedisConnection,
+        health_check: bool,
+    ) -> deadpool::managed::RecycleResult<redis::RedisError> {
+        hooks::run(&self.hooks.pre_recycle, conn).await?;
+        self.check(conn, health_check).await?;
+        hooks::run(&self.hooks.post_recycle, conn).await?;
+        Ok(())
+    }
+
+    /// Decide whether a connection can be reused from its metadata, without querying the
+    /// server: this is the part of the recycle not depending on the connection itself.
+    ///
+    /// Connections which are broken (e.g. left with a reply pending after a timeout),
+    /// failing too many commands (see [`with_error_eviction`](Self::with_error_eviction))
+    /// or expired at `now` are discarded; connections on which the number of commands
+    /// set with [`with_reset_after_uses`](Self::with_reset_after_uses) was reached are
+    /// reset. The connection check (`check_on_recycle`) is run afterwards on the
+    /// connections which are not discarded.
+    ///
+    /// ```rust
+    /// use std::time::{Duration, Instant};
+    /// use redis_async_pool::{ConnectionMetadata, RecycleDecision, RedisConnectionManager, Ttl};
+    ///
+    /// # fn main() -> redis::RedisResult<()> {
+    /// let manager = RedisConnectionManager::from_url("redis://localhost:6379", true, None)?
+    ///     .with_reset_after_uses(100);
+    /// let now = Instant::now();
+    /// let fresh = ConnectionMetadata {
+    ///     id: 1,
+    ///     age: Duration::from_secs(1),
+    ///     uses: 1,
+    ///     expires_at: Some(now + Duration::from_secs(60)),
+    ///     peer_addr: None,
+    ///     server_version: None,
+    ///     recent_error_rate: 0.0,
+    ///     avg_latency: None,
+    ///     recent_commands: 1,
+    ///     commands_since_reset: 1,
+    ///     clean: true,
+    ///     broken: false,
+    /// };
+    /// assert_eq!(manager.should_recycle(&fresh, now), RecycleDecision::Keep);
+    /// let expired = ConnectionMetadata { expires_at: Some(now), ..fresh.clone() };
+    /// assert_eq!(
+    ///     manager.should_recycle(&expired, now),
+    ///     RecycleDecision::Discard("Connection expired".to_string())
+    /// );
+    /// let worn = ConnectionMetadata { commands_since_reset: 100, ..fresh.clone() };
+    /// assert_eq!(manager.should_recycle(&worn, now), RecycleDecision::Reset);
+    /// let broken = ConnectionMetadata { broken: true, ..fresh };
+    /// assert_eq!(
+    ///     manager.should_recycle(&broken, now),
+    ///     RecycleDecision::Discard("Connection is broken".to_string())
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn should_recycle(&self, metadata: &ConnectionMetadata, now: Instant) -> RecycleDecision {
+        if metadata.broken {
+            return RecycleDecision::Discard("Connection is broken".to_string());
+        }
+        if let Some(threshold) = self.settings.error_eviction {
+            if metadata.recent_commands >= HISTORY_LEN && metadata.recent_error_rate > threshold {
+                return RecycleDecision::Discard("Connection error rate is too high".to_string());
+            }
+        }
+        if let Some(expires_at) = metadata.expires_at {
+            if now >= expires_at {
+                return RecycleDecision::Discard(CONNECTION_EXPIRED.to_string());
+            }
+        }
+        match self.reset_after_uses {
+            Some(uses) if metadata.commands_since_reset >= uses => RecycleDecision::Reset,
+            _ => RecycleDecision::Keep,
+        }
+    }
+
+    /// Whether expired connections are kept while no new connection can be created
+    pub(crate) fn use_stale(&self) -> bool {
+        self.create_failure_policy == CreateFailurePolicy::UseStale
+    }
+
+    /// Open a new connection
+    async fn connect(&self) -> Result<RedisConnection, redis::RedisError> {
+        let budget = self
+            .connection_budget
+            .as_ref()
+            .map(ConnectionBudget::acquire)
+            .transpose()?;
+        let (mut actual, endpoint, mut connect_timings) = self.connect_endpoint().await?;
+        let started = Instant::now();
+        // `laddr` requires Redis 6.2
+        let peer_addr = if self.peer_addr {
+            redis::cmd("CLIENT")
+                .arg("INFO")
+                .query_async::<_, String>(&mut actual)
+                .await
+                .ok()
+                .and_then(|client| info::client_field(&client, "laddr").and_then(info::socket_addr))
+        } else {
+            None
+        };
+        // `CLIENT ID` requires Redis 5
+        let client_id = if self.client_id {
+            redis::cmd("CLIENT")
+                .arg("ID")
+                .query_async::<_, u64>(&mut actual)
+                .await
+                .ok()
+        } else {
+            None
+        };
+        let server_version = if self.server_version {
+            redis::cmd("INFO")
+                .arg("server")
+                .query_async::<_, String>(&mut actual)
+                .await
+                .ok()
+                .and_then(|server| {
+                    info::field(&server, "redis_version").and_then(ServerVersion::parse)
+                })
+        } else {
+            None
+        };
+        if self.lib_info {
+            for (attribute, value) in &[
+                ("LIB-NAME", env!("CARGO_PKG_NAME")),
+                ("LIB-VER", env!("CARGO_PKG_VERSION")),
+            ] {
+                // `CLIENT SETINFO` requires Redis 7.2
+                let _ = redis::cmd("CLIENT")
+                    .arg("SETINFO")
+                    .arg(*attribute)
+                    .arg(*value)
+                    .query_async::<_, ()>(&mut actual)
+                    .await;
+            }
+        }
+        if let Some(limits) = &self.client_limits {
+            limits.apply(&mut actual).await;
+        }
+        if let Some(readiness) = &self.replica_readiness {
+            readiness.wait_ready(&mut actual).await?;
+        }
+        if let Some(user) = &self.acl_user {
+            for cmd in user.commands() {
+                cmd.query_async::<_, ()>(&mut actual).await?;
+            }
+        }
+        connect_timings.handshake = started.elapsed();
+        Ok(RedisConnection {
+            actual,
+            settings: self.settings.clone(),
+            endpoint,
+            pool_name: None,
+            peer_addr,
+            server_version,
+            generation: 0,
+            broken: false,
+            recycling: false,
+            returned: false,
+            last_checked: Instant::now(),
+            last_command_ok: true,
+            last_command_at: Instant::now(),
+            history: Default::default(),
+            latency: Default::default(),
+            commands_since_reset: 0,
+            tracker: Arc::new(Tracker::new(client_id)),
+            expires_at: self.expires_at(),
+            state: Default::default(),
+            scripts: Default::default(),
+            connect_timings,
+            reconnect_count: 0,
+            tag: None,
+            ttl_growth: Duration::default(),
+            budget,
+            runtime: self.settings.runtime.filter(|_| self.connector.is_none()),
+        })
+    }
+
+    /// Connect to the first endpoint available, starting by the last one which succeeded
+    async fn connect_endpoint(
+        &self,
+    ) -> redis::RedisResult<(redis::aio::Connection, usize, ConnectTimings)> {
+        if let Some(connector) = &self.connector {
+            let started = Instant::now();
+            let actual = connector().await?;
+            let timings = ConnectTimings {
+                connect: started.elapsed(),
+                ..Default::default()
+            };
+            return Ok((actual, 0, timings));
+        }
+        let endpoints = self.settings.endpoints();
+        let first = self.last_endpoint.load(Ordering::Relaxed);
+        let mut last_error = None;
+        for endpoint in (first..endpoints).chain(0..first) {
+            let mut timings = ConnectTimings::default();
+            let clients = match (endpoint, &self.host) {
+                (0, Some((host, port, info))) => {
+                    let started = Instant::now();
+                    let resolved = self.resolve(host, *port, info).await;
+                    timings.dns = Some(started.elapsed());
+                    match resolved {
+                        Ok(clients) => clients,
+                        Err(err) => {
+                            last_error = Some(err);
+                            continue;
+                        }
+                    }
+                }
+                _ => vec![self.settings.endpoint(endpoint).clone()],
+            };
+            let started = Instant::now();
+            for client in &clients {
+                match self.settings.connect(client).await {
+                    Ok(actual) => {
+                        self.last_endpoint.store(endpoint, Ordering::Relaxed);
+                        timings.connect = started.elapsed();
+                        return Ok((actual, endpoint, timings));
+                    }
+                    Err(err) => last_error = Some(err),
                 }
             }
-            // no expire on connections
-            None => Ok(()),
         }
+        Err(last_error.expect("a manager has at least one endpoint"))
     }
-}
 
-/// The connection created by the pool manager.
-///
-/// It is Deref & DerefMut to `redis::aio::Connection` so it can be used
-/// like a regular Redis asynchronous connection.
-pub struct RedisConnection {
-    actual: redis::aio::Connection,
-    expires_at: Option<Instant>,
-}
+    /// Resolve `host` with the runtime opening the connections, returning a client of the
+    /// main endpoint (`info`) for each of its addresses
+    async fn resolve(
+        &self,
+        host: &str,
+        port: u16,
+        info: &redis::ConnectionInfo,
+    ) -> redis::RedisResult<Vec<redis::Client>> {
+        let runtime = self.settings.runtime.unwrap_or_else(Runtime::detect);
+        let addrs = runtime.resolve(host, port).await?;
+        if addrs.is_empty() {
+            return Err((
+                redis::ErrorKind::IoError,
+                "No address found for host",
+                host.to_string(),
+            )
+                .into());
+        }
+        addrs
+            .into_iter()
+            .map(|addr| {
+                let mut info = info.clone();
+                info.addr = Box::new(ConnectionAddr::Tcp(addr.ip().to_string(), addr.port()));
+                redis::Client::open(info)
+            })
+            .collect()
+    }
 
-// Impl Deref & DefrefMut so the RedisConnection can be used as the real
-// redis::aio::Connection
+    /// Compute the expiry of a connection created now
+    fn expires_at(&self) -> Option<Instant> {
+        let now = Instant::now();
+        self.connection_ttl
+            .as_ref()
+            .and_then(|max_duration| match max_duration {
+                // a ttl overflowing Instant means no expiry
+                Ttl::Simple(ttl) => now.checked_add(*ttl)?.checked_add(self.jitter()),
+                Ttl::Fuzzy { min, fuzz } => now
+                    .checked_add(*min)?
+                    .checked_add(random_duration(*fuzz))?
+                    .checked_add(self.jitter()),
+                // already expired ;)
+                Ttl::Once => Some(now),
+                Ttl::Deadline(deadline) if *deadline > now => deadline.checked_add(self.jitter()),
+                Ttl::Deadline(_) => None,
+                Ttl::Adaptive { base, .. } => now.checked_add(*base)?.checked_add(self.jitter()),
+            })
+    }
 
-impl Deref for RedisConnection {
-    type Target = redis::aio::Connection;
-    fn deref(&self) -> &Self::Target {
-        &self.actual
+    /// Grow the ttl of a connection recycled successfully, with a `Ttl::Adaptive` ttl
+    fn grow_ttl(&self, conn: &mut RedisConnection) {
+        if let Some(Ttl::Adaptive { base, max, factor }) = self.connection_ttl {
+            let current = base + conn.ttl_growth;
+            let grown = current.saturating_mul(factor).min(max).max(current);
+            conn.ttl_growth += grown - current;
+            conn.expires_at = conn
+                .expires_at
+                .and_then(|expires_at| expires_at.checked_add(grown - current));
+        }
     }
-}
 
-impl DerefMut for RedisConnection {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.actual
+    fn jitter(&self) -> Duration {
+        self.ttl_jitter.map(random_duration).unwrap_or_default()
+    }
+
+    /// Check whether the connection can be reused, querying the server only if
+    /// `health_check` is set
+    async fn check(
+        &self,
+        conn: &mut RedisConnection,
+        health_check: bool,
+    ) -> deadpool::managed::RecycleResult<redis::RedisError> {
+        if let RecycleDecision::Discard(reason) =
+            self.should_recycle(&conn.metadata(), Instant::now())
+        {
+            return Err(RecycleError::Message(reason));
+        }
+        if self.check_on_recycle && health_check {
+            let check_due = match self.recycle_policy {
+                RecyclePolicy::Always => true,
+                RecyclePolicy::Interval(interval) => conn.last_checked.elapsed() >= interval,
+                RecyclePolicy::AfterFailure(interval) => {
+                    !conn.last_command_ok || conn.last_command_at.elapsed() >= interval
+                }
+            };
+            // skip the check of the connection while the server answers the shared check
+            let server_healthy = match (&self.shared_health_check, check_due) {
+                // only the main endpoint is checked
+                (Some(health), true) if conn.endpoint == 0 => health.check(&self.settings).await,
+                _ => false,
+            };
+            if check_due && !server_healthy {
+                match conn.exists::<_, bool>(b"key").await {
+                    Ok(_) => conn.last_checked = Instant::now(),
+                    // the connection is fine, commands issued by the caller fail with
+                    // the same error until the server has loaded its dataset
+                    Err(err) if errors::is_server_loading(&err) => {}
+                    // the error is not fatal to the connection according to the user
+                    Err(err) if matches!(&self.settings.is_dead, Some(is_dead) if !is_dead(&err)) =>
+                        {}
+                    Err(err)
+                        if self.reconnect_in_place
+                            && (err.is_connection_dropped() || err.is_io_error()) =>
+                    {
+                        self.reconnect(conn).await?
+                    }
+                    Err(err) => return Err(err.into()),
+                }
+            }
+        }
+        // evaluated again as a reconnected connection needs no reset
+        if self.should_recycle(&conn.metadata(), Instant::now()) == RecycleDecision::Reset {
+            self.reset(conn).await?;
+        }
+        self.grow_ttl(conn);
+        if self.ttl_basis == TtlBasis::SinceLastUse {
+            conn.expires_at = self
+                .expires_at()
+                .and_then(|expires_at| expires_at.checked_add(conn.ttl_growth));
+        }
+        Ok(())
     }
-}
 
-impl AsMut<redis::aio::Connection> for RedisConnection {
-    fn as_mut(&mut self) -> &mut redis::aio::Connection {
-        &mut self.actual
+    /// Replace the socket of a dropped connection, keeping its metadata
+    async fn reconnect(&self, conn: &mut RedisConnection) -> redis::RedisResult<()> {
+        // the dropped socket gives its share of the budget to the new one
+        conn.budget.take();
+        let mut fresh = self.connect().await?;
+        conn.tracker.set_client_id(fresh.client_id());
+        fresh.tracker = conn.tracker.clone();
+        fresh.expires_at = conn.expires_at;
+        fresh.ttl_growth = conn.ttl_growth;
+        fresh.generation = conn.generation;
+        fresh.pool_name = conn.pool_name.clone();
+        fresh.reconnect_count = conn.reconnect_count + 1;
+        fresh.tag = conn.tag.take();
+        let started = Instant::now();
+        hooks::run(&self.hooks.post_create, &mut fresh).await?;
+        fresh.connect_timings.handshake += started.elapsed();
+        std::mem::swap(conn, &mut fresh);
+        Ok(())
     }
-}
 
-impl AsRef<redis::aio::Connection> for RedisConnection {
-    fn as_ref(&self) -> &redis::aio::Connection {
-        &self.actual
+    /// Clear the state of the connection with `RESET` and configure it again
+    async fn reset(&self, conn: &mut RedisConnection) -> redis::RedisResult<()> {
+        let db = redis::aio::ConnectionLike::get_db(&*conn);
+        redis::cmd("RESET").query_async::<_, ()>(conn).await?;
+        conn.scripts.clear();
+        let credentials = match (&self.acl_user, &self.auth) {
+            (Some(user), _) => Some((Some(user.username.as_str()), user.password.as_str())),
+            (None, Some((username, passwd))) => Some((username.as_deref(), passwd.as_str())),
+            (None, None) => None,
+        };
+        match credentials {
+            Some((username, passwd)) => {
+                let mut auth = redis::cmd("AUTH");
+                if let Some(username) = username {
+                    auth.arg(username);
+                }
+                auth.arg(passwd).query_async::<_, ()>(conn).await?;
+            }
+            // fails if the server requires authentication
+            None => redis::cmd("PING").query_async::<_, ()>(conn).await?,
+        }
+        if db != 0 {
+            redis::cmd("SELECT")
+                .arg(db)
+                .query_async::<_, ()>(conn)
+                .await?;
+        }
+        if let Some(limits) = &self.client_limits {
+            limits.apply(conn).await;
+        }
+        hooks::run(&self.hooks.post_create, conn).await?;
+        conn.commands_since_reset = 0;
+        Ok(())
     }
 }
@@ -0,0 +1,86 @@
+//! Limits applied by the server to the connections of a pool.
+
+use redis::aio::ConnectionLike;
+
+/// Limits applied on each newly created connection, see
+/// [`RedisConnectionManager::with_client_limits`](crate::RedisConnectionManager::with_client_limits).
+///
+/// Only `no_evict` & `no_touch` apply to the connection itself. Redis has no output
+/// buffer or memory limit per connection: `output_buffer_limit` & `maxmemory_clients`
+/// set the configuration of the server (`CONFIG SET`), applying to every client
+/// connected to it, including those of other applications, and are set again by each
+/// new connection.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClientLimits {
+    /// Exempt the connection from client eviction, which closes the connections using the
+    /// most memory once `maxmemory-clients` is reached (`CLIENT NO-EVICT on`, Redis 7.0).
+    pub no_evict: bool,
+    /// Leave the LRU/LFU statistics of the keys read by the connection untouched, so its
+    /// reads do not protect keys from eviction (`CLIENT NO-TOUCH on`, Redis 7.2).
+    pub no_touch: bool,
+    /// Output buffer limit of the normal clients, disconnected once their pending
+    /// replies exceed it (`CONFIG SET client-output-buffer-limit`): global.
+    pub output_buffer_limit: Option<OutputBufferLimit>,
+    /// Maximum memory used by all the clients together, the connections using the most
+    /// being closed beyond it (`CONFIG SET maxmemory-clients`, Redis 7.0): global.
+    pub maxmemory_clients: Option<u64>,
+}
+
+/// Output buffer limit of the normal clients, see [`ClientLimits::output_buffer_limit`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OutputBufferLimit {
+    /// A client is disconnected as soon as its output buffer exceeds this size, in
+    /// bytes; `0` means no limit
+    pub hard_bytes: u64,
+    /// A client is disconnected once its output buffer exceeded this size, in bytes,
+    /// for `soft_seconds`; `0` means no limit
+    pub soft_bytes: u64,
+    /// Time the soft limit may be exceeded, in seconds
+    pub soft_seconds: u64,
+}
+
+impl ClientLimits {
+    /// The commands applying the limits
+    /// Apply the limits on `con`, skipping those the server does not support
+    pub(crate) async fn apply<C: ConnectionLike>(&self, con: &mut C) {
+        for cmd in self.commands() {
+            if let Err(_err) = cmd.query_async::<_, ()>(con).await {
+                #[cfg(feature = "log")]
+                log::debug!("redis connection limit not applied: {}", _err);
+            }
+        }
+    }
+
+    fn commands(&self) -> Vec<redis::Cmd> {
+        let mut commands = Vec::new();
+        let client = |option: &str| {
+            let mut cmd = redis::cmd("CLIENT");
+            cmd.arg(option).arg("on");
+            cmd
+        };
+        if self.no_evict {
+            commands.push(client("NO-EVICT"));
+        }
+        if self.no_touch {
+            commands.push(client("NO-TOUCH"));
+        }
+        if let Some(limit) = &self.output_buffer_limit {
+            let mut cmd = redis::cmd("CONFIG");
+            cmd.arg("SET")
+                .arg("client-output-buffer-limit")
+                .arg(format!(
+                    "normal {} {} {}",
+                    limit.hard_bytes, limit.soft_bytes, limit.soft_seconds
+                ));
+            commands.push(cmd);
+        }
+        if let Some(max) = self.maxmemory_clients {
+            let mut cmd = redis::cmd("CONFIG");
+            cmd.arg("SET").arg("maxmemory-clients").arg(max);
+            commands.push(cmd);
+        }
+        commands
+    }
+}
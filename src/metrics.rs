@@ -0,0 +1,266 @@
+//! Export of the pool statistics to Prometheus.
+
+use std::{sync::atomic::Ordering, time::Instant};
+
+use deadpool::managed::PoolError;
+use prometheus::{
+    core::{Collector, Desc},
+    proto::MetricFamily,
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use redis::{RedisError, RedisFuture};
+
+use crate::{RedisConnection, RedisPool};
+
+/// Metrics of the operations run with [`RedisPool::execute_labeled`], by operation
+pub(crate) struct OperationMetrics {
+    duration: HistogramVec,
+    errors: IntCounterVec,
+}
+
+impl OperationMetrics {
+    pub(crate) fn new(pool: Option<&str>) -> Self {
+        let opts = |name: &str, help: &str| {
+            let opts = Opts::new(name, help);
+            match pool {
+                Some(pool) => opts.const_label("pool", pool),
+                None => opts,
+            }
+        };
+        // the names & labels are valid, creating the metrics can not fail
+        Self {
+            duration: HistogramVec::new(
+                HistogramOpts::from(opts(
+                    "redis_pool_operation_duration_seconds",
+                    "Duration of the operations",
+                )),
+                &["operation"],
+            )
+            .unwrap(),
+            errors: IntCounterVec::new(
+                opts(
+                    "redis_pool_operation_errors_total",
+                    "Number of failed operations",
+                ),
+                &["operation"],
+            )
+            .unwrap(),
+        }
+    }
+}
+
+/// Metrics of a pool, updated from the pool each time they are collected
+struct PoolCollector {
+    pool: RedisPool,
+    max_size: IntGauge,
+    size: IntGauge,
+    idle: IntGauge,
+    in_use: IntGauge,
+    waiting: IntGauge,
+    created: IntCounter,
+    recycle_failures: IntCounter,
+}
+
+impl PoolCollector {
+    fn new(pool: RedisPool) -> prometheus::Result<Self> {
+        let opts = |name: &str, help: &str| {
+            let opts = Opts::new(name, help);
+            match pool.name() {
+                Some(pool) => opts.const_label("pool", pool),
+                None => opts,
+            }
+        };
+        Ok(Self {
+            max_size: IntGauge::with_opts(opts(
+                "redis_pool_max_size",
+                "Maximum number of connections",
+            ))?,
+            size: IntGauge::with_opts(opts("redis_pool_size", "Number of connections"))?,
+            idle: IntGauge::with_opts(opts("redis_pool_idle", "Number of idle connections"))?,
+            in_use: IntGauge::with_opts(opts("redis_pool_in_use", "Number of connections in use"))?,
+            waiting: IntGauge::with_opts(opts(
+                "redis_pool_waiting",
+                "Number of callers waiting for a connection",
+            ))?,
+            created: IntCounter::with_opts(opts(
+                "redis_pool_connections_created_total",
+                "Number of connections created",
+            ))?,
+            recycle_failures: IntCounter::with_opts(opts(
+                "redis_pool_recycle_failures_total",
+                "Number of connections discarded on recycle",
+            ))?,
+            pool,
+        })
+    }
+
+    fn collectors(&self) -> [&dyn Collector; 9] {
+        let operations = &self.pool.shared.operations;
+        [
+            &self.max_size,
+            &self.size,
+            &self.idle,
+            &self.in_use,
+            &self.waiting,
+            &self.created,
+            &self.recycle_failures,
+            &operations.duration,
+            &operations.errors,
+        ]
+    }
+}
+
+fn set_counter(counter: &IntCounter, value: u64) {
+    counter.reset();
+    counter.inc_by(value);
+}
+
+impl Collector for PoolCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        self.collectors()
+            .iter()
+            .flat_map(|collector| collector.desc())
+            .collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let stats = self.pool.stats();
+        self.max_size.set(stats.max_size as i64);
+        self.size.set(stats.size as i64);
+        self.idle.set(stats.available as i64);
+        self.in_use
+            .set(stats.size.saturating_sub(stats.available) as i64);
+        self.waiting.set(stats.waiting as i64);
+        let shared = &self.pool.shared;
+        set_counter(&self.created, shared.created.load(Ordering::Relaxed));
+        set_counter(
+            &self.recycle_failures,
+            shared.recycle_failures.load(Ordering::Relaxed),
+        );
+        self.collectors()
+            .iter()
+            .flat_map(|collector| collector.collect())
+            .collect()
+    }
+}
+
+impl RedisPool {
+    /// Register the metrics of this pool with a Prometheus `registry`.
+    ///
+    /// The metrics (`redis_pool_size`, `redis_pool_idle`, `redis_pool_in_use`,
+    /// `redis_pool_connections_created_total`, `redis_pool_recycle_failures_total`...)
+    /// are read from the pool each time the registry is gathered. The registry holds a
+    /// handle to the pool, so the pool is kept alive while registered.
+    ///
+    /// The metrics of a pool named with [`RedisPoolBuilder::with_name`](crate::RedisPoolBuilder::with_name)
+    /// have a `pool` label holding its name: several named pools can be registered with
+    /// the same registry.
+    ///
+    /// Enabled with the `prometheus` feature.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis_async_pool::{encode_prometheus, FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let pool = |name| -> Result<RedisPool, Box<dyn std::error::Error>> {
+    ///     Ok(RedisPool::builder(FakeRedisConnectionManager::new(true, None)?)
+    ///         .max_size(5)
+    ///         .with_name(name)
+    ///         .build()?)
+    /// };
+    /// let (cache, sessions) = (pool("cache")?, pool("sessions")?);
+    /// let registry = prometheus::Registry::new();
+    /// cache.register_prometheus(&registry)?;
+    /// sessions.register_prometheus(&registry)?;
+    ///
+    /// let in_use = cache.get().await?;
+    /// drop(cache.get().await?);
+    /// let exposition = encode_prometheus(&registry)?;
+    /// for metric in &[
+    ///     r#"redis_pool_max_size{pool="cache"} 5"#,
+    ///     r#"redis_pool_size{pool="cache"} 2"#,
+    ///     r#"redis_pool_idle{pool="cache"} 1"#,
+    ///     r#"redis_pool_in_use{pool="cache"} 1"#,
+    ///     r#"redis_pool_connections_created_total{pool="cache"} 2"#,
+    ///     r#"redis_pool_size{pool="sessions"} 0"#,
+    /// ] {
+    ///     assert!(exposition.contains(metric), "{} not in {}", metric, exposition);
+    /// }
+    /// drop(in_use);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn register_prometheus(&self, registry: &Registry) -> prometheus::Result<()> {
+        registry.register(Box::new(PoolCollector::new(self.clone())?))
+    }
+
+    /// Run the commands issued by `f` on a connection of the pool, recording their
+    /// duration under the `operation` label of the `redis_pool_operation_duration_seconds`
+    /// histogram, and their failure in the `redis_pool_operation_errors_total` counter.
+    ///
+    /// The time spent acquiring the connection is not recorded; failing to acquire it is
+    /// not counted as a failed operation.
+    ///
+    /// Enabled with the `prometheus` feature.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{encode_prometheus, FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 5);
+    /// let registry = prometheus::Registry::new();
+    /// pool.register_prometheus(&registry)?;
+    ///
+    /// pool.execute_labeled("save_user", |con| con.set::<_, _, ()>("user:1", "alice"))
+    ///     .await?;
+    /// let name: String = pool
+    ///     .execute_labeled("load_user", |con| con.get("user:1"))
+    ///     .await?;
+    /// assert_eq!(name, "alice");
+    ///
+    /// let exposition = encode_prometheus(&registry)?;
+    /// assert!(exposition
+    ///     .contains(r#"redis_pool_operation_duration_seconds_count{operation="save_user"} 1"#));
+    /// assert!(exposition
+    ///     .contains(r#"redis_pool_operation_duration_seconds_count{operation="load_user"} 1"#));
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn execute_labeled<T, F>(
+        &self,
+        operation: &str,
+        f: F,
+    ) -> Result<T, PoolError<RedisError>>
+    where
+        F: for<'a> FnOnce(&'a mut RedisConnection) -> RedisFuture<'a, T>,
+    {
+        let mut con = self.get().await?;
+        let started = Instant::now();
+        let result = f(&mut con).await;
+        let operations = &self.shared.operations;
+        operations
+            .duration
+            .with_label_values(&[operation])
+            .observe(started.elapsed().as_secs_f64());
+        if result.is_err() {
+            operations.errors.with_label_values(&[operation]).inc();
+        }
+        Ok(result?)
+    }
+}
+
+/// Encode the metrics of `registry` in the Prometheus text format, ready to be served to
+/// a Prometheus scraper.
+///
+/// Enabled with the `prometheus` feature.
+pub fn encode_prometheus(registry: &Registry) -> prometheus::Result<String> {
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&registry.gather(), &mut buffer)?;
+    String::from_utf8(buffer).map_err(|err| prometheus::Error::Msg(err.to_string()))
+}
@@ -0,0 +1,233 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use redis::{
+    aio::{ConnectionLike, MultiplexedConnection},
+    Cmd, Pipeline, RedisFuture, RedisResult, Value,
+};
+use tokio::sync::OwnedSemaphorePermit;
+
+use crate::{
+    pool::PoolShared, runtime::Runtime, ConnectionBudget, RedisConnectionManager, RedisPool,
+};
+
+/// A multiplexed connection shared by every caller of [`RedisPool::multiplexed`].
+///
+/// Commands sent concurrently through clones of this connection are pipelined on a
+/// single socket instead of waiting for a connection each.
+///
+/// The connection is opened to the main endpoint of the manager, within the `create`
+/// timeout of the pool, with the runtime of the connections of the manager. It takes a
+/// share of the [connection budget](RedisConnectionManager::with_connection_budget) and
+/// is set up with the [client limits](RedisConnectionManager::with_client_limits), the
+/// [replica readiness](RedisConnectionManager::with_replica_readiness) and the
+/// [ACL user](RedisConnectionManager::with_acl_user) of the manager. The settings
+/// applying to `RedisConnection` (hooks, connector, fallbacks, ttl, checks, audit, rate
+/// limit...) do not apply.
+///
+/// When a command fails because the connection is dropped, the pool forgets it: the
+/// next call to [`RedisPool::multiplexed`] opens a new one.
+#[derive(Clone)]
+pub struct SharedConnection {
+    con: MultiplexedConnection,
+    id: u64,
+    pool: RedisPool,
+    _budget: Option<Arc<OwnedSemaphorePermit>>,
+}
+
+/// The multiplexed connection of a pool
+pub(crate) struct Multiplexed {
+    id: u64,
+    /// Generation of the pool when the connection was opened
+    generation: u64,
+    con: MultiplexedConnection,
+    /// Share of the connection budget, given back once every clone of the connection is
+    /// dropped
+    budget: Option<Arc<OwnedSemaphorePermit>>,
+}
+
+/// Open a multiplexed connection, its driver being run by `runtime`
+async fn connect(client: &redis::Client, runtime: Runtime) -> RedisResult<MultiplexedConnection> {
+    match runtime {
+        #[cfg(feature = "tokio-comp")]
+        Runtime::Tokio => {
+            let (con, driver) = client.create_multiplexed_tokio_connection().await?;
+            tokio::spawn(driver);
+            Ok(con)
+        }
+        #[cfg(feature = "async-std-comp")]
+        Runtime::AsyncStd => {
+            let (con, driver) = client.create_multiplexed_async_std_connection().await?;
+            async_std::task::spawn(driver);
+            Ok(con)
+        }
+    }
+}
+
+impl RedisConnectionManager {
+    /// Open a multiplexed connection to the main endpoint, set up like the connections
+    /// created by `connect()`, its driver being run by `runtime` unless the manager
+    /// selects the runtime of its connections
+    async fn connect_multiplexed(
+        &self,
+        runtime: Runtime,
+    ) -> RedisResult<(MultiplexedConnection, Option<OwnedSemaphorePermit>)> {
+        let budget = self
+            .connection_budget
+            .as_ref()
+            .map(ConnectionBudget::acquire)
+            .transpose()?;
+        let runtime = self.settings.runtime.unwrap_or(runtime);
+        runtime.check()?;
+        let mut con = connect(self.client(), runtime).await?;
+        if let Some(limits) = &self.client_limits {
+            limits.apply(&mut con).await;
+        }
+        if let Some(readiness) = &self.replica_readiness {
+            readiness.wait_ready(&mut con).await?;
+        }
+        if let Some(user) = &self.acl_user {
+            for cmd in user.commands() {
+                cmd.query_async::<_, ()>(&mut con).await?;
+            }
+        }
+        Ok((con, budget))
+    }
+}
+
+impl RedisPool {
+    /// Get the multiplexed connection shared by the callers of this method, opening it
+    /// if needed.
+    ///
+    /// For a pool of a single connection, this is a faster alternative to `get()`:
+    /// concurrent callers share the connection instead of waiting for each other. The
+    /// connections handed out by `get()` are never multiplexed, since they are used as
+    /// `redis::aio::Connection`; the multiplexed connection does not take a slot of the
+    /// pool. It is opened again after [`roll`](Self::roll) or
+    /// [`reconfigure`](Self::reconfigure).
+    ///
+    /// Blocking commands and transactions (`WATCH`) must not be issued on a multiplexed
+    /// connection as they would hold or alter it for every caller: use `get()` for them.
+    /// See [`SharedConnection`] for the settings of the manager which apply.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{AclUser, ConnectionBudget, FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let writer = AclUser {
+    ///     username: "writer".to_string(),
+    ///     password: "secret".to_string(),
+    ///     rules: ["reset", "on", ">secret", "+set", "+get"]
+    ///         .iter()
+    ///         .map(|rule| rule.to_string())
+    ///         .collect(),
+    /// };
+    /// let budget = ConnectionBudget::new(1);
+    /// let manager = FakeRedisConnectionManager::new(true, None)?
+    ///     .with_acl_user(writer)
+    ///     .with_connection_budget(budget.clone());
+    /// let pool = RedisPool::new(manager, 1);
+    ///
+    /// let results = futures_util::future::join_all((0..20).map(|i| {
+    ///     let pool = &pool;
+    ///     async move {
+    ///         let mut con = pool.multiplexed().await?;
+    ///         con.set::<_, _, ()>(format!("key{}", i), i).await?;
+    ///         con.get::<_, usize>(format!("key{}", i)).await
+    ///     }
+    /// }))
+    /// .await;
+    /// for (i, result) in results.into_iter().enumerate() {
+    ///     assert_eq!(result?, i);
+    /// }
+    /// // a single connection, authenticated as the ACL user of the manager
+    /// assert_eq!(budget.open(), 1);
+    /// let mut con = pool.multiplexed().await?;
+    /// let err = con.del::<_, ()>("key0").await.unwrap_err();
+    /// assert_eq!(err.code(), Some("NOPERM"));
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn multiplexed(&self) -> RedisResult<SharedConnection> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        let generation = self.shared.generation.load(Ordering::Acquire);
+        let mut multiplexed = self.shared.multiplexed.lock().await;
+        let current = match &*multiplexed {
+            Some(current) if current.generation == generation => current,
+            _ => {
+                let manager = self.shared.current();
+                let runtime = self.shared.runtime();
+                let open = manager.connect_multiplexed(runtime);
+                let (con, budget) = match self.shared.timeouts.create {
+                    Some(timeout) => {
+                        runtime.check()?;
+                        runtime.timeout(timeout, open).await??
+                    }
+                    None => open.await?,
+                };
+                multiplexed.insert(Multiplexed {
+                    id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+                    generation,
+                    con,
+                    budget: budget.map(Arc::new),
+                })
+            }
+        };
+        Ok(SharedConnection {
+            con: current.con.clone(),
+            id: current.id,
+            pool: self.clone(),
+            _budget: current.budget.clone(),
+        })
+    }
+}
+
+impl SharedConnection {
+    /// Forget the connection if `result` tells it is dropped
+    async fn check<T>(&self, result: RedisResult<T>) -> RedisResult<T> {
+        if let Err(err) = &result {
+            if err.is_connection_dropped() || err.is_io_error() {
+                forget(&self.pool.shared, self.id).await;
+            }
+        }
+        result
+    }
+}
+
+/// Forget the multiplexed connection of the pool, unless it was replaced already
+async fn forget(shared: &PoolShared, id: u64) {
+    let mut multiplexed = shared.multiplexed.lock().await;
+    if matches!(&*multiplexed, Some(current) if current.id == id) {
+        *multiplexed = None;
+    }
+}
+
+impl ConnectionLike for SharedConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        Box::pin(async move {
+            let result = self.con.req_packed_command(cmd).await;
+            self.check(result).await
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        Box::pin(async move {
+            let result = self.con.req_packed_commands(cmd, offset, count).await;
+            self.check(result).await
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        self.con.get_db()
+    }
+}
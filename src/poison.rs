@@ -0,0 +1,59 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Circuit opened by a streak of failed commands across the connections of a manager.
+///
+/// While open, the pool is poisoned: it fails fast instead of handing out connections,
+/// until the cooldown elapsed or the poison is cleared.
+pub(crate) struct Poison {
+    pub(crate) after: u32,
+    pub(crate) cooldown: Duration,
+    state: Mutex<PoisonState>,
+}
+
+#[derive(Default)]
+struct PoisonState {
+    /// Number of consecutive failed commands
+    streak: u32,
+    poisoned_until: Option<Instant>,
+}
+
+impl Poison {
+    pub(crate) fn new(after: u32, cooldown: Duration) -> Self {
+        Self {
+            after,
+            cooldown,
+            state: Mutex::new(PoisonState::default()),
+        }
+    }
+
+    /// Record the outcome of a command, poisoning the pool once `after` commands failed
+    /// in a row
+    pub(crate) fn record(&self, ok: bool) {
+        let mut state = self.state.lock().unwrap();
+        if ok {
+            state.streak = 0;
+            return;
+        }
+        state.streak += 1;
+        if state.streak >= self.after {
+            state.streak = 0;
+            state.poisoned_until = Instant::now().checked_add(self.cooldown);
+        }
+    }
+
+    /// Time left before the poison wears off, `None` if the pool is not poisoned
+    pub(crate) fn remaining(&self) -> Option<Duration> {
+        let state = self.state.lock().unwrap();
+        let remaining = state
+            .poisoned_until?
+            .checked_duration_since(Instant::now())?;
+        Some(remaining).filter(|remaining| *remaining > Duration::default())
+    }
+
+    pub(crate) fn clear(&self) {
+        *self.state.lock().unwrap() = PoisonState::default();
+    }
+}
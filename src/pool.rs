@@ -0,0 +1,2103 @@
+use std::{
+    fmt,
+    future::Future,
+    ops::{Deref, DerefMut},
+    panic::Location,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock, Weak,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use deadpool::{
+    managed::{
+        Manager, Object, PoolConfig, PoolError, RecycleError, RecycleResult, TimeoutType, Timeouts,
+    },
+    Status,
+};
+use futures_util::stream::{self, Stream, StreamExt, TryStreamExt};
+use redis::{ErrorKind, RedisError, RedisFuture, RedisResult};
+use tokio::sync::OwnedSemaphorePermit;
+
+use crate::{
+    affinity::Affinity,
+    builder::RedisPoolBuilder,
+    capacity::Capacity,
+    coalesce::PendingGets,
+    connection::Tracker,
+    errors,
+    hooks::{self, Hook},
+    multiplexed::Multiplexed,
+    poison::Poison,
+    priority::{Priority, PriorityGate},
+    runtime::Runtime,
+    RecycleOutcome, RedisConnection, RedisConnectionManager, Ttl, CONNECTION_EXPIRED,
+};
+
+#[cfg(feature = "local-cache")]
+use crate::cache::LocalCache;
+#[cfg(feature = "log")]
+use crate::connection::OfPool;
+#[cfg(feature = "prometheus")]
+use crate::metrics::OperationMetrics;
+
+type Pool = deadpool::managed::Pool<RedisConnection, RedisError>;
+
+/// The redis connection pool
+///
+/// Use the `new` method to create a new pool, or [`RedisPool::builder`] to set
+/// timeouts. You can find more information in the documentation of the `deadpool` crate.
+///
+/// Connections are obtained with `get()`, `try_get()` or `timeout_get()`, the status
+/// of the pool with `stats()`.
+///
+/// ## Fairness
+///
+/// Callers waiting for a connection are served in arrival order: slots are
+/// handed out through a fair (FIFO) semaphore, so a connection returned to the
+/// pool goes to the oldest waiter and `try_get()` can not overtake callers already
+/// waiting. A waiter may still wait longer than another if the connection it is
+/// handed fails its recycle check and must be recreated. Callers of
+/// `get_with_priority()` are served by priority first, then in arrival order.
+///
+/// ```rust
+/// # #[cfg(feature = "fake")]
+/// # async_std::task::block_on(async {
+/// use std::{sync::Mutex, time::Duration};
+/// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+///
+/// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 1);
+/// let in_use = pool.get().await?;
+/// let order = Mutex::new(Vec::new());
+/// let release = async move {
+///     async_std::task::sleep(Duration::from_millis(10)).await;
+///     drop(in_use);
+/// };
+/// // the waiters are polled, so they start waiting, in order
+/// let waiters = futures_util::future::join_all((0..20).map(|i| {
+///     let (pool, order) = (&pool, &order);
+///     async move {
+///         let _con = pool.get().await.unwrap();
+///         order.lock().unwrap().push(i);
+///         async_std::task::sleep(Duration::from_millis(1)).await;
+///     }
+/// }));
+/// futures_util::join!(waiters, release);
+/// assert_eq!(order.into_inner().unwrap(), (0..20).collect::<Vec<_>>());
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// # })
+/// # .unwrap();
+/// ```
+///
+/// ## Cancellation
+///
+/// Dropping a pending `get()`, `try_get()` or `timeout_get()` future (e.g. when it
+/// loses a `select!`) gives back everything it holds: the slot it waited for, the
+/// connection being created, which is closed, or the connection being recycled.
+/// The latter stays in the pool but is discarded by its next recycle, as it may still
+/// have a reply pending.
+///
+/// ```rust
+/// # #[cfg(feature = "fake")]
+/// # async_std::task::block_on(async {
+/// use std::time::Duration;
+/// use futures_util::future::{self, Either};
+/// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+///
+/// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 2);
+/// let (con, mut admin) = (pool.get().await?, pool.get().await?);
+/// let interrupted = con.id();
+/// drop(con);
+///
+/// // the server stalls the check of the connection on recycle
+/// redis::cmd("CLIENT")
+///     .arg("PAUSE")
+///     .arg(200)
+///     .query_async::<_, ()>(&mut *admin)
+///     .await?;
+/// let get = Box::pin(pool.get());
+/// let timer = Box::pin(async_std::task::sleep(Duration::from_millis(50)));
+/// assert!(matches!(future::select(get, timer).await, Either::Right(_)));
+///
+/// async_std::task::sleep(Duration::from_millis(250)).await;
+/// let con = pool.get().await?;
+/// assert_ne!(con.id(), interrupted);
+/// assert_eq!(pool.connections_snapshot().len(), 2);
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// # })
+/// # .unwrap();
+/// ```
+#[derive(Clone)]
+pub struct RedisPool {
+    pool: Pool,
+    pub(crate) shared: Arc<PoolShared>,
+}
+
+/// State shared between the pool handle and the manager owned by deadpool.
+pub(crate) struct PoolShared {
+    manager: RwLock<Arc<RedisConnectionManager>>,
+    /// Incremented each time the manager is replaced or the pool is rolled;
+    /// connections created by a previous generation are discarded on recycle.
+    pub(crate) generation: AtomicU64,
+    /// Trackers of the connections created by the pool
+    connections: Mutex<Vec<Weak<Tracker>>>,
+    pub(crate) pending_gets: PendingGets,
+    acquire_stats: Mutex<AcquireStats>,
+    /// Timeouts applied by `get()`; deadpool is given none since its timers
+    /// require a tokio runtime.
+    pub(crate) timeouts: Timeouts,
+    runtime: Option<Runtime>,
+    /// Limit of the connections in use of a resizable pool
+    capacity: Option<Capacity>,
+    /// Callers waiting for a connection while the pool is contended
+    gate: Arc<PriorityGate>,
+    /// Connections parked for the threads which gave them back
+    affinity: Option<Affinity>,
+    /// Values read with `RedisPool::cache_get`, served while the server is unavailable
+    #[cfg(feature = "local-cache")]
+    pub(crate) local_cache: Option<LocalCache>,
+    /// Name given with `RedisPoolBuilder::with_name`
+    pub(crate) name: Option<Arc<str>>,
+    /// Time after which a connection still in use is reported
+    #[cfg(feature = "log")]
+    max_borrow_duration: Option<Duration>,
+    /// Closures run on every connection with `RedisPool::for_each_connection`
+    migrations: Mutex<Migrations>,
+    /// Connection shared by the callers of `RedisPool::multiplexed`
+    pub(crate) multiplexed: futures_util::lock::Mutex<Option<Multiplexed>>,
+    /// Number of connections created
+    pub(crate) created: AtomicU64,
+    /// Number of connections discarded on recycle
+    pub(crate) recycle_failures: AtomicU64,
+    /// Metrics of the operations run with `RedisPool::execute_labeled`
+    #[cfg(feature = "prometheus")]
+    pub(crate) operations: OperationMetrics,
+}
+
+impl PoolShared {
+    pub(crate) fn current(&self) -> Arc<RedisConnectionManager> {
+        self.manager.read().unwrap().clone()
+    }
+
+    /// Drop the migrations run on every connection of the pool
+    fn prune_migrations(&self) {
+        let connections = self.connections.lock().unwrap();
+        let mut migrations = self.migrations.lock().unwrap();
+        if migrations.creating > 0 {
+            // the connections being created may not have run the last ones
+            return;
+        }
+        let run = connections
+            .iter()
+            .filter_map(Weak::upgrade)
+            // those will not be recycled by the pool again
+            .filter(|tracker| {
+                !tracker.killed.load(Ordering::Relaxed) && !tracker.taken.load(Ordering::Relaxed)
+            })
+            .map(|tracker| tracker.migrations.load(Ordering::Relaxed))
+            .min()
+            .unwrap_or_else(|| migrations.registered());
+        let pruned = run - migrations.pruned;
+        migrations.pending.drain(..pruned);
+        migrations.pruned = run;
+    }
+
+    pub(crate) fn runtime(&self) -> Runtime {
+        self.runtime.unwrap_or_else(Runtime::detect)
+    }
+}
+
+/// Closures registered with `RedisPool::for_each_connection`, in registration order.
+///
+/// Connections count the migrations they ran (see `Tracker::migrations`), the first ones
+/// are dropped once every connection ran them.
+#[derive(Default)]
+struct Migrations {
+    /// Number of migrations dropped
+    pruned: usize,
+    /// Migrations not yet run on every connection
+    pending: Vec<Arc<Hook>>,
+    /// Number of connections being created
+    creating: usize,
+}
+
+impl Migrations {
+    /// Number of migrations registered since the pool was created
+    fn registered(&self) -> usize {
+        self.pruned + self.pending.len()
+    }
+}
+
+/// Counts a connection being created, as long as it is not tracked by the pool
+struct Creating<'a>(&'a Mutex<Migrations>);
+
+impl<'a> Creating<'a> {
+    /// Start the creation of a connection, returning it with the number of migrations
+    /// the connection is expected to be configured as
+    fn start(migrations: &'a Mutex<Migrations>) -> (Self, usize) {
+        let mut guard = migrations.lock().unwrap();
+        guard.creating += 1;
+        (Creating(migrations), guard.registered())
+    }
+}
+
+impl Drop for Creating<'_> {
+    fn drop(&mut self) {
+        self.0.lock().unwrap().creating -= 1;
+    }
+}
+
+/// Manager given to deadpool, delegating to the current `RedisConnectionManager`.
+///
+/// Deadpool recycles connections from within `Pool::get()`, which can not be given the
+/// options of the acquisition: it only flags the connections it hands out again, which
+/// are then recycled by `RedisPool` with [`ManagerProxy::recycle_checked`].
+struct ManagerProxy(Arc<PoolShared>);
+
+impl ManagerProxy {
+    async fn try_create(&self) -> Result<RedisConnection, RedisError> {
+        let generation = self.0.generation.load(Ordering::Acquire);
+        // new connections are expected to be configured as the migrations registered
+        let (_creating, migrations) = Creating::start(&self.0.migrations);
+        let manager = self.0.current();
+        let create = manager.create();
+        let mut conn = match self.0.timeouts.create {
+            Some(timeout) => self
+                .0
+                .runtime()
+                .timeout(timeout, create)
+                .await
+                .map_err(|_| errors::create_timeout())??,
+            None => create.await?,
+        };
+        conn.generation = generation;
+        conn.tracker.migrations.store(migrations, Ordering::Relaxed);
+        conn.pool_name = self.0.name.clone();
+        let mut connections = self.0.connections.lock().unwrap();
+        connections.retain(|tracker| tracker.strong_count() > 0);
+        connections.push(Arc::downgrade(&conn.tracker));
+        Ok(conn)
+    }
+    async fn try_recycle(
+        &self,
+        conn: &mut RedisConnection,
+        health_check: bool,
+    ) -> RecycleResult<RedisError> {
+        if conn.tracker.killed.load(Ordering::Relaxed) {
+            return Err(RecycleError::Message("Connection killed".to_string()));
+        }
+        if conn.generation != self.0.generation.load(Ordering::Acquire) {
+            return Err(RecycleError::Message(
+                "Connection created before the pool was rolled or reconfigured".to_string(),
+            ));
+        }
+        if let Some(pending) = conn.state.pending() {
+            return Err(RecycleError::Message(format!(
+                "Connection left {}",
+                pending
+            )));
+        }
+        if conn.state.watching && !conn.broken {
+            redis::cmd("UNWATCH").query_async::<_, ()>(conn).await?;
+            conn.state.watching = false;
+        }
+        let manager = self.0.current();
+        let recycle = async {
+            manager.recycle_checked(conn, health_check).await?;
+            self.migrate(conn).await?;
+            Ok(())
+        };
+        match self.0.timeouts.recycle {
+            Some(timeout) => self
+                .0
+                .runtime()
+                .timeout(timeout, recycle)
+                .await
+                .unwrap_or_else(|_| {
+                    Err(RecycleError::Message(
+                        "Connection recycle timed out".to_string(),
+                    ))
+                }),
+            None => recycle.await,
+        }
+    }
+
+    /// Recycle a connection handed out again, running the connection check only if
+    /// `health_check` is set
+    async fn recycle_checked(
+        &self,
+        conn: &mut RedisConnection,
+        health_check: bool,
+    ) -> RecycleResult<RedisError> {
+        let result = if conn.recycling {
+            Err(RecycleError::Message(
+                "Connection recycle was interrupted".to_string(),
+            ))
+        } else {
+            conn.recycling = true;
+            let result = match self.try_recycle(conn, health_check).await {
+                Err(RecycleError::Message(message))
+                    if message == CONNECTION_EXPIRED && self.0.current().use_stale() =>
+                {
+                    // renew the connection, keeping the expired one if that fails
+                    if let Ok(renewed) = self.create().await {
+                        *conn = renewed;
+                    }
+                    Ok(())
+                }
+                result => result,
+            };
+            conn.recycling = false;
+            result
+        };
+        if result.is_err() {
+            self.0.recycle_failures.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(observer) = &self.0.current().recycle_observer {
+            let outcome = match &result {
+                Ok(()) => RecycleOutcome::Kept,
+                Err(RecycleError::Message(message)) => RecycleOutcome::Discarded(message.clone()),
+                Err(RecycleError::Backend(err)) => RecycleOutcome::Discarded(err.to_string()),
+            };
+            observer(conn, outcome);
+        }
+        #[cfg(feature = "log")]
+        match &result {
+            Ok(()) => log::trace!(
+                "recycled redis connection {}{}",
+                conn.tracker.id,
+                OfPool(conn.pool_name.as_deref())
+            ),
+            Err(err) => log::debug!(
+                "discarding redis connection {}{}: {}",
+                conn.tracker.id,
+                OfPool(conn.pool_name.as_deref()),
+                err
+            ),
+        }
+        result
+    }
+
+    /// Run the migrations registered since the connection was created or last migrated
+    async fn migrate(&self, conn: &mut RedisConnection) -> RedisResult<()> {
+        let pending: Vec<_> = {
+            let migrations = self.0.migrations.lock().unwrap();
+            let run = conn.tracker.migrations.load(Ordering::Relaxed);
+            migrations.pending[run - migrations.pruned..].to_vec()
+        };
+        if pending.is_empty() {
+            return Ok(());
+        }
+        for migration in pending {
+            migration(conn).await?;
+            conn.tracker.migrations.fetch_add(1, Ordering::Relaxed);
+        }
+        self.0.prune_migrations();
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Manager<RedisConnection, RedisError> for ManagerProxy {
+    async fn create(&self) -> Result<RedisConnection, RedisError> {
+        let result = self.try_create().await;
+        if result.is_ok() {
+            self.0.created.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(transitions) = &self.0.current().health_transitions {
+            transitions.record(result.is_ok());
+        }
+        #[cfg(feature = "log")]
+        match &result {
+            Ok(conn) => log::debug!(
+                "created redis connection {}{}: {:?}",
+                conn.tracker.id,
+                OfPool(self.0.name.as_deref()),
+                conn.connect_timings
+            ),
+            Err(err) => log::warn!(
+                "failed to create a redis connection{}: {}",
+                OfPool(self.0.name.as_deref()),
+                err
+            ),
+        }
+        result
+    }
+    async fn recycle(&self, conn: &mut RedisConnection) -> RecycleResult<RedisError> {
+        conn.returned = true;
+        Ok(())
+    }
+}
+
+impl RedisPool {
+    /// Create a new pool of at most `max_size` connections created by the given `manager`.
+    pub fn new(manager: RedisConnectionManager, max_size: usize) -> Self {
+        Self::from_config(manager, PoolConfig::new(max_size))
+    }
+
+    /// Create a new pool using the given deadpool `config`.
+    pub fn from_config(manager: RedisConnectionManager, config: PoolConfig) -> Self {
+        Self::build(
+            manager,
+            config,
+            None,
+            None,
+            None,
+            None,
+            #[cfg(feature = "local-cache")]
+            None,
+            #[cfg(feature = "log")]
+            None,
+        )
+    }
+
+    /// Create a builder of a pool of connections created by the given `manager`.
+    pub fn builder(manager: RedisConnectionManager) -> RedisPoolBuilder {
+        RedisPoolBuilder::new(Ok(manager))
+    }
+
+    /// Create a builder of a pool of connections to the Redis server at `url`, see
+    /// [`RedisConnectionManager::from_url`] for the parameters.
+    ///
+    /// An invalid url is reported by [`RedisPoolBuilder::build`].
+    pub fn builder_from_url(
+        url: &str,
+        check_on_recycle: bool,
+        connection_ttl: Option<Ttl>,
+    ) -> RedisPoolBuilder {
+        RedisPoolBuilder::new(RedisConnectionManager::from_url(
+            url,
+            check_on_recycle,
+            connection_ttl,
+        ))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn build(
+        manager: RedisConnectionManager,
+        config: PoolConfig,
+        runtime: Option<Runtime>,
+        max_size_limit: Option<usize>,
+        name: Option<String>,
+        thread_affinity: Option<usize>,
+        #[cfg(feature = "local-cache")] local_cache: Option<usize>,
+        #[cfg(feature = "log")] max_borrow_duration: Option<Duration>,
+    ) -> Self {
+        // deadpool is sized for the largest size, the capacity enforcing the current one
+        let capacity =
+            max_size_limit.map(|limit| Capacity::new(config.max_size.min(limit), limit.max(1)));
+        let deadpool_size = capacity
+            .as_ref()
+            .map_or(config.max_size, |capacity| capacity.limit);
+        let name: Option<Arc<str>> = name.map(Arc::from);
+        let shared = Arc::new(PoolShared {
+            manager: RwLock::new(Arc::new(manager)),
+            generation: AtomicU64::new(0),
+            connections: Mutex::new(Vec::new()),
+            pending_gets: PendingGets::default(),
+            acquire_stats: Mutex::new(AcquireStats::default()),
+            timeouts: config.timeouts.clone(),
+            runtime,
+            capacity,
+            gate: Default::default(),
+            affinity: thread_affinity.map(Affinity::new),
+            #[cfg(feature = "local-cache")]
+            local_cache: local_cache.map(LocalCache::new),
+            migrations: Default::default(),
+            #[cfg(feature = "prometheus")]
+            operations: OperationMetrics::new(name.as_deref()),
+            name,
+            multiplexed: Default::default(),
+            #[cfg(feature = "log")]
+            max_borrow_duration,
+            created: AtomicU64::new(0),
+            recycle_failures: AtomicU64::new(0),
+        });
+        Self {
+            pool: Pool::from_config(ManagerProxy(shared.clone()), PoolConfig::new(deadpool_size)),
+            shared,
+        }
+    }
+
+    /// Retrieve a connection from the pool or wait for one to become available.
+    ///
+    /// The timeouts configured with [`RedisPoolBuilder::timeouts`] apply.
+    ///
+    /// With the `tracing` feature, the acquisition runs in a `redis_pool_get` span and
+    /// ends with an event, both holding the source location of the caller in their
+    /// `caller` field, to find out which call sites starve the pool; the same goes for the
+    /// other `get` methods.
+    ///
+    /// ```rust
+    /// # #[cfg(all(feature = "fake", feature = "tracing"))]
+    /// # async_std::task::block_on(async {
+    /// use std::{
+    ///     fmt::Debug,
+    ///     sync::{Arc, Mutex},
+    /// };
+    /// use tracing::{
+    ///     field::{Field, Visit},
+    ///     span, Event, Metadata, Subscriber,
+    /// };
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// /// Collects the `caller` fields of the spans and of the events
+    /// #[derive(Clone, Default)]
+    /// struct Callers(Arc<Mutex<Vec<String>>>);
+    ///
+    /// impl Visit for Callers {
+    ///     fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+    ///         if field.name() == "caller" {
+    ///             self.0.lock().unwrap().push(format!("{:?}", value));
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// impl Subscriber for Callers {
+    ///     fn enabled(&self, _: &Metadata<'_>) -> bool {
+    ///         true
+    ///     }
+    ///     fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+    ///         span.record(&mut self.clone());
+    ///         span::Id::from_u64(1)
+    ///     }
+    ///     fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+    ///     fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+    ///     fn event(&self, event: &Event<'_>) {
+    ///         event.record(&mut self.clone());
+    ///     }
+    ///     fn enter(&self, _: &span::Id) {}
+    ///     fn exit(&self, _: &span::Id) {}
+    /// }
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 5);
+    /// let callers = Callers::default();
+    /// let _guard = tracing::subscriber::set_default(callers.clone());
+    ///
+    /// let (con, line) = (pool.get().await?, line!());
+    /// let call_site = format!("{}:{}:", file!(), line);
+    /// let recorded = callers.0.lock().unwrap().clone();
+    /// // the span, then the event
+    /// assert_eq!(recorded.len(), 2);
+    /// assert!(recorded.iter().all(|caller| caller.starts_with(&call_site)));
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    #[track_caller]
+    pub fn get(
+        &self,
+    ) -> impl Future<Output = Result<PooledConnection, PoolError<RedisError>>> + '_ {
+        self.get_prioritized(
+            &self.shared.timeouts,
+            Priority::Normal,
+            true,
+            Location::caller(),
+        )
+    }
+
+    /// Retrieve a connection like [`get`](Self::get), without running the connection
+    /// check enabled by `check_on_recycle` if an idle connection is handed out.
+    ///
+    /// This saves a round-trip when the connections are known to be fine, e.g. on a hot
+    /// path right after another successful acquisition. The connection is still discarded
+    /// if expired (ttl), broken or evicted by its error rate, and the recycle hooks run.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::sync::{
+    ///     atomic::{AtomicUsize, Ordering},
+    ///     Arc,
+    /// };
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// // count the connection checks (`EXISTS`)
+    /// let checks = Arc::new(AtomicUsize::new(0));
+    /// let counted = checks.clone();
+    /// let manager = FakeRedisConnectionManager::new(true, None)?.with_audit(move |command, _| {
+    ///     if command == "EXISTS" {
+    ///         counted.fetch_add(1, Ordering::Relaxed);
+    ///     }
+    /// });
+    /// let pool = RedisPool::new(manager, 2);
+    /// pool.warm_up(2, 2).await?;
+    ///
+    /// let (unchecked, checked) = futures_util::join!(pool.get_unchecked(), pool.get());
+    /// assert_ne!(unchecked?.id(), checked?.id());
+    /// assert_eq!(checks.load(Ordering::Relaxed), 1);
+    /// for _ in 0..3 {
+    ///     pool.get_unchecked().await?;
+    /// }
+    /// assert_eq!(checks.load(Ordering::Relaxed), 1);
+    /// pool.get().await?;
+    /// assert_eq!(checks.load(Ordering::Relaxed), 2);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    #[track_caller]
+    pub fn get_unchecked(
+        &self,
+    ) -> impl Future<Output = Result<PooledConnection, PoolError<RedisError>>> + '_ {
+        self.get_prioritized(
+            &self.shared.timeouts,
+            Priority::Normal,
+            false,
+            Location::caller(),
+        )
+    }
+
+    /// Retrieve a connection from the pool and do not wait if there is currently
+    /// no connection available and the maximum pool size has been reached.
+    #[track_caller]
+    pub fn try_get(
+        &self,
+    ) -> impl Future<Output = Result<PooledConnection, PoolError<RedisError>>> + '_ {
+        let location = Location::caller();
+        async move {
+            let mut timeouts = self.shared.timeouts.clone();
+            timeouts.wait = Some(Duration::from_secs(0));
+            self.get_prioritized(&timeouts, Priority::Normal, true, location)
+                .await
+        }
+    }
+
+    /// Retrieve a connection, served before the callers of lower priority when the pool
+    /// is contended.
+    ///
+    /// While no connection is available, callers are queued by priority, then in arrival
+    /// order; `get()` has the `Priority::Normal` priority. The first caller queued waits
+    /// for the next connection given back whatever its priority: the queue is ordered
+    /// behind it. `try_get()` never waits so it is not queued. The timeouts configured with [`RedisPoolBuilder::timeouts`] apply.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::{sync::Mutex, time::Duration};
+    /// use futures_util::future;
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{FakeRedisConnectionManager, Priority, RedisPool};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 1);
+    /// let held = pool.get().await?;
+    /// let served = Mutex::new(Vec::new());
+    /// let caller = |name, priority| {
+    ///     let (pool, served) = (&pool, &served);
+    ///     async move {
+    ///         let mut con = pool.get_with_priority(priority).await?;
+    ///         served.lock().unwrap().push(name);
+    ///         con.set::<_, _, ()>(name, "value").await?;
+    ///         Ok::<_, Box<dyn std::error::Error>>(())
+    ///     }
+    /// };
+    /// // the reads are queued before the write
+    /// let callers = vec![
+    ///     caller("read-1", Priority::Low),
+    ///     caller("read-2", Priority::Low),
+    ///     caller("read-3", Priority::Low),
+    ///     caller("write", Priority::High),
+    /// ];
+    /// let release = async move {
+    ///     async_std::task::sleep(Duration::from_millis(50)).await;
+    ///     drop(held);
+    /// };
+    /// let (results, ()) = future::join(future::join_all(callers), release).await;
+    /// for result in results {
+    ///     result?;
+    /// }
+    /// // the first read was already waiting for the connection
+    /// assert_eq!(*served.lock().unwrap(), ["read-1", "write", "read-2", "read-3"]);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    #[track_caller]
+    pub fn get_with_priority(
+        &self,
+        priority: Priority,
+    ) -> impl Future<Output = Result<PooledConnection, PoolError<RedisError>>> + '_ {
+        self.get_prioritized(&self.shared.timeouts, priority, true, Location::caller())
+    }
+
+    /// Retrieve a connection using a different wait timeout than the one configured.
+    ///
+    /// The `wait` timeout bounds the whole acquisition, including the creation or the
+    /// recycling of the connection; those are still bounded by the `create` & `recycle`
+    /// timeouts of the pool.
+    #[track_caller]
+    pub fn timeout_get<'a>(
+        &'a self,
+        timeouts: &'a Timeouts,
+    ) -> impl Future<Output = Result<PooledConnection, PoolError<RedisError>>> + 'a {
+        self.get_prioritized(timeouts, Priority::Normal, true, Location::caller())
+    }
+
+    /// Retrieve a connection for the caller at `_location`, traced with the `tracing`
+    /// feature; the connection check is run on recycle only if `health_check` is set
+    async fn get_prioritized(
+        &self,
+        timeouts: &Timeouts,
+        priority: Priority,
+        health_check: bool,
+        _location: &'static Location<'static>,
+    ) -> Result<PooledConnection, PoolError<RedisError>> {
+        #[cfg(feature = "tracing")]
+        {
+            use tracing::Instrument;
+
+            let span = tracing::debug_span!("redis_pool_get", caller = %_location);
+            async {
+                let started = Instant::now();
+                let acquired = self.get_untraced(timeouts, priority, health_check).await;
+                match &acquired {
+                    Ok(con) => tracing::debug!(
+                        caller = %_location,
+                        id = con.id(),
+                        elapsed = ?started.elapsed(),
+                        "redis connection acquired"
+                    ),
+                    Err(err) => tracing::debug!(
+                        caller = %_location,
+                        elapsed = ?started.elapsed(),
+                        error = %err,
+                        "redis connection not acquired"
+                    ),
+                }
+                acquired
+            }
+            .instrument(span)
+            .await
+        }
+        #[cfg(not(feature = "tracing"))]
+        self.get_untraced(timeouts, priority, health_check).await
+    }
+
+    async fn get_untraced(
+        &self,
+        timeouts: &Timeouts,
+        priority: Priority,
+        health_check: bool,
+    ) -> Result<PooledConnection, PoolError<RedisError>> {
+        if let Some(remaining) = self.poison().and_then(|poison| poison.remaining()) {
+            return Err(PoolError::Backend(errors::pool_poisoned(remaining)));
+        }
+        let started = Instant::now();
+        // a connection parked for this thread is handed out first, any parked connection
+        // when the pool has no idle one
+        let _acquiring = match &self.shared.affinity {
+            Some(affinity) => loop {
+                match affinity.take(self.pool.status().available > 0) {
+                    Ok((mut object, permit)) => {
+                        let manager = ManagerProxy(self.shared.clone());
+                        if Box::pin(manager.recycle_checked(&mut object, health_check))
+                            .await
+                            .is_ok()
+                        {
+                            self.shared
+                                .acquire_stats
+                                .lock()
+                                .unwrap()
+                                .record(started.elapsed(), 0);
+                            return self.checkout(object, permit).await;
+                        }
+                        drop(Object::take(object));
+                    }
+                    Err(acquiring) => break acquiring,
+                }
+            },
+            None => None,
+        };
+        // callers waiting once this one is queued, if it has to wait
+        let waiting = self.shared.gate.waiting() as isize
+            + match &self.shared.capacity {
+                Some(capacity) if capacity.exhausted() => capacity.waiting() as isize + 1,
+                Some(_) => 0,
+                None => {
+                    // a caller creating a connection does not wait for another one
+                    let status = self.pool.status();
+                    if status.available > 0 || status.size < status.max_size {
+                        0
+                    } else {
+                        1 - status.available
+                    }
+                }
+            };
+        let runtime = self.shared.runtime();
+        if timeouts.wait.is_some()
+            || self.shared.timeouts.create.is_some()
+            || self.shared.timeouts.recycle.is_some()
+        {
+            runtime.check().map_err(PoolError::Backend)?;
+        }
+        let object = match timeouts.wait {
+            Some(wait) if wait.as_nanos() == 0 => match &self.shared.capacity {
+                Some(capacity) => match capacity.try_acquire() {
+                    Some(permit) => self
+                        .try_take(health_check)
+                        .await
+                        .map(|object| (object, Some(permit))),
+                    None => Err(PoolError::Timeout(TimeoutType::Wait)),
+                },
+                None => self
+                    .try_take(health_check)
+                    .await
+                    .map(|object| (object, None)),
+            },
+            Some(wait) => runtime
+                .timeout(wait, self.acquire(priority, health_check))
+                .await
+                .unwrap_or(Err(PoolError::Timeout(TimeoutType::Wait))),
+            None => self.acquire(priority, health_check).await,
+        };
+        match object {
+            Ok((object, permit)) => {
+                self.shared
+                    .acquire_stats
+                    .lock()
+                    .unwrap()
+                    .record(started.elapsed(), waiting as usize);
+                self.checkout(object, permit).await
+            }
+            Err(PoolError::Backend(err)) if errors::is_create_timeout(&err) => {
+                Err(PoolError::Timeout(TimeoutType::Create))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Whether callers wait for a connection
+    fn has_waiters(&self) -> bool {
+        self.pool.status().available < 0
+            || self.shared.gate.waiting() > 0
+            || matches!(&self.shared.capacity, Some(capacity) if capacity.waiting() > 0)
+    }
+
+    /// Whether a caller would have to wait for a connection, neither idle nor creatable
+    fn contended(&self) -> bool {
+        match &self.shared.capacity {
+            Some(capacity) => capacity.exhausted(),
+            None => {
+                let status = self.pool.status();
+                status.available <= 0 && status.size >= status.max_size
+            }
+        }
+    }
+
+    /// Wait for the turn when the pool is contended, then for a slot of a resizable pool,
+    /// if any, then for a connection
+    async fn acquire(
+        &self,
+        priority: Priority,
+        health_check: bool,
+    ) -> Result<
+        (
+            Object<RedisConnection, RedisError>,
+            Option<OwnedSemaphorePermit>,
+        ),
+        PoolError<RedisError>,
+    > {
+        let _turn = if self.shared.gate.busy() || self.contended() {
+            Some(self.shared.gate.acquire(priority).await)
+        } else {
+            None
+        };
+        let permit = match &self.shared.capacity {
+            Some(capacity) => Some(capacity.acquire().await),
+            None => None,
+        };
+        loop {
+            if let Some(object) = self.recycled(self.pool.get().await?, health_check).await {
+                return Ok((object, permit));
+            }
+        }
+    }
+
+    /// Take an idle connection, or create one, without waiting
+    async fn try_take(
+        &self,
+        health_check: bool,
+    ) -> Result<Object<RedisConnection, RedisError>, PoolError<RedisError>> {
+        loop {
+            if let Some(object) = self
+                .recycled(self.pool.try_get().await?, health_check)
+                .await
+            {
+                return Ok(object);
+            }
+        }
+    }
+
+    /// Recycle the connection handed out by deadpool unless it was just created, running
+    /// the connection check only if `health_check` is set; `None` once it is dropped as
+    /// it can not be reused.
+    async fn recycled(
+        &self,
+        mut object: Object<RedisConnection, RedisError>,
+        health_check: bool,
+    ) -> Option<Object<RedisConnection, RedisError>> {
+        if object.returned {
+            let manager = ManagerProxy(self.shared.clone());
+            // boxed as deadpool did, not to inflate the futures of the acquisitions
+            if Box::pin(manager.recycle_checked(&mut object, health_check))
+                .await
+                .is_err()
+            {
+                drop(Object::take(object));
+                return None;
+            }
+            object.returned = false;
+        }
+        Some(object)
+    }
+
+    async fn checkout(
+        &self,
+        object: Object<RedisConnection, RedisError>,
+        permit: Option<OwnedSemaphorePermit>,
+    ) -> Result<PooledConnection, PoolError<RedisError>> {
+        let tracker = object.tracker.clone();
+        tracker.uses.fetch_add(1, Ordering::Relaxed);
+        tracker.in_use.store(true, Ordering::Relaxed);
+        #[cfg(feature = "log")]
+        self.watch_borrow(&tracker);
+        let mut con = PooledConnection {
+            object: Some(object),
+            _in_use: InUseGuard(tracker),
+            permit,
+            affinity: self.shared.affinity.as_ref().map(|_| self.clone()),
+        };
+        let manager = self.shared.current();
+        if let Err(err) = hooks::run(&manager.hooks.on_checkout, &mut con).await {
+            con.broken = true;
+            return Err(PoolError::Backend(err));
+        }
+        Ok(con)
+    }
+
+    /// Warn if the connection tracked by `tracker`, just handed out, is still in use once
+    /// the max borrow duration elapsed
+    #[cfg(feature = "log")]
+    fn watch_borrow(&self, tracker: &Arc<Tracker>) {
+        let duration = match self.shared.max_borrow_duration {
+            Some(duration) => duration,
+            None => return,
+        };
+        let uses = tracker.uses.load(Ordering::Relaxed);
+        let borrowed = Arc::downgrade(tracker);
+        let pool_name = self.shared.name.clone();
+        let runtime = self.shared.runtime();
+        runtime.spawn(async move {
+            runtime.sleep(duration).await;
+            let borrowed = match borrowed.upgrade() {
+                Some(tracker) => tracker,
+                None => return,
+            };
+            // still in use, and not handed out again since
+            if borrowed.in_use.load(Ordering::Relaxed)
+                && borrowed.uses.load(Ordering::Relaxed) == uses
+            {
+                log::warn!(
+                    "redis connection {}{} held for more than {:?}",
+                    borrowed.id,
+                    OfPool(pool_name.as_deref()),
+                    duration
+                );
+            }
+        });
+    }
+
+    /// Change the maximum number of connections of the pool, up to the limit set with
+    /// [`RedisPoolBuilder::max_size_limit`]; returns the new maximum size.
+    ///
+    /// Growing takes effect immediately. When shrinking, this returns once the
+    /// connections in use beyond the new size have been given back; the idle connections
+    /// in excess are then closed. Without a limit, the pool can not be resized and its
+    /// maximum size is returned.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::Duration;
+    /// use futures_util::future::{self, Either};
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool, Runtime};
+    ///
+    /// let pool = RedisPool::builder(FakeRedisConnectionManager::new(true, None)?)
+    ///     .max_size(2)
+    ///     .max_size_limit(4)
+    ///     .with_runtime(Runtime::AsyncStd)
+    ///     .build()?;
+    /// let mut connections = vec![pool.get().await?, pool.get().await?];
+    /// assert!(pool.try_get().await.is_err());
+    ///
+    /// assert_eq!(pool.resize(8).await, 4);
+    /// connections.push(pool.try_get().await?);
+    /// connections.push(pool.try_get().await?);
+    /// assert!(pool.try_get().await.is_err());
+    /// assert_eq!(pool.stats().max_size, 4);
+    ///
+    /// // shrinking waits for the connections in use beyond the new size
+    /// let shrink = Box::pin(pool.resize(1));
+    /// let timer = Box::pin(async_std::task::sleep(Duration::from_millis(50)));
+    /// let shrink = match future::select(shrink, timer).await {
+    ///     Either::Right((_, shrink)) => shrink,
+    ///     Either::Left(_) => panic!("shrunk while the connections are in use"),
+    /// };
+    /// connections.truncate(1);
+    /// assert_eq!(shrink.await, 1);
+    /// assert!(pool.try_get().await.is_err());
+    /// drop(connections);
+    /// let _con = pool.try_get().await?;
+    /// assert!(pool.try_get().await.is_err());
+    /// assert_eq!(pool.stats().max_size, 1);
+    /// assert_eq!(pool.stats().size, 1);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn resize(&self, max_size: usize) -> usize {
+        let capacity = match &self.shared.capacity {
+            Some(capacity) => capacity,
+            None => return self.pool.status().max_size,
+        };
+        let max_size = capacity.resize(max_size).await;
+        loop {
+            let status = self.pool.status();
+            if status.size <= max_size || status.available <= 0 {
+                break;
+            }
+            match self.pool.try_get().await {
+                Ok(object) => drop(Object::take(object)),
+                Err(_) => break,
+            }
+        }
+        max_size
+    }
+
+    /// Open `count` connections ahead of their use, creating up to `concurrency` of them
+    /// at once.
+    ///
+    /// The connections are acquired together then given back to the pool, idle; existing
+    /// idle connections are recycled and count towards `count`, which is capped to the
+    /// maximum size of the pool. The first failure stops the warm up and is returned, the
+    /// connections created until then are kept. The timeouts of the pool apply to each
+    /// acquisition.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::{
+    ///     sync::atomic::{AtomicUsize, Ordering},
+    ///     time::Duration,
+    /// };
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// // the creations in progress, and the most of them at once
+    /// static CREATING: AtomicUsize = AtomicUsize::new(0);
+    /// static MOST: AtomicUsize = AtomicUsize::new(0);
+    /// let manager = FakeRedisConnectionManager::new(true, None)?.with_post_create(|_| {
+    ///     Box::pin(async {
+    ///         let creating = CREATING.fetch_add(1, Ordering::SeqCst) + 1;
+    ///         MOST.fetch_max(creating, Ordering::SeqCst);
+    ///         async_std::task::sleep(Duration::from_millis(20)).await;
+    ///         CREATING.fetch_sub(1, Ordering::SeqCst);
+    ///         Ok(())
+    ///     })
+    /// });
+    /// let pool = RedisPool::new(manager, 10);
+    /// pool.warm_up(10, 3).await?;
+    /// assert_eq!(pool.stats().size, 10);
+    /// assert_eq!(pool.stats().available, 10);
+    /// assert_eq!(MOST.load(Ordering::SeqCst), 3);
+    ///
+    /// // idle connections count, the count is capped to the maximum size
+    /// pool.warm_up(20, 20).await?;
+    /// assert_eq!(pool.stats().size, 10);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn warm_up(
+        &self,
+        count: usize,
+        concurrency: usize,
+    ) -> Result<(), PoolError<RedisError>> {
+        let count = count.min(self.stats().max_size);
+        let connections = stream::iter(0..count)
+            .map(|_| self.get())
+            .buffer_unordered(concurrency.max(1))
+            .try_collect::<Vec<_>>()
+            .await?;
+        drop(connections);
+        Ok(())
+    }
+
+    /// Open `count` connections ahead of their use like [`warm_up`](Self::warm_up),
+    /// running `validate` on each of them, e.g. to check that a critical script can be
+    /// run, detecting permission problems at startup.
+    ///
+    /// A connection failing its validation is closed and its error is reported; failures
+    /// to acquire a connection are reported too, without stopping the warm up.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use redis::ErrorKind;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 4);
+    /// let validations = AtomicUsize::new(0);
+    /// let report = pool
+    ///     .warm_up_validated(4, 4, |con| {
+    ///         // one connection out of two is denied the script
+    ///         let denied = validations.fetch_add(1, Ordering::Relaxed) % 2 == 1;
+    ///         Box::pin(async move {
+    ///             if denied {
+    ///                 return Err((ErrorKind::ExtensionError, "NOPERM", "no script".into()).into());
+    ///             }
+    ///             redis::cmd("PING").query_async(con).await
+    ///         })
+    ///     })
+    ///     .await;
+    /// assert_eq!(report.ready, 2);
+    /// assert_eq!(report.failures.len(), 2);
+    /// // the connections which failed their validation are closed
+    /// assert_eq!(pool.stats().size, 2);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn warm_up_validated<F>(
+        &self,
+        count: usize,
+        concurrency: usize,
+        validate: F,
+    ) -> WarmUpReport
+    where
+        F: for<'a> Fn(&'a mut RedisConnection) -> RedisFuture<'a, ()> + Sync,
+    {
+        let count = count.min(self.stats().max_size);
+        let validate = &validate;
+        let outcomes = stream::iter(0..count)
+            .map(|_| async move {
+                let mut con = self.get().await?;
+                match validate(&mut con).await {
+                    Ok(()) => Ok(con),
+                    Err(err) => {
+                        drop(PooledConnection::take(con));
+                        Err(PoolError::Backend(err))
+                    }
+                }
+            })
+            .buffer_unordered(concurrency.max(1))
+            .collect::<Vec<_>>()
+            .await;
+        let mut report = WarmUpReport::default();
+        for outcome in outcomes {
+            match outcome {
+                Ok(_) => report.ready += 1,
+                Err(err) => report.failures.push(err),
+            }
+        }
+        report
+    }
+
+    /// List the connections currently managed by the pool.
+    ///
+    /// Connections which have been taken out of the pool with
+    /// [`PooledConnection::take`] are not listed.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis_async_pool::{
+    ///     ConnectionState, FakeRedisConnectionManager, PooledConnection, RedisPool,
+    /// };
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 5);
+    /// let (used, idle) = (pool.get().await?, pool.get().await?);
+    /// let (used_id, idle_id) = (used.id(), idle.id());
+    /// drop(idle);
+    /// let taken = pool.get().await?;
+    /// assert_eq!(taken.id(), idle_id);
+    /// let taken = PooledConnection::take(taken);
+    /// let idle = pool.get().await?;
+    /// let idle_id = idle.id();
+    /// drop(idle);
+    ///
+    /// let mut snapshot = pool.connections_snapshot();
+    /// snapshot.sort_by_key(|info| info.id);
+    /// let snapshot: Vec<_> = snapshot
+    ///     .iter()
+    ///     .map(|info| (info.id, info.uses, info.state))
+    ///     .collect();
+    /// assert_eq!(
+    ///     snapshot,
+    ///     [(used_id, 1, ConnectionState::InUse), (idle_id, 1, ConnectionState::Idle)]
+    /// );
+    /// # drop(taken);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn connections_snapshot(&self) -> Vec<ConnectionInfo> {
+        let mut connections = self.shared.connections.lock().unwrap();
+        connections.retain(|tracker| match tracker.upgrade() {
+            Some(tracker) => !tracker.taken.load(Ordering::Relaxed),
+            None => false,
+        });
+        connections
+            .iter()
+            .filter_map(Weak::upgrade)
+            .map(|tracker| ConnectionInfo {
+                id: tracker.id,
+                age: tracker.created_at.elapsed(),
+                uses: tracker.uses.load(Ordering::Relaxed),
+                state: if tracker.in_use.load(Ordering::Relaxed) {
+                    ConnectionState::InUse
+                } else {
+                    ConnectionState::Idle
+                },
+            })
+            .collect()
+    }
+
+    /// Replace the manager used to create and recycle connections.
+    ///
+    /// This allows to migrate the pool to a new Redis endpoint without rebuilding it:
+    /// new connections are created by `manager`, idle connections created by the
+    /// previous manager are dropped on their next checkout. Connections in use are not
+    /// interrupted, they are discarded once returned to the pool and checked out again.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 2);
+    /// let mut in_use = pool.get().await?;
+    /// in_use.set::<_, _, ()>("key", "old").await?;
+    /// drop(pool.get().await?);
+    ///
+    /// // another server, which does not have the key
+    /// pool.reconfigure(FakeRedisConnectionManager::new(true, None)?);
+    /// let mut con = pool.get().await?;
+    /// assert_eq!(con.get::<_, Option<String>>("key").await?, None);
+    /// // the connection in use still goes to the previous server
+    /// assert_eq!(in_use.get::<_, String>("key").await?, "old");
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn reconfigure(&self, manager: RedisConnectionManager) {
+        let mut current = self.shared.manager.write().unwrap();
+        *current = Arc::new(manager);
+        self.shared.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Flag every existing connection to be recreated on its next recycle.
+    ///
+    /// Idle connections are dropped on their next checkout and replaced by new ones;
+    /// connections in use are not interrupted, they are discarded once returned to the
+    /// pool and checked out again. This is [`reconfigure`](Self::reconfigure) keeping the
+    /// current manager.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 3);
+    /// pool.warm_up(2, 2).await?;
+    /// let mut in_use = pool.get().await?;
+    /// let rolled: Vec<_> = pool.connections_snapshot().iter().map(|con| con.id).collect();
+    ///
+    /// pool.roll();
+    /// // the connection in use is not interrupted
+    /// in_use.set::<_, _, ()>("key", "value").await?;
+    /// drop(in_use);
+    /// // every connection handed out afterwards is a new one
+    /// let connections = vec![pool.get().await?, pool.get().await?, pool.get().await?];
+    /// assert!(connections.iter().all(|con| !rolled.contains(&con.id())));
+    /// assert_eq!(pool.connections_snapshot().len(), 3);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn roll(&self) {
+        self.shared.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Run `f` on every connection of the pool, e.g. to apply a setting changed at runtime
+    /// to the existing connections; reports the idle connections `f` ran on.
+    ///
+    /// `f` runs right away on the idle connections, and on the connections in use the next
+    /// time they are handed out, after their recycle. A connection on which `f` fails (or
+    /// which fails its recycle) is dropped, the error being reported for the idle ones.
+    /// Connections created afterwards are expected to be configured by the manager, e.g.
+    /// with a post create hook: `f` is not run on them.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 3);
+    /// pool.warm_up(3, 3).await?;
+    /// let in_use = pool.get().await?;
+    ///
+    /// let migrated = pool
+    ///     .for_each_connection(|con| {
+    ///         let marker = format!("migrated:{}", con.id());
+    ///         Box::pin(async move { con.set(marker, 1).await })
+    ///     })
+    ///     .await;
+    /// assert_eq!(migrated.migrated, 2);
+    /// assert!(migrated.failures.is_empty());
+    ///
+    /// // the connection in use is migrated once handed out again
+    /// drop(in_use);
+    /// let connections = vec![pool.get().await?, pool.get().await?, pool.get().await?];
+    /// let ids: Vec<_> = connections.iter().map(|con| con.id()).collect();
+    /// drop(connections);
+    /// for id in ids {
+    ///     let marker: Option<u8> = pool.get_key(format!("migrated:{}", id)).await?;
+    ///     assert_eq!(marker, Some(1));
+    /// }
+    ///
+    /// // the connections `f` fails on are dropped, their replacements are not counted
+    /// let failed = pool
+    ///     .for_each_connection(|con| Box::pin(async move { con.incr("counter", "x").await }))
+    ///     .await;
+    /// assert_eq!(failed.migrated, 0);
+    /// assert_eq!(failed.failures.len(), 3);
+    /// assert!(pool.get().await?.get::<_, Option<u8>>("counter").await?.is_none());
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn for_each_connection<F>(&self, f: F) -> MigrationReport
+    where
+        F: for<'a> Fn(&'a mut RedisConnection) -> RedisFuture<'a, ()> + Send + Sync + 'static,
+    {
+        self.shared
+            .migrations
+            .lock()
+            .unwrap()
+            .pending
+            .push(Arc::new(Box::new(f)));
+        // the idle connections are migrated on recycle, held until all are
+        let mut held = Vec::new();
+        let mut report = MigrationReport::default();
+        while self.pool.status().available > 0 {
+            let mut object = match self.pool.try_get().await {
+                Ok(object) => object,
+                Err(_) => break,
+            };
+            if object.returned {
+                let manager = ManagerProxy(self.shared.clone());
+                match Box::pin(manager.recycle_checked(&mut object, false)).await {
+                    Ok(()) => report.migrated += 1,
+                    Err(err) => {
+                        drop(Object::take(object));
+                        report.failures.push(match err {
+                            RecycleError::Backend(err) => err,
+                            RecycleError::Message(message) => errors::recycle_failed(message),
+                        });
+                        continue;
+                    }
+                }
+                object.returned = false;
+            }
+            // a connection just created is already configured
+            held.push(object);
+        }
+        drop(held);
+        self.shared.prune_migrations();
+        report
+    }
+
+    /// Kill the connection `id` (see [`RedisConnection::id`]) on the server side with
+    /// `CLIENT KILL` and evict it from the pool; returns `false` if the pool has no such
+    /// connection.
+    ///
+    /// The command is sent on a connection created for the purpose, out of the pool, so
+    /// that it does not wait for a connection of a saturated pool; a connection already
+    /// gone from the server is not an error. An idle connection is
+    /// dropped right away; commands issued on a connection in use fail from now on, it is
+    /// dropped once returned to the pool and checked out again. Without the server side
+    /// identifier of the connection, queried on creation when enabled with
+    /// [`RedisConnectionManager::with_client_id`], the connection is only evicted.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::Duration;
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{ConnectionState, FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let manager = FakeRedisConnectionManager::new(true, None)?.with_client_id();
+    /// let pool = RedisPool::new(manager, 3);
+    /// pool.warm_up(2, 2).await?;
+    /// let mut in_use = pool.get().await?;
+    /// let in_use_id = in_use.id();
+    /// let snapshot = pool.connections_snapshot();
+    /// let idle = snapshot.iter().find(|con| con.state == ConnectionState::Idle);
+    /// let idle = idle.unwrap().id;
+    ///
+    /// assert!(pool.kill_connection(idle).await?);
+    /// assert!(pool.kill_connection(in_use_id).await?);
+    /// assert!(!pool.kill_connection(idle).await?);
+    /// assert!(in_use.get::<_, Option<u8>>("key").await.is_err());
+    ///
+    /// // the connection in use is dropped once handed out again
+    /// drop(in_use);
+    /// let connections = vec![pool.get().await?, pool.get().await?, pool.get().await?];
+    /// let ids: Vec<_> = connections.iter().map(|con| con.id()).collect();
+    /// assert!(!ids.contains(&idle) && !ids.contains(&in_use_id));
+    /// assert_eq!(pool.connections_snapshot().len(), 3);
+    ///
+    /// // the only connection of a saturated pool
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?.with_client_id(), 1);
+    /// let mut in_use = pool.get().await?;
+    /// let kill = pool.kill_connection(in_use.id());
+    /// assert!(async_std::future::timeout(Duration::from_secs(1), kill).await??);
+    /// assert!(in_use.get::<_, Option<u8>>("key").await.is_err());
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn kill_connection(&self, id: u64) -> Result<bool, PoolError<RedisError>> {
+        let tracker = {
+            let connections = self.shared.connections.lock().unwrap();
+            connections
+                .iter()
+                .filter_map(Weak::upgrade)
+                .find(|tracker| {
+                    tracker.id == id
+                        && !tracker.killed.load(Ordering::Relaxed)
+                        && !tracker.taken.load(Ordering::Relaxed)
+                })
+        };
+        let tracker = match tracker {
+            Some(tracker) => tracker,
+            None => return Ok(false),
+        };
+        // flagged first so that it is not handed out to send the command
+        tracker.killed.store(true, Ordering::Relaxed);
+        if let Some(client_id) = tracker.client_id() {
+            let kill = self
+                .with_fresh_connection(|con| {
+                    Box::pin(async move {
+                        redis::cmd("CLIENT")
+                            .arg("KILL")
+                            .arg("ID")
+                            .arg(client_id)
+                            .query_async(con)
+                            .await
+                    })
+                })
+                .await;
+            match kill {
+                // already gone
+                Err(PoolError::Backend(err))
+                    if err.kind() == ErrorKind::ResponseError
+                        && err.detail() == Some("No such client") => {}
+                kill => kill?,
+            }
+        }
+        if let Some(affinity) = &self.shared.affinity {
+            let killed = affinity.evict(|con| con.tracker.killed.load(Ordering::Relaxed));
+            killed
+                .into_iter()
+                .for_each(|(object, _)| drop(Object::take(object)));
+        }
+        // an idle connection is dropped by its recycle
+        let mut checked_out = Vec::new();
+        while !tracker.in_use.load(Ordering::Relaxed)
+            && Arc::strong_count(&tracker) > 1
+            && self.pool.status().available > 0
+        {
+            match self.try_take(false).await {
+                Ok(object) => checked_out.push(object),
+                Err(_) => break,
+            }
+        }
+        Ok(true)
+    }
+
+    /// Close every idle connection and flag the connections in use to be recreated, so
+    /// the pool starts afresh; returns the number of connections closed.
+    ///
+    /// Unlike closing the pool, it keeps handing out connections: new ones are created on
+    /// demand. Connections in use are not interrupted, they are discarded once returned
+    /// to the pool and checked out again, as with [`roll`](Self::roll).
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 5);
+    /// pool.warm_up(3, 3).await?;
+    /// let ids: Vec<_> = pool.connections_snapshot().iter().map(|con| con.id).collect();
+    ///
+    /// assert_eq!(pool.reset_all().await, 3);
+    /// assert_eq!(pool.stats().size, 0);
+    /// let con = pool.get().await?;
+    /// assert!(!ids.contains(&con.id()));
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn reset_all(&self) -> usize {
+        let mut closed = 0;
+        if let Some(affinity) = &self.shared.affinity {
+            for (object, _) in affinity.evict(|_| true) {
+                drop(Object::take(object));
+                closed += 1;
+            }
+        }
+        // the idle connections are still valid: they are not checked before being closed
+        while self.pool.status().available > 0 {
+            match self.try_take(false).await {
+                Ok(object) => drop(Object::take(object)),
+                Err(_) => break,
+            }
+            closed += 1;
+        }
+        self.roll();
+        closed
+    }
+
+    /// Get a connection and run `f` on it, the whole sequence being bounded by `timeout`.
+    ///
+    /// If the deadline is reached while acquiring the connection, a
+    /// `PoolError::Timeout(TimeoutType::Wait)` is returned. If it is reached while running
+    /// `f`, a `PoolError::Backend` holding a `TimedOut` io error is returned; if a command of
+    /// `f` was still waiting for its reply, the connection is then dropped instead of being
+    /// reused, like any connection whose command is cancelled.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::Duration;
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{
+    ///     deadpool::managed::{PoolError, TimeoutType},
+    ///     FakeRedisConnectionManager, RedisPool,
+    /// };
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 1);
+    /// let value: Option<String> = pool
+    ///     .execute_within(Duration::from_millis(100), |con| {
+    ///         Box::pin(async move { con.get("key").await })
+    ///     })
+    ///     .await?;
+    /// assert_eq!(value, None);
+    ///
+    /// // the pool is exhausted: the deadline is reached while acquiring
+    /// let in_use = pool.get().await?;
+    /// let result = pool
+    ///     .execute_within(Duration::from_millis(20), |con| {
+    ///         Box::pin(async move { con.get::<_, Option<String>>("key").await })
+    ///     })
+    ///     .await;
+    /// assert!(matches!(result, Err(PoolError::Timeout(TimeoutType::Wait))));
+    /// drop(in_use);
+    ///
+    /// // the deadline is reached while running the closure, between two commands: the
+    /// // connection is given back
+    /// let id = pool.get().await?.id();
+    /// let result = pool
+    ///     .execute_within(Duration::from_millis(20), |_con| {
+    ///         Box::pin(async move {
+    ///             async_std::task::sleep(Duration::from_millis(100)).await;
+    ///             Ok(())
+    ///         })
+    ///     })
+    ///     .await;
+    /// assert!(matches!(result, Err(PoolError::Backend(err)) if err.is_timeout()));
+    /// assert_eq!(pool.get().await?.id(), id);
+    ///
+    /// // the deadline is reached while waiting for a reply: the connection is dropped
+    /// let result = pool
+    ///     .execute_within(Duration::from_millis(50), |con| {
+    ///         Box::pin(async move {
+    ///             redis::cmd("CLIENT")
+    ///                 .arg("PAUSE")
+    ///                 .arg(200)
+    ///                 .query_async::<_, ()>(&mut *con)
+    ///                 .await?;
+    ///             con.get::<_, Option<String>>("key").await
+    ///         })
+    ///     })
+    ///     .await;
+    /// assert!(matches!(result, Err(PoolError::Backend(err)) if err.is_timeout()));
+    /// assert_ne!(pool.get().await?.id(), id);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn execute_within<T, F>(
+        &self,
+        timeout: Duration,
+        f: F,
+    ) -> Result<T, PoolError<RedisError>>
+    where
+        F: for<'a> FnOnce(&'a mut RedisConnection) -> RedisFuture<'a, T>,
+    {
+        let runtime = self.shared.runtime();
+        runtime.check().map_err(PoolError::Backend)?;
+        let deadline = Instant::now().checked_add(timeout);
+        let mut con = runtime
+            .timeout(timeout, self.get())
+            .await
+            .map_err(|_| PoolError::Timeout(TimeoutType::Wait))??;
+        let remaining = deadline.map_or(timeout, |deadline| {
+            deadline.saturating_duration_since(Instant::now())
+        });
+        match runtime.timeout(remaining, f(&mut con)).await {
+            Ok(result) => result.map_err(PoolError::Backend),
+            Err(elapsed) => Err(PoolError::Backend(elapsed.into())),
+        }
+    }
+
+    /// Run `f` on a new connection, closed afterwards instead of being given to the pool,
+    /// e.g. for administrative commands (`FLUSHDB`, `CONFIG REWRITE`...) whose effects on
+    /// the connection should not leak to other callers.
+    ///
+    /// The connection is created by the manager of the pool, hooks included, within the
+    /// `create` timeout of the pool. It does not take a slot of the pool.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 5);
+    /// pool.with_fresh_connection(|con| con.set::<_, _, ()>("key", "value"))
+    ///     .await?;
+    /// assert_eq!(pool.stats().size, 0);
+    /// assert_eq!(pool.get_key::<_, String>("key").await?, "value");
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn with_fresh_connection<T, F>(&self, f: F) -> Result<T, PoolError<RedisError>>
+    where
+        F: for<'a> FnOnce(&'a mut RedisConnection) -> RedisFuture<'a, T>,
+    {
+        let manager = self.shared.current();
+        let create = manager.create();
+        let mut con = match self.shared.timeouts.create {
+            Some(timeout) => self
+                .shared
+                .runtime()
+                .timeout(timeout, create)
+                .await
+                .map_err(|_| PoolError::Timeout(TimeoutType::Create))??,
+            None => create.await?,
+        };
+        con.pool_name = self.shared.name.clone();
+        Ok(f(&mut con).await?)
+    }
+
+    /// The name of the pool, set with [`RedisPoolBuilder::with_name`]
+    pub fn name(&self) -> Option<&str> {
+        self.shared.name.as_deref()
+    }
+
+    /// Get a snapshot of the pool state.
+    pub fn stats(&self) -> PoolStats {
+        let mut stats = PoolStats::from(self.pool.status());
+        if let Some(capacity) = &self.shared.capacity {
+            stats.max_size = capacity.max_size();
+            stats.waiting = capacity.waiting();
+        }
+        stats.waiting += self.shared.gate.waiting();
+        stats
+    }
+
+    /// Tell whether the Redis server answers a `PING` on a new connection.
+    ///
+    /// The connection is opened out of the pool: it does not take a slot and is not
+    /// affected by the pool saturation, which makes it suitable for readiness probes.
+    /// The `create` timeout of the pool, if any, bounds the check.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisConnectionManager, RedisPool};
+    ///
+    /// // reachable while the only slot of the pool is taken
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 1);
+    /// let _con = pool.get().await?;
+    /// assert!(pool.is_reachable().await);
+    /// assert_eq!(pool.stats().size, 1);
+    ///
+    /// // nothing listens on port 1
+    /// let manager = RedisConnectionManager::from_url("redis://127.0.0.1:1", true, None)?;
+    /// assert!(!RedisPool::new(manager, 1).is_reachable().await);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn is_reachable(&self) -> bool {
+        let manager = self.shared.current();
+        let ping = async move {
+            let mut con = manager.settings.connect(manager.client()).await?;
+            redis::cmd("PING").query_async::<_, String>(&mut con).await
+        };
+        match self.shared.timeouts.create {
+            Some(timeout) => {
+                let runtime = self.shared.runtime();
+                runtime.check().is_ok() && matches!(runtime.timeout(timeout, ping).await, Ok(Ok(_)))
+            }
+            None => ping.await.is_ok(),
+        }
+    }
+
+    /// Tell whether the pool is poisoned, failing fast, see
+    /// [`RedisConnectionManager::with_poison_after`].
+    pub fn is_poisoned(&self) -> bool {
+        matches!(self.poison(), Some(poison) if poison.remaining().is_some())
+    }
+
+    /// End the poisoning of the pool before its cooldown elapsed, see
+    /// [`RedisConnectionManager::with_poison_after`].
+    pub fn clear_poison(&self) {
+        if let Some(poison) = self.poison() {
+            poison.clear();
+        }
+    }
+
+    fn poison(&self) -> Option<Arc<Poison>> {
+        self.shared.current().settings.poison.clone()
+    }
+
+    /// Tell whether the last shared health check succeeded, `true` if the manager has none.
+    ///
+    /// See [`RedisConnectionManager::with_shared_health_check`].
+    pub fn is_healthy(&self) -> bool {
+        match &self.shared.current().shared_health_check {
+            Some(health) => health.is_healthy(),
+            None => true,
+        }
+    }
+
+    /// Get the statistics of the connections acquired since the pool was created.
+    ///
+    /// A high wait time while Redis answers quickly means the pool is too small.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::Duration;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 1);
+    /// let held = pool.get().await?;
+    /// assert_eq!(pool.acquire_stats().max_waiting, 0);
+    ///
+    /// // 3 callers wait for the only connection, held for 50ms
+    /// let waiters = futures_util::future::try_join_all((0..3).map(|_| async {
+    ///     pool.get().await.map(drop)
+    /// }));
+    /// let release = async {
+    ///     async_std::task::sleep(Duration::from_millis(50)).await;
+    ///     drop(held);
+    /// };
+    /// let (waited, ()) = futures_util::join!(waiters, release);
+    /// waited?;
+    ///
+    /// let stats = pool.acquire_stats();
+    /// assert_eq!(stats.acquired, 4);
+    /// assert_eq!(stats.max_waiting, 3);
+    /// assert!(stats.max_wait >= Duration::from_millis(50));
+    /// assert!(stats.mean_wait() < stats.max_wait);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn acquire_stats(&self) -> AcquireStats {
+        *self.shared.acquire_stats.lock().unwrap()
+    }
+
+    /// Produce a stream of pool stats snapshots.
+    ///
+    /// The first snapshot is yielded immediately, then one every `interval`, waited for
+    /// with the timers of the runtime of the pool: the stream ends after the first
+    /// snapshot if they can not be used by the current task. The stream holds a handle to
+    /// the pool, so the pool is kept alive while the stream is.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::Duration;
+    /// use futures_util::StreamExt;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 2);
+    /// let mut status = Box::pin(pool.watch_status(Duration::from_millis(10)));
+    /// assert_eq!(status.next().await.unwrap().size, 0);
+    /// let _con = pool.get().await?;
+    /// let update = status.next().await.unwrap();
+    /// assert_eq!((update.size, update.available), (1, 0));
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn watch_status(&self, interval: Duration) -> impl Stream<Item = PoolStats> {
+        stream::unfold((self.clone(), true), move |(pool, first)| async move {
+            if !first {
+                let runtime = pool.shared.runtime();
+                runtime.check().ok()?;
+                runtime.sleep(interval).await;
+            }
+            Some((pool.stats(), (pool, false)))
+        })
+    }
+}
+
+/// A connection checked out of a [`RedisPool`].
+///
+/// It derefs to [`RedisConnection`]; the connection goes back to the pool when dropped.
+/// The metadata of the connection (id, age, uses, expiry...) is available through
+/// [`RedisConnection::metadata`] and the other methods of `RedisConnection`, while
+/// the commands are issued through the `redis` traits.
+///
+/// ```rust,no_run
+/// # use std::error::Error;
+/// # use redis::AsyncCommands;
+/// # use redis_async_pool::{RedisConnectionManager, RedisPool};
+/// # #[async_std::main]
+/// # async fn main() -> Result<(), Box<dyn Error>> {
+/// # let pool = RedisPool::new(
+/// #     RedisConnectionManager::new(redis::Client::open("redis://localhost:6379")?, true, None),
+/// #     5,
+/// # );
+/// let mut con = pool.get().await?;
+/// con.set::<_, _, ()>("key", "value").await?;
+/// let metadata = con.metadata();
+/// println!("connection {} used {} times", metadata.id, metadata.uses);
+/// # Ok(())
+/// # }
+/// ```
+pub struct PooledConnection {
+    // dropped first, so the connection is back in the pool when the slot is released;
+    // `None` once parked or taken
+    object: Option<Object<RedisConnection, RedisError>>,
+    _in_use: InUseGuard,
+    /// Slot of a resizable pool
+    permit: Option<OwnedSemaphorePermit>,
+    /// Pool parking the connection for the current thread once given back
+    affinity: Option<RedisPool>,
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        let (pool, affinity) = match &self.affinity {
+            Some(pool) => match &pool.shared.affinity {
+                Some(affinity) => (pool, affinity),
+                None => return,
+            },
+            None => return,
+        };
+        if let Some(object) = self.object.take() {
+            let parked = affinity.park((object, self.permit.take()), pool.has_waiters());
+            if let Some((object, permit)) = parked {
+                drop(object);
+                drop(permit);
+            }
+        }
+    }
+}
+
+/// Flags the connection as idle once given back
+struct InUseGuard(Arc<Tracker>);
+
+impl Drop for InUseGuard {
+    fn drop(&mut self) {
+        self.0.in_use.store(false, Ordering::Relaxed);
+    }
+}
+
+impl PooledConnection {
+    /// Give this connection back to the pool, checking it is clean (see
+    /// [`RedisConnection::is_clean`]).
+    ///
+    /// A connection left in a transaction or subscribed to channels makes this method panic
+    /// in debug builds; in release builds it is logged as an error when the `log` feature
+    /// is enabled.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::panic::{catch_unwind, AssertUnwindSafe};
+    /// use redis_async_pool::{FakeRedisConnectionManager, PooledConnection, RedisPool};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 1);
+    /// let con = pool.get().await?;
+    /// let id = con.id();
+    /// PooledConnection::release_checked(con);
+    ///
+    /// // a connection left in a transaction
+    /// let mut con = pool.get().await?;
+    /// assert_eq!(con.id(), id);
+    /// redis::cmd("MULTI").query_async::<_, ()>(&mut *con).await?;
+    /// assert!(!con.is_clean());
+    /// let released = catch_unwind(AssertUnwindSafe(|| PooledConnection::release_checked(con)));
+    /// assert_eq!(released.is_err(), cfg!(debug_assertions));
+    /// // and is not reused
+    /// assert_ne!(pool.get().await?.id(), id);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn release_checked(this: Self) {
+        if let Some(pending) = this.state.pending() {
+            #[cfg(feature = "log")]
+            log::error!(
+                "redis connection {}{} released {}",
+                this.tracker.id,
+                OfPool(this.pool_name.as_deref()),
+                pending
+            );
+            if cfg!(debug_assertions) {
+                panic!("redis connection {} released {}", this.tracker.id, pending);
+            }
+        }
+    }
+
+    /// Take this connection out of the pool permanently. This reduces the size of the pool.
+    pub fn take(mut this: Self) -> RedisConnection {
+        let con = Object::take(this.object.take().expect("connection is held"));
+        con.tracker.taken.store(true, Ordering::Relaxed);
+        con
+    }
+}
+
+impl Deref for PooledConnection {
+    type Target = RedisConnection;
+    fn deref(&self) -> &Self::Target {
+        self.object.as_ref().expect("connection is held")
+    }
+}
+
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.object.as_mut().expect("connection is held")
+    }
+}
+
+impl fmt::Debug for PooledConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.object {
+            Some(object) => fmt::Debug::fmt(&**object, f),
+            None => f.write_str("PooledConnection(released)"),
+        }
+    }
+}
+
+/// Outcome of [`RedisPool::warm_up_validated`]
+#[derive(Debug, Default)]
+pub struct WarmUpReport {
+    /// Number of connections validated, left idle in the pool
+    pub ready: usize,
+    /// Errors of the connections which could not be acquired or failed their validation
+    pub failures: Vec<PoolError<RedisError>>,
+}
+
+/// Outcome of [`RedisPool::for_each_connection`]
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    /// Number of idle connections migrated
+    pub migrated: usize,
+    /// Errors of the idle connections dropped instead of being migrated
+    pub failures: Vec<RedisError>,
+}
+
+/// Metadata of a connection managed by a pool
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    /// Identifier of the connection, see [`RedisConnection::id`]
+    pub id: u64,
+    /// Time elapsed since the connection was created
+    pub age: Duration,
+    /// Number of times the connection has been handed out
+    pub uses: u64,
+    /// Whether the connection is in use
+    pub state: ConnectionState,
+}
+
+/// Whether a connection is in use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// The connection waits in the pool
+    Idle,
+    /// The connection is checked out
+    InUse,
+}
+
+/// Snapshot of the state of a pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    /// The maximum number of connections
+    pub max_size: usize,
+    /// The current number of connections
+    pub size: usize,
+    /// The number of idle connections
+    pub available: usize,
+    /// The number of callers waiting for a connection
+    pub waiting: usize,
+}
+
+/// Statistics of the connections acquired from a pool, see [`RedisPool::acquire_stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AcquireStats {
+    /// The number of connections acquired
+    pub acquired: u64,
+    /// The time spent waiting for those connections, creation & recycling included
+    pub total_wait: Duration,
+    /// The longest time spent waiting for a connection
+    pub max_wait: Duration,
+    /// The highest number of callers waiting for a connection at once
+    pub max_waiting: usize,
+}
+
+impl AcquireStats {
+    fn record(&mut self, wait: Duration, waiting: usize) {
+        self.acquired += 1;
+        self.total_wait += wait;
+        self.max_wait = self.max_wait.max(wait);
+        self.max_waiting = self.max_waiting.max(waiting);
+    }
+
+    /// The average time spent waiting for a connection
+    pub fn mean_wait(&self) -> Duration {
+        if self.acquired == 0 {
+            Duration::default()
+        } else {
+            Duration::from_nanos((self.total_wait.as_nanos() / self.acquired as u128) as u64)
+        }
+    }
+}
+
+impl From<Status> for PoolStats {
+    fn from(status: Status) -> Self {
+        Self {
+            max_size: status.max_size,
+            size: status.size,
+            available: status.available.max(0) as usize,
+            waiting: (-status.available).max(0) as usize,
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::oneshot;
+
+/// Priority of a connection acquisition, see [`RedisPool::get_with_priority`](crate::RedisPool::get_with_priority)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Served after the other callers, e.g. for background reads
+    Low,
+    /// The priority of `get()`
+    Normal,
+    /// Served before the other callers, e.g. for latency critical writes
+    High,
+}
+
+/// Queue of the callers waiting for a connection of a contended pool.
+///
+/// A single caller at a time, holding the turn, waits for a connection within the pool;
+/// the others wait here and are given the turn by priority, then in arrival order.
+#[derive(Default)]
+pub(crate) struct PriorityGate {
+    state: Mutex<GateState>,
+}
+
+#[derive(Default)]
+struct GateState {
+    /// Whether a caller holds the turn
+    busy: bool,
+    waiters: BinaryHeap<Waiter>,
+    /// Arrival order of the next waiter
+    next_seq: u64,
+}
+
+struct Waiter {
+    priority: Priority,
+    seq: u64,
+    turn: oneshot::Sender<()>,
+}
+
+impl Ord for Waiter {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // highest priority, then first arrived, first
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.seq == other.seq
+    }
+}
+
+impl Eq for Waiter {}
+
+/// The turn to wait for a connection within the pool, handed to the next waiter once
+/// dropped
+pub(crate) struct Turn(Arc<PriorityGate>);
+
+impl Drop for Turn {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+/// Hands the turn over if it was given to a waiter which stopped waiting
+struct Waiting {
+    gate: Arc<PriorityGate>,
+    turn: Option<oneshot::Receiver<()>>,
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        if let Some(mut turn) = self.turn.take() {
+            if turn.try_recv().is_ok() {
+                self.gate.release();
+            }
+        }
+    }
+}
+
+impl PriorityGate {
+    /// Number of callers waiting for the turn
+    pub(crate) fn waiting(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        // waiters which stopped waiting are only removed when the turn is handed over
+        state
+            .waiters
+            .iter()
+            .filter(|waiter| !waiter.turn.is_closed())
+            .count()
+    }
+
+    /// Whether a caller holds the turn
+    pub(crate) fn busy(&self) -> bool {
+        self.state.lock().unwrap().busy
+    }
+
+    /// Wait for the turn, served by `priority` then in arrival order
+    pub(crate) async fn acquire(self: &Arc<Self>, priority: Priority) -> Turn {
+        let turn = {
+            let mut state = self.state.lock().unwrap();
+            if !state.busy {
+                state.busy = true;
+                return Turn(self.clone());
+            }
+            let (sender, receiver) = oneshot::channel();
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.waiters.push(Waiter {
+                priority,
+                seq,
+                turn: sender,
+            });
+            receiver
+        };
+        let mut waiting = Waiting {
+            gate: self.clone(),
+            turn: Some(turn),
+        };
+        // the sender is only dropped once the turn was sent
+        let _ = waiting.turn.as_mut().unwrap().await;
+        waiting.turn = None;
+        Turn(self.clone())
+    }
+
+    /// Hand the turn to the next waiter still waiting
+    fn release(&self) {
+        let mut state = self.state.lock().unwrap();
+        while let Some(waiter) = state.waiters.pop() {
+            if waiter.turn.send(()).is_ok() {
+                return;
+            }
+        }
+        state.busy = false;
+    }
+}
@@ -0,0 +1,51 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Token bucket limiting the rate of the commands sent by the connections of a manager.
+///
+/// The bucket holds up to one second of commands. Tokens are reserved ahead: a command
+/// finding the bucket empty takes its tokens anyway and waits for them to be refilled,
+/// so callers are served in arrival order.
+pub(crate) struct RateLimiter {
+    per_second: f64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    /// Negative while commands wait for their tokens
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    /// Number of commands allowed per second
+    pub(crate) fn per_second(&self) -> u32 {
+        self.per_second as u32
+    }
+
+    pub(crate) fn new(per_second: u32) -> Self {
+        Self {
+            per_second: per_second as f64,
+            bucket: Mutex::new(Bucket {
+                tokens: per_second as f64,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Take `commands` tokens, returning how long to wait before sending the commands
+    pub(crate) fn reserve(&self, commands: usize) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        let refill = now.duration_since(bucket.refilled_at).as_secs_f64() * self.per_second;
+        bucket.tokens = (bucket.tokens + refill).min(self.per_second) - commands as f64;
+        bucket.refilled_at = now;
+        if bucket.tokens >= 0.0 {
+            Duration::default()
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / self.per_second)
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use std::time::{Duration, Instant};
+
+use redis::{aio::ConnectionLike, ErrorKind, RedisResult};
+
+use crate::{info, runtime};
+
+/// Delay between two replication offset checks
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Readiness probe run on connections created to a replica.
+pub(crate) struct ReplicaReadiness {
+    pub(crate) master: redis::Client,
+    pub(crate) max_lag: u64,
+    pub(crate) max_wait: Duration,
+}
+
+impl ReplicaReadiness {
+    /// Wait until the replica `con` is connected to has caught up with its master.
+    pub(crate) async fn wait_ready<C>(&self, con: &mut C) -> RedisResult<()>
+    where
+        C: ConnectionLike + Send,
+    {
+        let started = Instant::now();
+        let mut master = self.master.get_async_connection().await?;
+        loop {
+            let master_offset = repl_offset(&mut master, "master_repl_offset").await?;
+            let replica_offset = repl_offset(con, "slave_repl_offset").await?;
+            let lag = master_offset.saturating_sub(replica_offset);
+            if lag <= self.max_lag {
+                return Ok(());
+            }
+            if started.elapsed() >= self.max_wait {
+                return Err((
+                    ErrorKind::ExtensionError,
+                    "Replica is lagging behind its master",
+                    format!("replication lag of {} bytes after {:?}", lag, self.max_wait),
+                )
+                    .into());
+            }
+            runtime::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+async fn repl_offset<C>(con: &mut C, field: &str) -> RedisResult<u64>
+where
+    C: ConnectionLike + Send,
+{
+    let replication: String = redis::cmd("INFO")
+        .arg("replication")
+        .query_async(con)
+        .await?;
+    info::field(&replication, field)
+        .and_then(|offset| offset.parse().ok())
+        .ok_or_else(|| {
+            (
+                ErrorKind::TypeError,
+                "Missing replication offset in INFO output",
+                field.to_string(),
+            )
+                .into()
+        })
+}
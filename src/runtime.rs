@@ -0,0 +1,204 @@
+//! Minimal runtime abstraction.
+//!
+//! The pool must stay runtime agnostic, yet some features need a timer. The
+//! runtimes available are selected at compile time with the `tokio-comp` and
+//! `async-std-comp` features. When both are enabled, like the redis crate does
+//! for connections, the tokio timer is used when called from within a tokio
+//! runtime, the async-std one otherwise, unless a runtime is explicitly selected.
+
+use std::{
+    future::Future,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use redis::{aio::Connection, RedisResult};
+
+#[cfg(not(any(feature = "tokio-comp", feature = "async-std-comp")))]
+compile_error!("at least one of the `tokio-comp` and `async-std-comp` features must be enabled");
+
+/// The async runtime providing the timers used by the pool timeouts.
+///
+/// By default, the runtime is detected each time a timer is needed: tokio is
+/// used from within a tokio (0.2) runtime, async-std otherwise. Only the
+/// runtimes whose feature (`tokio-comp`, `async-std-comp`) is enabled are available.
+///
+/// ```rust
+/// # #[cfg(all(feature = "tokio-comp", feature = "async-std-comp"))]
+/// # {
+/// use std::time::{Duration, Instant};
+/// use redis_async_pool::{CreateFailurePolicy, RedisConnectionManager, RedisPool};
+///
+/// // nothing listens on port 1: the creation is retried after sleeping 50ms then 100ms
+/// let retried = || async {
+///     let manager = RedisConnectionManager::from_url("redis://127.0.0.1:1", true, None)
+///         .unwrap()
+///         .with_create_failure_policy(CreateFailurePolicy::Retry {
+///             retries: 2,
+///             backoff: Duration::from_millis(50),
+///         });
+///     let pool = RedisPool::new(manager, 1);
+///     let started = Instant::now();
+///     assert!(pool.get().await.is_err());
+///     started.elapsed()
+/// };
+///
+/// let slept = async_std::task::block_on(retried());
+/// assert!(slept >= Duration::from_millis(150));
+/// let mut tokio = tokio::runtime::Builder::new()
+///     .basic_scheduler()
+///     .enable_all()
+///     .build()?;
+/// let slept = tokio.block_on(retried());
+/// assert!(slept >= Duration::from_millis(150));
+/// # }
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Runtime {
+    /// Use tokio timers, the pool must be used from within a tokio runtime
+    #[cfg(feature = "tokio-comp")]
+    Tokio,
+    /// Use async-std timers, which run on any executor
+    #[cfg(feature = "async-std-comp")]
+    AsyncStd,
+}
+
+impl Runtime {
+    /// Detect the runtime the current task runs on.
+    #[cfg(all(feature = "tokio-comp", feature = "async-std-comp"))]
+    pub(crate) fn detect() -> Self {
+        if tokio::runtime::Handle::try_current().is_ok() {
+            Runtime::Tokio
+        } else {
+            Runtime::AsyncStd
+        }
+    }
+
+    /// Only tokio is available.
+    #[cfg(all(feature = "tokio-comp", not(feature = "async-std-comp")))]
+    pub(crate) fn detect() -> Self {
+        Runtime::Tokio
+    }
+
+    /// Only async-std is available.
+    #[cfg(all(not(feature = "tokio-comp"), feature = "async-std-comp"))]
+    pub(crate) fn detect() -> Self {
+        Runtime::AsyncStd
+    }
+
+    /// Check timers of this runtime can be used by the current task.
+    pub(crate) fn check(self) -> RedisResult<()> {
+        match self {
+            #[cfg(feature = "tokio-comp")]
+            Runtime::Tokio if tokio::runtime::Handle::try_current().is_err() => Err((
+                redis::ErrorKind::InvalidClientConfig,
+                "The pool is configured to use the tokio runtime but is not used from within a tokio runtime",
+            )
+                .into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Require `future` to complete before `duration` has elapsed.
+    pub(crate) async fn timeout<F>(
+        self,
+        duration: Duration,
+        future: F,
+    ) -> Result<F::Output, Elapsed>
+    where
+        F: Future,
+    {
+        // a deadline which can not be represented never elapses
+        if Instant::now().checked_add(duration).is_none() {
+            return Ok(future.await);
+        }
+        match self {
+            #[cfg(feature = "tokio-comp")]
+            Runtime::Tokio => tokio::time::timeout(duration, future)
+                .await
+                .map_err(|_| Elapsed),
+            #[cfg(feature = "async-std-comp")]
+            Runtime::AsyncStd => async_std::future::timeout(duration, future)
+                .await
+                .map_err(|_| Elapsed),
+        }
+    }
+
+    /// Open a connection with `client` through the constructor of this runtime.
+    pub(crate) async fn connect(self, client: &redis::Client) -> RedisResult<Connection> {
+        self.check()?;
+        match self {
+            #[cfg(feature = "tokio-comp")]
+            Runtime::Tokio => client.get_tokio_connection_tokio().await,
+            #[cfg(feature = "async-std-comp")]
+            Runtime::AsyncStd => client.get_async_std_connection().await,
+        }
+    }
+
+    /// Run `future` in the background.
+    pub(crate) fn spawn<F>(self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        match self {
+            #[cfg(feature = "tokio-comp")]
+            Runtime::Tokio => drop(tokio::spawn(future)),
+            #[cfg(feature = "async-std-comp")]
+            Runtime::AsyncStd => drop(async_std::task::spawn(future)),
+        }
+    }
+
+    /// Resolve the addresses of `host` with the resolver of this runtime.
+    pub(crate) async fn resolve(self, host: &str, port: u16) -> RedisResult<Vec<SocketAddr>> {
+        self.check()?;
+        let addrs = match self {
+            #[cfg(feature = "tokio-comp")]
+            Runtime::Tokio => tokio::net::lookup_host((host, port))
+                .await
+                .map(Iterator::collect),
+            #[cfg(feature = "async-std-comp")]
+            Runtime::AsyncStd => async_std::net::ToSocketAddrs::to_socket_addrs(&(host, port))
+                .await
+                .map(Iterator::collect),
+        };
+        Ok(addrs?)
+    }
+
+    /// Wait until `duration` has elapsed.
+    pub(crate) async fn sleep(self, duration: Duration) {
+        if Instant::now().checked_add(duration).is_none() {
+            return futures_util::future::pending().await;
+        }
+        match self {
+            #[cfg(feature = "tokio-comp")]
+            Runtime::Tokio => tokio::time::delay_for(duration).await,
+            #[cfg(feature = "async-std-comp")]
+            Runtime::AsyncStd => async_std::task::sleep(duration).await,
+        }
+    }
+}
+
+/// Error returned by [`timeout`] when the duration elapsed before the future completed.
+#[derive(Debug)]
+pub(crate) struct Elapsed;
+
+/// Require `future` to complete before `duration` has elapsed, using the detected runtime.
+pub(crate) async fn timeout<F>(duration: Duration, future: F) -> Result<F::Output, Elapsed>
+where
+    F: Future,
+{
+    Runtime::detect().timeout(duration, future).await
+}
+
+/// Wait until `duration` has elapsed, using the detected runtime.
+pub(crate) async fn sleep(duration: Duration) {
+    Runtime::detect().sleep(duration).await
+}
+
+impl From<Elapsed> for redis::RedisError {
+    fn from(_: Elapsed) -> Self {
+        std::io::Error::new(std::io::ErrorKind::TimedOut, "deadline exceeded").into()
+    }
+}
@@ -0,0 +1,110 @@
+use redis::{ErrorKind, FromRedisValue, RedisResult, Script, ToRedisArgs};
+
+use crate::RedisConnection;
+
+impl RedisConnection {
+    /// Run a Lua `script` with `EVALSHA`, loading it with `SCRIPT LOAD` first if it has
+    /// not been loaded through this connection yet.
+    ///
+    /// The scripts loaded are tracked per connection: the tracking is cleared when the
+    /// connection is reset or reopened (see
+    /// [`RedisConnectionManager::with_reconnect_in_place`](crate::RedisConnectionManager::with_reconnect_in_place)),
+    /// and a script flushed from the server (`NOSCRIPT` error) is loaded again
+    /// transparently.
+    ///
+    /// ```rust,no_run
+    /// # use std::error::Error;
+    /// # use redis_async_pool::{RedisConnectionManager, RedisPool};
+    /// # #[async_std::main]
+    /// # async fn main() -> Result<(), Box<dyn Error>> {
+    /// # let pool = RedisPool::new(
+    /// #     RedisConnectionManager::new(redis::Client::open("redis://localhost:6379")?, true, None),
+    /// #     5,
+    /// # );
+    /// let mut con = pool.get().await?;
+    /// let value: i64 = con
+    ///     .eval_cached("return redis.call('INCRBY', KEYS[1], ARGV[1])", "counter", 2)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::sync::{Arc, Mutex};
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let sent = Arc::new(Mutex::new(Vec::new()));
+    /// let recorded = sent.clone();
+    /// let manager = FakeRedisConnectionManager::new(true, None)?
+    ///     .with_client_id()
+    ///     .with_reconnect_in_place()
+    ///     .with_audit(move |command, _| {
+    ///         if command == "SCRIPT" || command == "EVALSHA" {
+    ///             recorded.lock().unwrap().push(command.to_string());
+    ///         }
+    ///     });
+    /// let pool = RedisPool::new(manager, 2);
+    /// let sent = move || std::mem::take(&mut *sent.lock().unwrap());
+    /// let set = "return redis.call('SET', KEYS[1], ARGV[1])";
+    ///
+    /// let (mut con, mut admin) = (pool.get().await?, pool.get().await?);
+    /// con.eval_cached::<_, _, ()>(set, "key", "first").await?;
+    /// assert_eq!(sent(), ["SCRIPT", "EVALSHA"]);
+    /// con.eval_cached::<_, _, ()>(set, "key", "second").await?;
+    /// assert_eq!(sent(), ["EVALSHA"]);
+    /// assert_eq!(con.get::<_, String>("key").await?, "second");
+    ///
+    /// // flushed from the server
+    /// redis::cmd("SCRIPT").arg("FLUSH").query_async::<_, ()>(&mut *admin).await?;
+    /// sent();
+    /// con.eval_cached::<_, _, ()>(set, "key", "third").await?;
+    /// assert_eq!(sent(), ["EVALSHA", "SCRIPT", "EVALSHA"]);
+    ///
+    /// // the socket of the connection dropped then reopened
+    /// let id = con.id();
+    /// redis::cmd("CLIENT")
+    ///     .arg("KILL")
+    ///     .arg("ID")
+    ///     .arg(con.client_id().unwrap())
+    ///     .query_async::<_, ()>(&mut *admin)
+    ///     .await?;
+    /// drop(con);
+    /// let mut con = pool.get().await?;
+    /// assert_eq!((con.id(), con.reconnect_count()), (id, 1));
+    /// con.eval_cached::<_, _, ()>(set, "key", "fourth").await?;
+    /// assert_eq!(sent(), ["SCRIPT", "EVALSHA"]);
+    /// assert_eq!(con.get::<_, String>("key").await?, "fourth");
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn eval_cached<K, A, T>(&mut self, script: &str, keys: K, args: A) -> RedisResult<T>
+    where
+        K: ToRedisArgs,
+        A: ToRedisArgs,
+        T: FromRedisValue,
+    {
+        let hash = Script::new(script).get_hash().to_string();
+        let keys = keys.to_redis_args();
+        let mut evalsha = redis::cmd("EVALSHA");
+        evalsha.arg(&hash).arg(keys.len()).arg(keys).arg(args);
+        if self.scripts.contains(&hash) {
+            match evalsha.query_async(self).await {
+                Err(err) if err.kind() == ErrorKind::NoScriptError => {
+                    self.scripts.remove(&hash);
+                }
+                result => return result,
+            }
+        }
+        redis::cmd("SCRIPT")
+            .arg("LOAD")
+            .arg(script)
+            .query_async::<_, ()>(self)
+            .await?;
+        self.scripts.insert(hash);
+        evalsha.query_async(self).await
+    }
+}
@@ -0,0 +1,112 @@
+use futures_util::stream::{Stream, StreamExt};
+use redis::{Msg, RedisResult};
+
+use crate::RedisPool;
+
+/// A failover announced by a Redis Sentinel, see [`RedisPool::watch_failover`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failover {
+    /// Name of the master, as monitored by the sentinel
+    pub master_name: String,
+    /// Host of the former master
+    pub old_host: String,
+    /// Port of the former master
+    pub old_port: u16,
+    /// Host of the new master
+    pub new_host: String,
+    /// Port of the new master
+    pub new_port: u16,
+}
+
+impl Failover {
+    /// Parse a `+switch-master` message: `<master name> <old ip> <old port> <new ip> <new port>`
+    fn parse(msg: &Msg) -> Option<Self> {
+        let payload = msg.get_payload::<String>().ok()?;
+        let mut fields = payload.split_whitespace();
+        let failover = Self {
+            master_name: fields.next()?.to_string(),
+            old_host: fields.next()?.to_string(),
+            old_port: fields.next()?.parse().ok()?,
+            new_host: fields.next()?.to_string(),
+            new_port: fields.next()?.parse().ok()?,
+        };
+        Some(failover)
+    }
+}
+
+impl RedisPool {
+    /// Watch the failovers of the master `master_name` announced by a Redis Sentinel
+    /// (`+switch-master`), rolling the pool on each of them (see [`roll`](Self::roll)).
+    ///
+    /// The connections to the former master are then recreated on their next checkout
+    /// rather than failing later: this is useful when the endpoint of the manager follows
+    /// the master (DNS record, proxy...). Otherwise, the yielded [`Failover`] tells the
+    /// address of the new master, to [`reconfigure`](Self::reconfigure) the pool with.
+    ///
+    /// The events are received on a dedicated connection to the `sentinel`; the pool is
+    /// rolled as the stream is polled, so it must be polled continuously, e.g. in a task
+    /// of its own. The stream ends if the connection fails.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use futures_util::stream::StreamExt;
+    /// use redis_async_pool::{FakeRedisConnectionManager, Failover, RedisPool};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 3);
+    /// pool.warm_up(3, 3).await?;
+    /// let rolled: Vec<_> = pool.connections_snapshot().iter().map(|con| con.id).collect();
+    ///
+    /// // a fake server standing for the sentinel
+    /// let sentinel = FakeRedisConnectionManager::new(true, None)?
+    ///     .config_snapshot()
+    ///     .endpoint
+    ///     .client()?;
+    /// let mut failovers = Box::pin(pool.watch_failover(&sentinel, "mymaster").await?);
+    /// let mut announce = sentinel.get_async_connection().await?;
+    /// for master in &["other", "mymaster"] {
+    ///     redis::cmd("PUBLISH")
+    ///         .arg("+switch-master")
+    ///         .arg(format!("{} 10.0.0.1 6379 10.0.0.2 6380", master))
+    ///         .query_async::<_, ()>(&mut announce)
+    ///         .await?;
+    /// }
+    ///
+    /// // the failover of another master is skipped
+    /// let failover = failovers.next().await;
+    /// assert_eq!(
+    ///     failover,
+    ///     Some(Failover {
+    ///         master_name: "mymaster".to_string(),
+    ///         old_host: "10.0.0.1".to_string(),
+    ///         old_port: 6379,
+    ///         new_host: "10.0.0.2".to_string(),
+    ///         new_port: 6380,
+    ///     })
+    /// );
+    /// // every connection handed out afterwards is a new one
+    /// let connections = vec![pool.get().await?, pool.get().await?, pool.get().await?];
+    /// assert!(connections.iter().all(|con| !rolled.contains(&con.id())));
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn watch_failover(
+        &self,
+        sentinel: &redis::Client,
+        master_name: &str,
+    ) -> RedisResult<impl Stream<Item = Failover>> {
+        let mut pubsub = sentinel.get_async_connection().await?.into_pubsub();
+        pubsub.subscribe("+switch-master").await?;
+        let pool = self.clone();
+        let master_name = master_name.to_string();
+        Ok(pubsub.into_on_message().filter_map(move |msg| {
+            let failover =
+                Failover::parse(&msg).filter(|failover| failover.master_name == master_name);
+            if failover.is_some() {
+                pool.roll();
+            }
+            async move { failover }
+        }))
+    }
+}
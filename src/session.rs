@@ -0,0 +1,80 @@
+use std::ops::{Deref, DerefMut};
+
+use deadpool::managed::PoolError;
+use redis::RedisError;
+
+use crate::{PooledConnection, RedisConnection, RedisPool};
+
+/// A connection pinned for a series of commands, obtained with [`RedisPool::session`].
+///
+/// All the commands issued through a session run on the same connection, which makes
+/// it suitable for optimistic locking with `WATCH`/`MULTI`/`EXEC`. Connections are only
+/// evicted (ttl, error rate, checks...) when recycled, so the connection of a session
+/// is never evicted while held. Keys left watched are unwatched once the connection is
+/// given back, before it is handed out again.
+///
+/// ```rust
+/// # #[cfg(feature = "fake")]
+/// # async_std::task::block_on(async {
+/// use redis::AsyncCommands;
+/// use redis_async_pool::{FakeRedisConnectionManager, RedisPool, Session};
+///
+/// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 2);
+/// pool.set_key::<_, _, ()>("counter", 1).await?;
+/// // increment the counter unless it is changed in the meantime
+/// async fn increment(session: &mut Session) -> redis::RedisResult<Option<()>> {
+///     redis::cmd("WATCH").arg("counter").query_async::<_, ()>(&mut **session).await?;
+///     let counter: i64 = session.get("counter").await?;
+///     redis::pipe()
+///         .atomic()
+///         .set("counter", counter + 1)
+///         .ignore()
+///         .query_async(&mut **session)
+///         .await
+/// }
+///
+/// let mut session = pool.session().await?;
+/// assert_eq!(increment(&mut session).await?, Some(()));
+/// assert_eq!(pool.get_key::<_, i64>("counter").await?, 2);
+///
+/// // the transaction is aborted when another connection writes the watched key
+/// let mut other = pool.session().await?;
+/// redis::cmd("WATCH").arg("counter").query_async::<_, ()>(&mut *session).await?;
+/// let counter: i64 = session.get("counter").await?;
+/// other.set::<_, _, ()>("counter", 10).await?;
+/// let committed: Option<()> = redis::pipe()
+///     .atomic()
+///     .set("counter", counter + 1)
+///     .ignore()
+///     .query_async(&mut *session)
+///     .await?;
+/// assert_eq!(committed, None);
+/// assert_eq!(other.get::<_, i64>("counter").await?, 10);
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// # })
+/// # .unwrap();
+/// ```
+pub struct Session {
+    con: PooledConnection,
+}
+
+impl RedisPool {
+    /// Get a connection pinned for a series of commands, see [`Session`].
+    pub async fn session(&self) -> Result<Session, PoolError<RedisError>> {
+        let con = self.get().await?;
+        Ok(Session { con })
+    }
+}
+
+impl Deref for Session {
+    type Target = RedisConnection;
+    fn deref(&self) -> &Self::Target {
+        &self.con
+    }
+}
+
+impl DerefMut for Session {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.con
+    }
+}
@@ -0,0 +1,397 @@
+use std::time::{Duration, Instant};
+
+use deadpool::managed::PoolError;
+use futures_util::future;
+use rand::Rng;
+use redis::{aio::ConnectionLike, Cmd, Pipeline, RedisError, RedisFuture, RedisResult, Value};
+
+use crate::{connection::cmd_name, errors, PooledConnection, RedisConnection, RedisPool};
+
+/// Commands sent to the replicas by a [`SplitConnection`]
+const READ_COMMANDS: &[&str] = &[
+    "BITCOUNT",
+    "BITPOS",
+    "DBSIZE",
+    "EXISTS",
+    "GEODIST",
+    "GEOHASH",
+    "GEOPOS",
+    "GET",
+    "GETBIT",
+    "GETRANGE",
+    "HEXISTS",
+    "HGET",
+    "HGETALL",
+    "HKEYS",
+    "HLEN",
+    "HMGET",
+    "HSCAN",
+    "HSTRLEN",
+    "HVALS",
+    "KEYS",
+    "LINDEX",
+    "LLEN",
+    "LRANGE",
+    "MGET",
+    "PFCOUNT",
+    "PTTL",
+    "RANDOMKEY",
+    "SCAN",
+    "SCARD",
+    "SDIFF",
+    "SINTER",
+    "SISMEMBER",
+    "SMEMBERS",
+    "SRANDMEMBER",
+    "SSCAN",
+    "STRLEN",
+    "SUNION",
+    "TTL",
+    "TYPE",
+    "XLEN",
+    "XRANGE",
+    "XREVRANGE",
+    "ZCARD",
+    "ZCOUNT",
+    "ZLEXCOUNT",
+    "ZRANGE",
+    "ZRANGEBYLEX",
+    "ZRANGEBYSCORE",
+    "ZRANK",
+    "ZREVRANGE",
+    "ZREVRANGEBYLEX",
+    "ZREVRANGEBYSCORE",
+    "ZREVRANK",
+    "ZSCAN",
+    "ZSCORE",
+];
+
+/// A master pool and replicas pools, handing out [`SplitConnection`]s.
+#[derive(Clone)]
+pub struct SplitPool {
+    master: RedisPool,
+    replicas: Vec<RedisPool>,
+}
+
+impl SplitPool {
+    /// Create a pool sending writes to `master` and reads to `replicas`.
+    pub fn new(master: RedisPool, replicas: RedisPool) -> Self {
+        Self::with_replicas(master, vec![replicas])
+    }
+
+    /// Create a pool sending writes to `master` and reads to one of `replicas`, a pool
+    /// per replica.
+    ///
+    /// Each [`SplitConnection`] reads from a replica picked at random. Without replicas,
+    /// reads go to the master.
+    pub fn with_replicas(master: RedisPool, replicas: Vec<RedisPool>) -> Self {
+        Self { master, replicas }
+    }
+
+    /// The pool of connections to the master
+    pub fn master(&self) -> &RedisPool {
+        &self.master
+    }
+
+    /// The pools of connections to the replicas
+    pub fn replicas(&self) -> &[RedisPool] {
+        &self.replicas
+    }
+
+    /// Get a connection routing each command to the master or to the replicas.
+    pub fn connection(&self) -> SplitConnection {
+        let replica = match self.replicas.len() {
+            0 => None,
+            len => Some(rand::thread_rng().gen_range(0, len)),
+        };
+        SplitConnection {
+            pool: self.clone(),
+            replica_pool: replica,
+            master: None,
+            replica: None,
+            pinned: false,
+            read_your_writes: None,
+            last_write: None,
+        }
+    }
+
+    /// Get a connection reading its own writes: after a write, its reads go to the master
+    /// until `read_your_writes_window` elapsed, then to the replicas again.
+    ///
+    /// The window should cover the replication lag of the replicas. Writes issued through
+    /// other connections are not taken into account.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::Duration;
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool, SplitPool};
+    ///
+    /// // fake servers do not replicate: a read sees the write only on the master
+    /// let pool = SplitPool::new(
+    ///     RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 5),
+    ///     RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 5),
+    /// );
+    /// let mut con = pool.session(Duration::from_millis(100));
+    /// con.set::<_, _, ()>("key", "value").await?;
+    /// let value: Option<String> = con.get("key").await?;
+    /// assert_eq!(value.as_deref(), Some("value"));
+    ///
+    /// async_std::task::sleep(Duration::from_millis(150)).await;
+    /// let value: Option<String> = con.get("key").await?;
+    /// assert_eq!(value, None);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn session(&self, read_your_writes_window: Duration) -> SplitConnection {
+        SplitConnection {
+            read_your_writes: Some(read_your_writes_window),
+            ..self.connection()
+        }
+    }
+
+    /// Run `f` on a connection of each replicas pool concurrently, e.g. to warm the
+    /// caches of every replica.
+    ///
+    /// The results are returned in the order of the pools given to
+    /// [`with_replicas`](Self::with_replicas); a replica whose connection can not be
+    /// acquired does not prevent `f` from running on the others.
+    ///
+    /// ```rust,no_run
+    /// # use std::error::Error;
+    /// # use redis::AsyncCommands;
+    /// # use redis_async_pool::{RedisConnectionManager, RedisPool, SplitPool};
+    /// # #[async_std::main]
+    /// # async fn main() -> Result<(), Box<dyn Error>> {
+    /// # let pool = |url| -> redis::RedisResult<RedisPool> {
+    /// #     Ok(RedisPool::new(RedisConnectionManager::from_url(url, true, None)?, 5))
+    /// # };
+    /// let pool = SplitPool::with_replicas(
+    ///     pool("redis://master:6379")?,
+    ///     vec![pool("redis://replica-1:6379")?, pool("redis://replica-2:6379")?],
+    /// );
+    /// let values = pool
+    ///     .broadcast_read(|con| {
+    ///         Box::pin(async move { con.get::<_, Option<String>>("key").await })
+    ///     })
+    ///     .await;
+    /// assert_eq!(values.len(), 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::Duration;
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool, Runtime, SplitPool};
+    ///
+    /// let pool = |size| -> std::io::Result<RedisPool> {
+    ///     Ok(RedisPool::builder(FakeRedisConnectionManager::new(true, None)?)
+    ///         .max_size(size)
+    ///         .with_wait_timeout(Duration::from_millis(50))
+    ///         .with_runtime(Runtime::AsyncStd)
+    ///         .build()
+    ///         .unwrap())
+    /// };
+    /// let replicas = vec![pool(1)?, pool(1)?, pool(1)?];
+    /// for (n, replica) in replicas.iter().enumerate() {
+    ///     replica.get().await?.set::<_, _, ()>("key", n).await?;
+    /// }
+    /// // the second replica has no connection available
+    /// let _busy = replicas[1].get().await?;
+    /// let pool = SplitPool::with_replicas(pool(1)?, replicas);
+    ///
+    /// let values = pool
+    ///     .broadcast_read(|con| Box::pin(async move { con.get::<_, u8>("key").await }))
+    ///     .await;
+    /// assert_eq!(values[0].as_ref().ok(), Some(&0));
+    /// assert!(values[1].is_err());
+    /// assert_eq!(values[2].as_ref().ok(), Some(&2));
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn broadcast_read<T, F>(&self, f: F) -> Vec<Result<T, PoolError<RedisError>>>
+    where
+        F: for<'a> Fn(&'a mut RedisConnection) -> RedisFuture<'a, T>,
+    {
+        let f = &f;
+        future::join_all(self.replicas.iter().map(|pool| async move {
+            let mut con = pool.get().await?;
+            Ok(f(&mut con).await?)
+        }))
+        .await
+    }
+}
+
+/// A connection sending read-only commands to the replicas and the other commands to
+/// the master, created with [`SplitPool::connection`].
+///
+/// A connection of each pool is taken when first needed and kept until this connection
+/// is dropped. A pipeline goes to the replicas when all its commands are read-only.
+/// Between `WATCH` or `MULTI` and `EXEC` or `DISCARD`, every command goes to the master.
+///
+/// Replicas lag behind the master: a read following a write may not see it, unless the
+/// connection is created with [`SplitPool::session`].
+///
+/// ```rust,no_run
+/// # use std::error::Error;
+/// # use redis::AsyncCommands;
+/// # use redis_async_pool::{RedisConnectionManager, RedisPool, SplitPool};
+/// # #[async_std::main]
+/// # async fn main() -> Result<(), Box<dyn Error>> {
+/// let pool = SplitPool::new(
+///     RedisPool::new(
+///         RedisConnectionManager::from_url("redis://master:6379", true, None)?,
+///         5,
+///     ),
+///     RedisPool::new(
+///         RedisConnectionManager::from_url("redis://replicas:6379", true, None)?,
+///         5,
+///     ),
+/// );
+/// let mut con = pool.connection();
+/// con.set::<_, _, ()>("key", "value").await?; // master
+/// let value: Option<String> = con.get("key").await?; // replicas
+/// # Ok(())
+/// # }
+/// ```
+///
+/// ```rust
+/// # #[cfg(feature = "fake")]
+/// # async_std::task::block_on(async {
+/// use redis::AsyncCommands;
+/// use redis_async_pool::{FakeRedisConnectionManager, RedisPool, SplitPool};
+///
+/// // fake servers do not replicate: only the master sees the writes
+/// let pool = SplitPool::new(
+///     RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 5),
+///     RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 5),
+/// );
+/// let mut con = pool.connection();
+/// con.set::<_, _, ()>("key", "master").await?;
+/// pool.replicas()[0].get().await?.set::<_, _, ()>("key", "replica").await?;
+/// let value: String = con.get("key").await?;
+/// assert_eq!(value, "replica");
+///
+/// let (value, exists): (String, bool) = redis::pipe()
+///     .get("key")
+///     .exists("key")
+///     .query_async(&mut con)
+///     .await?;
+/// assert_eq!((value.as_str(), exists), ("replica", true));
+/// let (value, ()): (String, ()) = redis::pipe()
+///     .get("key")
+///     .del("other")
+///     .query_async(&mut con)
+///     .await?;
+/// assert_eq!(value, "master");
+///
+/// // reads of a transaction go to the master
+/// redis::cmd("WATCH").arg("key").query_async::<_, ()>(&mut con).await?;
+/// let value: String = con.get("key").await?;
+/// assert_eq!(value, "master");
+/// redis::cmd("UNWATCH").query_async::<_, ()>(&mut con).await?;
+/// let value: String = con.get("key").await?;
+/// assert_eq!(value, "replica");
+/// assert_eq!(pool.master().stats().size, 1);
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// # })
+/// # .unwrap();
+/// ```
+pub struct SplitConnection {
+    pool: SplitPool,
+    /// Index of the replicas pool reads go to, `None` without replicas
+    replica_pool: Option<usize>,
+    master: Option<PooledConnection>,
+    replica: Option<PooledConnection>,
+    /// Set while a transaction requires every command to go to the master
+    pinned: bool,
+    /// Time reads go to the master after a write, see `SplitPool::session`
+    read_your_writes: Option<Duration>,
+    last_write: Option<Instant>,
+}
+
+/// Whether `cmd` only reads data, so it can be sent to a replica or sent again
+pub(crate) fn is_read(cmd: &Cmd) -> bool {
+    let name = cmd_name(cmd);
+    READ_COMMANDS.binary_search(&name.as_str()).is_ok()
+}
+
+impl SplitConnection {
+    /// Get the connection a request goes to, taking it from its pool if needed
+    async fn route(&mut self, read: bool) -> RedisResult<&mut PooledConnection> {
+        let read = read && !self.reads_own_writes();
+        let (slot, pool) = match self.replica_pool {
+            Some(index) if read && !self.pinned => (&mut self.replica, &self.pool.replicas[index]),
+            _ => (&mut self.master, &self.pool.master),
+        };
+        if slot.is_none() {
+            *slot = Some(pool.get().await.map_err(errors::from_pool_error)?);
+        }
+        Ok(slot.as_mut().unwrap())
+    }
+
+    /// Whether reads go to the master as a write was issued recently
+    fn reads_own_writes(&self) -> bool {
+        match (self.read_your_writes, self.last_write) {
+            (Some(window), Some(last_write)) => last_write.elapsed() < window,
+            _ => false,
+        }
+    }
+
+    /// Track the transactions requiring every command to go to the master
+    fn observe(&mut self, cmd: &Cmd) {
+        match cmd_name(cmd).as_str() {
+            "WATCH" | "MULTI" => self.pinned = true,
+            "EXEC" | "DISCARD" | "UNWATCH" => self.pinned = false,
+            _ => {}
+        }
+    }
+}
+
+impl ConnectionLike for SplitConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        Box::pin(async move {
+            let read = is_read(cmd);
+            let result = self.route(read).await?.req_packed_command(cmd).await;
+            self.observe(cmd);
+            if !read {
+                self.last_write = Some(Instant::now());
+            }
+            result
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        Box::pin(async move {
+            let read = cmd.cmd_iter().all(is_read);
+            let result = self
+                .route(read)
+                .await?
+                .req_packed_commands(cmd, offset, count)
+                .await;
+            cmd.cmd_iter().for_each(|cmd| self.observe(cmd));
+            if !read {
+                self.last_write = Some(Instant::now());
+            }
+            result
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        self.master
+            .as_ref()
+            .or(self.replica.as_ref())
+            .map_or(0, |con| con.actual.get_db())
+    }
+}
@@ -0,0 +1,248 @@
+use std::time::Duration;
+
+use deadpool::managed::PoolError;
+use futures_util::stream::{self, Stream, StreamExt};
+use redis::{
+    streams::{StreamId, StreamReadOptions, StreamReadReply},
+    AsyncCommands, ErrorKind, RedisError,
+};
+
+use crate::{PooledConnection, RedisPool};
+
+/// Consumer of a Redis Stream within a consumer group, created with
+/// [`RedisPool::stream_consumer`].
+///
+/// Entries are read with `XREADGROUP` on a connection dedicated to the consumer: it is
+/// taken from the pool on the first read and kept until the consumer is dropped, so it
+/// holds a pool slot. When the connection fails, it is dropped and a new one is taken
+/// on the next read. Entries are acknowledged (`XACK`) on other pooled connections so
+/// this can be done while a read is blocked.
+///
+/// Reads block up to the `block` duration: it must be shorter than the pause detection
+/// threshold of the manager, if any.
+///
+/// ```rust,no_run
+/// # use std::error::Error;
+/// # use futures_util::stream::StreamExt;
+/// # use redis_async_pool::{RedisConnectionManager, RedisPool};
+/// # #[async_std::main]
+/// # async fn main() -> Result<(), Box<dyn Error>> {
+/// # let pool = RedisPool::new(
+/// #     RedisConnectionManager::new(redis::Client::open("redis://localhost:6379")?, true, None),
+/// #     5,
+/// # );
+/// let consumer = pool.stream_consumer("events", "workers", "worker-1");
+/// consumer.create_group("$").await?;
+/// let acker = consumer.acker();
+/// let mut entries = Box::pin(consumer.entries());
+/// while let Some(entry) = entries.next().await {
+///     let entry = entry?;
+///     // process the entry
+///     acker.ack(&[&entry.id]).await?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// A failed read takes another connection on the next one:
+///
+/// ```rust
+/// # #[cfg(feature = "fake")]
+/// # async_std::task::block_on(async {
+/// use std::time::{Duration, Instant};
+/// use futures_util::stream::StreamExt;
+/// use redis::AsyncCommands;
+/// use redis_async_pool::{ConnectionState, FakeRedisConnectionManager, RedisPool};
+///
+/// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?.with_client_id(), 5);
+/// let mut consumer = pool
+///     .stream_consumer("events", "workers", "worker-1")
+///     .with_block(Duration::from_millis(50))
+///     .with_count(2);
+/// consumer.create_group("$").await?;
+/// consumer.create_group("$").await?;
+/// let acker = consumer.acker();
+///
+/// // a read waits up to the block duration for new entries
+/// let started = Instant::now();
+/// assert!(consumer.read().await?.is_empty());
+/// assert!(started.elapsed() >= Duration::from_millis(50));
+///
+/// let mut con = pool.get().await?;
+/// for n in 0..3 {
+///     let _: String = con.xadd("events", "*", &[("n", n)]).await?;
+/// }
+/// drop(con);
+/// let read = consumer.read().await?;
+/// assert_eq!(read.len(), 2);
+/// assert_eq!(read[0].get::<u8>("n"), Some(0));
+///
+/// // the consumer holds its connection between reads
+/// let snapshot = pool.connections_snapshot();
+/// let held = snapshot.iter().find(|con| con.state == ConnectionState::InUse);
+/// assert!(pool.kill_connection(held.unwrap().id).await?);
+/// assert!(consumer.read().await.is_err());
+///
+/// let mut entries = Box::pin(consumer.entries());
+/// let entry = entries.next().await.unwrap()?;
+/// assert_eq!(entry.get::<u8>("n"), Some(2));
+/// let ids: Vec<_> = read.iter().map(|entry| entry.id.as_str()).collect();
+/// assert_eq!(acker.ack(&ids).await?, 2);
+/// assert_eq!(acker.ack(&[&entry.id]).await?, 1);
+/// assert_eq!(acker.ack(&[&entry.id]).await?, 0);
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// # })
+/// # .unwrap();
+/// ```
+pub struct StreamConsumer {
+    acker: StreamAcker,
+    consumer: String,
+    block: Duration,
+    count: usize,
+    con: Option<PooledConnection>,
+}
+
+/// Acknowledges the entries read by a [`StreamConsumer`], see [`StreamConsumer::acker`].
+#[derive(Clone)]
+pub struct StreamAcker {
+    pool: RedisPool,
+    stream: String,
+    group: String,
+}
+
+impl RedisPool {
+    /// Create a consumer named `consumer` of the group `group` of the stream `stream`.
+    ///
+    /// Reads block up to 5 seconds and return up to 100 entries by default.
+    pub fn stream_consumer(&self, stream: &str, group: &str, consumer: &str) -> StreamConsumer {
+        StreamConsumer {
+            acker: StreamAcker {
+                pool: self.clone(),
+                stream: stream.to_string(),
+                group: group.to_string(),
+            },
+            consumer: consumer.to_string(),
+            block: Duration::from_secs(5),
+            count: 100,
+            con: None,
+        }
+    }
+}
+
+impl StreamConsumer {
+    /// Set how long a read waits for new entries.
+    pub fn with_block(mut self, block: Duration) -> Self {
+        self.block = block;
+        self
+    }
+
+    /// Set the maximum number of entries returned by a read.
+    pub fn with_count(mut self, count: usize) -> Self {
+        self.count = count;
+        self
+    }
+
+    /// Create the consumer group, starting at the entry `start_id` (`$` for new entries only),
+    /// and the stream if it does not exist (`XGROUP CREATE ... MKSTREAM`).
+    ///
+    /// Nothing is done if the group already exists.
+    pub async fn create_group(&self, start_id: &str) -> Result<(), PoolError<RedisError>> {
+        let mut con = self.acker.pool.get().await?;
+        let created: Result<(), _> = con
+            .xgroup_create_mkstream(&self.acker.stream, &self.acker.group, start_id)
+            .await;
+        match created {
+            Err(err) if err.code() == Some("BUSYGROUP") => Ok(()),
+            created => Ok(created?),
+        }
+    }
+
+    /// Read the entries never delivered to the group, waiting for them up to the `block`
+    /// duration; the result is empty when no entry came.
+    ///
+    /// A read cancelled while blocked, e.g. when the task of the consumer is stopped,
+    /// leaves its reply pending: the connection is then dropped instead of being reused.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use std::time::Duration;
+    /// use redis::AsyncCommands;
+    /// use redis_async_pool::{FakeRedisConnectionManager, RedisPool};
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 1);
+    /// let mut consumer = pool
+    ///     .stream_consumer("events", "workers", "worker-1")
+    ///     .with_block(Duration::from_secs(1));
+    /// consumer.create_group("$").await?;
+    /// let read = async_std::future::timeout(Duration::from_millis(50), consumer.read()).await;
+    /// assert!(read.is_err());
+    /// drop(consumer);
+    ///
+    /// let mut con = pool.get().await?;
+    /// con.set::<_, _, ()>("key", "value").await?;
+    /// assert_eq!(con.get::<_, String>("key").await?, "value");
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub async fn read(&mut self) -> Result<Vec<StreamId>, PoolError<RedisError>> {
+        if matches!(&self.con, Some(con) if con.broken) {
+            // a previous read was cancelled while waiting for its reply
+            self.con = None;
+        }
+        let con = match &mut self.con {
+            Some(con) => con,
+            None => self.con.get_or_insert(self.acker.pool.get().await?),
+        };
+        let options = StreamReadOptions::default()
+            .group(&self.acker.group, &self.consumer)
+            .count(self.count)
+            .block(self.block.as_millis() as usize);
+        let read: Result<StreamReadReply, _> = con
+            .xread_options(&[&self.acker.stream], &[">"], options)
+            .await;
+        match read {
+            Ok(reply) => Ok(reply.keys.into_iter().flat_map(|key| key.ids).collect()),
+            Err(err) => {
+                if err.kind() != ErrorKind::ResponseError || con.broken {
+                    // take another connection on the next read
+                    self.con = None;
+                }
+                Err(PoolError::Backend(err))
+            }
+        }
+    }
+
+    /// Get a handle acknowledging the entries read by this consumer.
+    pub fn acker(&self) -> StreamAcker {
+        self.acker.clone()
+    }
+
+    /// Acknowledge the entries `ids` (`XACK`), returning the number of entries acknowledged.
+    pub async fn ack(&self, ids: &[&str]) -> Result<usize, PoolError<RedisError>> {
+        self.acker.ack(ids).await
+    }
+
+    /// Turn this consumer into a stream of entries, read in batches.
+    ///
+    /// Errors are yielded and the reading goes on: stop consuming the stream to stop it.
+    pub fn entries(self) -> impl Stream<Item = Result<StreamId, PoolError<RedisError>>> {
+        stream::unfold(self, |mut consumer| async move {
+            let batch = consumer.read().await;
+            Some((batch, consumer))
+        })
+        .flat_map(|batch| match batch {
+            Ok(entries) => stream::iter(entries.into_iter().map(Ok).collect::<Vec<_>>()),
+            Err(err) => stream::iter(vec![Err(err)]),
+        })
+    }
+}
+
+impl StreamAcker {
+    /// Acknowledge the entries `ids` (`XACK`), returning the number of entries acknowledged.
+    pub async fn ack(&self, ids: &[&str]) -> Result<usize, PoolError<RedisError>> {
+        let mut con = self.pool.get().await?;
+        Ok(con.xack(&self.stream, &self.group, ids).await?)
+    }
+}
@@ -0,0 +1,134 @@
+use std::marker::PhantomData;
+
+use redis::{AsyncCommands, RedisResult, ToRedisArgs};
+
+use crate::RedisConnection;
+
+/// Encoding of values stored in Redis, used by [`TypedConnection`].
+///
+/// Implement it for the types stored as Redis strings (Protobuf messages, JSON
+/// documents...). Encoding or decoding errors should be reported as
+/// `ErrorKind::TypeError` errors.
+///
+/// ```rust
+/// use redis::{ErrorKind, RedisResult};
+/// use redis_async_pool::Codec;
+///
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// impl Codec for Point {
+///     fn encode(&self) -> RedisResult<Vec<u8>> {
+///         Ok(format!("{},{}", self.x, self.y).into_bytes())
+///     }
+///
+///     fn decode(bytes: &[u8]) -> RedisResult<Self> {
+///         let invalid = || (ErrorKind::TypeError, "Invalid point").into();
+///         let text = std::str::from_utf8(bytes).map_err(|_| invalid())?;
+///         let mut coordinates = text.split(',').map(|c| c.parse().map_err(|_| invalid()));
+///         match (coordinates.next(), coordinates.next(), coordinates.next()) {
+///             (Some(x), Some(y), None) => Ok(Point { x: x?, y: y? }),
+///             _ => Err(invalid()),
+///         }
+///     }
+/// }
+/// ```
+pub trait Codec: Sized {
+    /// Encode the value into the bytes stored in Redis
+    fn encode(&self) -> RedisResult<Vec<u8>>;
+    /// Decode a value from the bytes stored in Redis
+    fn decode(bytes: &[u8]) -> RedisResult<Self>;
+}
+
+/// A view of a [`RedisConnection`] storing values of type `T` encoded with its [`Codec`].
+///
+/// Created with [`RedisConnection::typed`].
+pub struct TypedConnection<'a, T> {
+    conn: &'a mut RedisConnection,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl RedisConnection {
+    /// Get a view of this connection reading & writing values of type `T`.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake")]
+    /// # async_std::task::block_on(async {
+    /// use redis::{AsyncCommands, ErrorKind, RedisResult};
+    /// use redis_async_pool::{Codec, FakeRedisConnectionManager, RedisPool};
+    ///
+    /// // stored as a JSON object
+    /// #[derive(Debug, PartialEq)]
+    /// struct Point {
+    ///     x: i32,
+    ///     y: i32,
+    /// }
+    ///
+    /// impl Codec for Point {
+    ///     fn encode(&self) -> RedisResult<Vec<u8>> {
+    ///         Ok(format!(r#"{{"x":{},"y":{}}}"#, self.x, self.y).into_bytes())
+    ///     }
+    ///
+    ///     fn decode(bytes: &[u8]) -> RedisResult<Self> {
+    ///         let invalid = || (ErrorKind::TypeError, "Invalid point").into();
+    ///         let json = std::str::from_utf8(bytes).map_err(|_| invalid())?;
+    ///         let fields = json.strip_prefix(r#"{"x":"#).and_then(|json| json.strip_suffix('}'));
+    ///         let (x, y) = fields.and_then(|fields| fields.split_once(r#","y":"#)).ok_or_else(invalid)?;
+    ///         match (x.parse(), y.parse()) {
+    ///             (Ok(x), Ok(y)) => Ok(Point { x, y }),
+    ///             _ => Err(invalid()),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let pool = RedisPool::new(FakeRedisConnectionManager::new(true, None)?, 1);
+    /// let mut con = pool.get().await?;
+    /// let mut points = con.typed::<Point>();
+    /// points.set_typed("origin", &Point { x: 0, y: -1 }).await?;
+    /// assert_eq!(points.get_typed("origin").await?, Some(Point { x: 0, y: -1 }));
+    /// assert_eq!(points.get_typed("missing").await?, None);
+    ///
+    /// // the encoded value is stored as a string
+    /// let json: String = con.get("origin").await?;
+    /// assert_eq!(json, r#"{"x":0,"y":-1}"#);
+    /// con.set::<_, _, ()>("origin", "0,-1").await?;
+    /// let err = con.typed::<Point>().get_typed("origin").await.unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::TypeError);
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// # })
+    /// # .unwrap();
+    /// ```
+    pub fn typed<T: Codec>(&mut self) -> TypedConnection<'_, T> {
+        TypedConnection {
+            conn: self,
+            _value: PhantomData,
+        }
+    }
+}
+
+impl<T: Codec> TypedConnection<'_, T> {
+    /// Encode `value` and store it at `key` (`SET`).
+    pub async fn set_typed<K>(&mut self, key: K, value: &T) -> RedisResult<()>
+    where
+        K: ToRedisArgs + Send + Sync,
+    {
+        let bytes = value.encode()?;
+        self.conn.set(key, bytes).await
+    }
+
+    /// Get the value stored at `key` and decode it (`GET`), `None` if the key does not exist.
+    pub async fn get_typed<K>(&mut self, key: K) -> RedisResult<Option<T>>
+    where
+        K: ToRedisArgs + Send + Sync,
+    {
+        let bytes: Option<Vec<u8>> = self.conn.get(key).await?;
+        bytes.as_deref().map(T::decode).transpose()
+    }
+
+    /// The underlying connection
+    pub fn connection(&mut self) -> &mut RedisConnection {
+        self.conn
+    }
+}